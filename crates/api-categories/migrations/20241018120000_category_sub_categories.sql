@@ -0,0 +1,9 @@
+-- sub_categories is maintained by the api from parent_id, repair rows that drifted
+update category c set sub_categories = coalesce(
+    (
+        select array_agg(child.id order by child.created_at, child.id)
+        from category child
+        where child.parent_id = c.id
+    ),
+    '{}'
+);
@@ -0,0 +1,3 @@
+alter table category add column deleted_at timestamptz; -- set when the category is archived
+
+create index idx_category_deleted_at on category (deleted_at);
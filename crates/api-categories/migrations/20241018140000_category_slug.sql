@@ -0,0 +1,51 @@
+alter table category add column slug varchar; -- url friendly name, unique among siblings
+
+update category set slug = coalesce(
+    nullif(trim(both '-' from regexp_replace(lower(name), '[^a-z0-9]+', '-', 'g')), ''),
+    'category'
+);
+
+-- the oldest of siblings that share a slug keeps it, the others get the first numbered suffix no
+-- sibling uses yet, as slugs are deduplicated at runtime
+do $$
+declare
+    duplicate record;
+    n integer;
+begin
+    for duplicate in
+        select id, parent_id, slug from (
+            select id, parent_id, slug, created_at,
+                row_number() over (partition by parent_id, slug order by created_at, id) as rank
+            from category
+        ) ranked
+        where rank > 1
+        order by created_at, id
+    loop
+        n := 2;
+        while exists (
+            select 1 from category
+            where parent_id is not distinct from duplicate.parent_id
+                and slug = duplicate.slug || '-' || n
+        ) loop
+            n := n + 1;
+        end loop;
+
+        update category set slug = duplicate.slug || '-' || n where id = duplicate.id;
+    end loop;
+end
+$$;
+
+alter table category alter column slug set not null;
+
+create unique index idx_category_parent_slug on category (parent_id, slug) nulls not distinct;
+
+-- previous slugs of a category, so old links keep resolving
+create table category_slug_alias (
+    parent_id varchar(21) references category(id) on delete cascade, -- parent at the time of the alias
+    slug varchar not null,
+    category_id varchar(21) not null references category(id) on delete cascade,
+    created_at timestamptz default current_timestamp not null
+);
+
+create unique index idx_category_slug_alias_parent_slug on category_slug_alias (parent_id, slug) nulls not distinct;
+create index idx_category_slug_alias_category_id on category_slug_alias (category_id);
@@ -0,0 +1,10 @@
+alter table category add column description varchar; -- optional description in the default language
+
+-- names and descriptions in other languages
+create table category_translation (
+    category_id varchar(21) not null references category(id) on delete cascade,
+    locale varchar(35) not null, -- lowercase language tag, e.g. fr or pt-br
+    name varchar not null,
+    description varchar,
+    primary key (category_id, locale)
+);
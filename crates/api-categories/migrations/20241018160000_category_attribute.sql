@@ -0,0 +1,26 @@
+-- attributes sellers fill in when listing in a category, sub categories inherit them
+create table category_attribute (
+    id varchar(21) primary key,
+    category_id varchar(21) not null references category(id) on delete cascade,
+    name varchar not null,
+    attribute_type varchar not null check (attribute_type in ('text', 'number', 'boolean', 'enum')),
+    unit varchar, -- optional unit of the value
+    required boolean not null default false,
+    created_at timestamptz default current_timestamp not null,
+    updated_at timestamptz default current_timestamp not null
+);
+
+create unique index idx_category_attribute_name on category_attribute (category_id, lower(name));
+
+create trigger set_updated_at
+before update on category_attribute
+for each row
+execute function update_updated_at();
+
+-- values to choose from for enum attributes
+create table category_attribute_value (
+    attribute_id varchar(21) not null references category_attribute(id) on delete cascade,
+    value varchar not null,
+    position int not null, -- order the values are shown in
+    primary key (attribute_id, value)
+);
@@ -0,0 +1,18 @@
+alter table category add column version bigint not null default 1; -- increases whenever the category changes
+
+-- sub_categories follow from the children, adding a child does not change the category itself
+create or replace function increment_version()
+returns trigger as $$
+begin
+    if (to_jsonb(new) - 'sub_categories' - 'updated_at' - 'version')
+        is distinct from (to_jsonb(old) - 'sub_categories' - 'updated_at' - 'version') then
+        new.version = old.version + 1;
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger set_version
+before update on category
+for each row
+execute function increment_version();
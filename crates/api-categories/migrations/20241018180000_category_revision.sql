@@ -0,0 +1,69 @@
+-- every change made to a category, one revision per category per transaction
+create table category_revision (
+    id bigint generated always as identity primary key,
+    category_id varchar(21) not null, -- not a foreign key, the history outlives a purged category
+    operation varchar(16) not null check (
+        operation in ('create', 'update', 'move', 'delete', 'restore', 'purge', 'revert')
+    ),
+    before jsonb, -- null when the category was created
+    after jsonb, -- null when the category was purged
+    actor varchar, -- who made the change, when known
+    reverted_from bigint references category_revision(id) on delete set null,
+    transaction_id bigint not null default txid_current(),
+    created_at timestamptz not null default now()
+);
+
+create index category_revision_category_id on category_revision (category_id, id);
+create unique index category_revision_transaction on category_revision (transaction_id, category_id);
+
+create or replace function category_revision_operation(before jsonb, after jsonb, reverted_from bigint)
+returns varchar as $$
+begin
+    return case
+        when reverted_from is not null then 'revert'
+        when before is null then 'create'
+        when after is null then 'purge'
+        when before ->> 'deleted_at' is null and after ->> 'deleted_at' is not null then 'delete'
+        when before ->> 'deleted_at' is not null and after ->> 'deleted_at' is null then 'restore'
+        when before ->> 'parent_id' is distinct from after ->> 'parent_id' then 'move'
+        else 'update'
+    end;
+end;
+$$ language plpgsql immutable;
+
+-- the api sets sellershut.actor and sellershut.reverted_from for the transaction
+create or replace function record_category_revision()
+returns trigger as $$
+declare
+    before_row jsonb := case when tg_op = 'INSERT' then null else to_jsonb(old) end;
+    after_row jsonb := case when tg_op = 'DELETE' then null else to_jsonb(new) end;
+    reverted_from bigint := nullif(current_setting('sellershut.reverted_from', true), '')::bigint;
+    ignored text[] := array['sub_categories', 'updated_at', 'version'];
+begin
+    -- sub_categories follow from the children, they are not a change to the category itself
+    if tg_op = 'UPDATE' and (before_row - ignored) = (after_row - ignored) then
+        return null;
+    end if;
+
+    -- a mutation can touch a row more than once, the revision spans from the first to the last
+    insert into category_revision (category_id, operation, before, after, actor, reverted_from)
+    values (
+        coalesce(new.id, old.id),
+        category_revision_operation(before_row, after_row, reverted_from),
+        before_row,
+        after_row,
+        nullif(current_setting('sellershut.actor', true), ''),
+        reverted_from
+    )
+    on conflict (transaction_id, category_id) do update set
+        after = excluded.after,
+        operation = category_revision_operation(category_revision.before, excluded.after, excluded.reverted_from);
+
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger record_revision
+after insert or update or delete on category
+for each row
+execute function record_category_revision();
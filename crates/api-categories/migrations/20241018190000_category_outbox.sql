@@ -0,0 +1,13 @@
+-- events written in the same transaction as the change they describe, relayed to jetstream
+create table category_outbox (
+    id bigint generated always as identity primary key,
+    subject varchar not null,
+    payload bytea not null,
+    headers jsonb not null default '{}', -- trace context of the mutation that wrote the event
+    attempts integer not null default 0,
+    last_error varchar,
+    created_at timestamptz not null default now(),
+    sent_at timestamptz
+);
+
+create index category_outbox_unsent on category_outbox (id) where sent_at is null;
@@ -0,0 +1,3 @@
+alter table category add column external_id varchar; -- identifier in another system, such as an imported taxonomy
+
+create unique index idx_category_external_id on category (external_id) where external_id is not null;
@@ -1,8 +1,28 @@
-use async_graphql::{InputObject, SimpleObject};
-use sellershut_core::google::protobuf::Timestamp;
+use std::collections::HashSet;
+
+use async_graphql::{
+    connection::{Connection, Edge, EmptyFields},
+    dataloader::DataLoader,
+    ComplexObject, Context, Enum, InputObject, Result, SimpleObject,
+};
+use sellershut_core::{
+    common::pagination::{
+        cursor::{cursor_value::CursorType, Index},
+        CursorBuilder,
+    },
+    google::protobuf::Timestamp,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, types::time::OffsetDateTime};
 
+use crate::{
+    api::{
+        error::map_status,
+        query::{page_complexity, ConnectionFields, Params},
+    },
+    state::{cursor_signer, ApiState, CategoryLoader},
+};
+
 fn default_time() -> OffsetDateTime {
     OffsetDateTime::now_utc()
 }
@@ -10,13 +30,16 @@ fn default_time() -> OffsetDateTime {
 #[derive(
     SimpleObject, InputObject, FromRow, Debug, Serialize, Deserialize, PartialEq, Eq, Clone,
 )]
-#[graphql(input_name = "CategoryInput")]
+#[graphql(input_name = "CategoryInput", complex)]
 #[cfg_attr(test, derive(fake::Dummy))]
 pub struct Category {
     #[graphql(skip_input)]
     pub id: String,
     pub name: String,
     #[graphql(default)]
+    pub slug: String,
+    pub description: Option<String>,
+    #[graphql(default)]
     pub sub_categories: Vec<String>,
     pub image_url: Option<String>,
     #[cfg_attr(test, dummy(default))]
@@ -25,6 +48,123 @@ pub struct Category {
     pub created_at: OffsetDateTime,
     #[graphql(default_with = "default_time()")]
     pub updated_at: OffsetDateTime,
+    #[graphql(skip_input)]
+    #[cfg_attr(test, dummy(default))]
+    pub deleted_at: Option<OffsetDateTime>,
+    /// Increases whenever the category changes. Updates pass the version that was read, and are
+    /// rejected if the category changed in the meantime
+    #[graphql(default)]
+    #[cfg_attr(test, dummy(default))]
+    pub version: i64,
+    /// An identifier in another system, such as an imported taxonomy. Unique when set
+    #[cfg_attr(test, dummy(default))]
+    pub external_id: Option<String>,
+}
+
+#[ComplexObject]
+impl Category {
+    /// The direct parent, unset for top-level categories
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        let Some(ref parent_id) = self.parent_id else {
+            return Ok(None);
+        };
+
+        let loader = ctx.data::<DataLoader<CategoryLoader>>()?;
+        loader.load_one(parent_id.clone()).await
+    }
+
+    /// The categories above this one, from the top-level category down to the direct parent
+    async fn ancestors(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let loader = ctx.data::<DataLoader<CategoryLoader>>()?;
+
+        let mut ancestors = vec![];
+        let mut seen = HashSet::from([self.id.clone()]);
+        let mut parent_id = self.parent_id.clone();
+
+        // one level at a time, loads of categories at the same depth are batched together
+        while let Some(id) = parent_id.filter(|id| seen.insert(id.clone())) {
+            let Some(parent) = loader.load_one(id).await? else {
+                break;
+            };
+            parent_id = parent.parent_id.clone();
+            ancestors.push(parent);
+        }
+
+        ancestors.reverse();
+        Ok(ancestors)
+    }
+
+    /// The sub categories, in the order they were added
+    #[graphql(complexity = "page_complexity(first.or(last), child_complexity)")]
+    async fn children(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 1))] after: Option<String>,
+        #[graphql(validator(min_length = 1))] before: Option<String>,
+        #[graphql(validator(minimum = 1, maximum = 100))] first: Option<i32>,
+        #[graphql(validator(minimum = 1, maximum = 100))] last: Option<i32>,
+    ) -> Result<Connection<String, Category, ConnectionFields, EmptyFields>> {
+        let pagination = Params::parse(after, before, first, last)?;
+        let count = self.sub_categories.len();
+
+        // signed like the other cursors, they hold the child and its position in sub_categories
+        let signer = cursor_signer(&ctx.data::<ApiState>()?.state);
+        let scope = format!("children:{}", self.id);
+
+        let (mut start, mut end) = (0, count);
+        if let Some(ref cursor_type) = pagination.cursor_value.and_then(|value| value.cursor_type) {
+            let cursor = CursorBuilder::decode(cursor_type, &signer, scope.as_bytes())
+                .map_err(|e| map_status(tonic::Status::invalid_argument(e.to_string())))?;
+            let found = self.sub_categories.iter().position(|id| id == cursor.id());
+            // a child that is gone since leaves its position to the next one
+            let position = match found {
+                Some(position) => position,
+                None => cursor
+                    .key()
+                    .parse::<usize>()
+                    .map(|position| position.min(count))
+                    .map_err(|_| {
+                        map_status(tonic::Status::invalid_argument("cursor is malformed"))
+                    })?,
+            };
+
+            match cursor_type {
+                CursorType::After(_) => {
+                    start = (position + usize::from(found.is_some())).min(count)
+                }
+                CursorType::Before(_) => end = position,
+            }
+        }
+        match pagination.index {
+            Some(Index::First(first)) => end = end.min(start + first as usize),
+            Some(Index::Last(last)) => start = start.max(end.saturating_sub(last as usize)),
+            None => {}
+        }
+        let end = end.max(start);
+
+        let ids = &self.sub_categories[start..end];
+        let loader = ctx.data::<DataLoader<CategoryLoader>>()?;
+        let mut children = loader.load_many(ids.iter().cloned()).await?;
+
+        let mut connection = Connection::with_additional_fields(
+            start > 0,
+            end < count,
+            ConnectionFields {
+                total_count: Some(count as i64),
+            },
+        );
+        connection.edges = (start..end)
+            .zip(ids)
+            .filter_map(|(position, id)| {
+                let cursor = CursorBuilder::new(id, &position.to_string());
+                children
+                    .remove(id)
+                    .map(|child| Edge::new(cursor.encode(&signer, scope.as_bytes()), child))
+            })
+            .collect();
+
+        Ok(connection)
+    }
 }
 
 pub fn to_offset_datetime(timestamp: Option<Timestamp>) -> async_graphql::Result<OffsetDateTime> {
@@ -46,15 +186,389 @@ impl TryFrom<sellershut_core::categories::Category> for Category {
         Ok(Self {
             id: value.id,
             name: value.name,
+            slug: value.slug,
+            description: value.description,
             sub_categories: value.sub_categories,
             image_url: value.image_url,
             parent_id: value.parent_id,
             created_at: to_offset_datetime(value.created_at)?,
             updated_at: to_offset_datetime(value.updated_at)?,
+            deleted_at: value
+                .deleted_at
+                .map(|timestamp| to_offset_datetime(Some(timestamp)))
+                .transpose()?,
+            version: value.version,
+            external_id: value.external_id,
         })
     }
 }
 
+/// The name and description of a category in another language
+#[derive(SimpleObject, InputObject, Debug, Clone, PartialEq, Eq)]
+#[graphql(input_name = "CategoryTranslationInput")]
+pub struct CategoryTranslation {
+    /// Language tag, such as "fr" or "pt-BR"
+    pub locale: String,
+    /// Name in this language
+    pub name: String,
+    /// Description in this language
+    pub description: Option<String>,
+}
+
+impl From<sellershut_core::categories::CategoryTranslation> for CategoryTranslation {
+    fn from(value: sellershut_core::categories::CategoryTranslation) -> Self {
+        Self {
+            locale: value.locale,
+            name: value.name,
+            description: value.description,
+        }
+    }
+}
+
+impl From<CategoryTranslation> for sellershut_core::categories::CategoryTranslation {
+    fn from(value: CategoryTranslation) -> Self {
+        Self {
+            locale: value.locale,
+            name: value.name,
+            description: value.description,
+        }
+    }
+}
+
+/// The type of value an attribute takes
+#[derive(Enum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeType {
+    /// Free text
+    #[default]
+    Text,
+    /// A number, optionally with a unit
+    Number,
+    /// Yes or no
+    Boolean,
+    /// One of the allowed values
+    Enum,
+}
+
+impl From<AttributeType> for sellershut_core::categories::AttributeType {
+    fn from(value: AttributeType) -> Self {
+        match value {
+            AttributeType::Text => Self::Text,
+            AttributeType::Number => Self::Number,
+            AttributeType::Boolean => Self::Boolean,
+            AttributeType::Enum => Self::Enum,
+        }
+    }
+}
+
+impl From<sellershut_core::categories::AttributeType> for AttributeType {
+    fn from(value: sellershut_core::categories::AttributeType) -> Self {
+        match value {
+            sellershut_core::categories::AttributeType::Text => Self::Text,
+            sellershut_core::categories::AttributeType::Number => Self::Number,
+            sellershut_core::categories::AttributeType::Boolean => Self::Boolean,
+            sellershut_core::categories::AttributeType::Enum => Self::Enum,
+        }
+    }
+}
+
+/// An attribute sellers fill in when listing in a category, sub categories inherit it
+#[derive(SimpleObject, InputObject, Debug, Clone, PartialEq, Eq)]
+#[graphql(input_name = "CategoryAttributeInput")]
+pub struct CategoryAttribute {
+    #[graphql(skip_input)]
+    pub id: String,
+    /// The category the attribute is defined on
+    #[graphql(skip_input)]
+    pub category_id: String,
+    /// Unique within a category. Overrides an inherited attribute of the same name
+    pub name: String,
+    pub attribute_type: AttributeType,
+    /// Values to choose from, only for enum attributes
+    #[graphql(default)]
+    pub allowed_values: Vec<String>,
+    pub unit: Option<String>,
+    /// Whether a listing must have a value for the attribute
+    #[graphql(default)]
+    pub required: bool,
+}
+
+impl TryFrom<sellershut_core::categories::CategoryAttribute> for CategoryAttribute {
+    type Error = async_graphql::Error;
+
+    fn try_from(
+        value: sellershut_core::categories::CategoryAttribute,
+    ) -> async_graphql::Result<Self> {
+        let attribute_type =
+            sellershut_core::categories::AttributeType::try_from(value.attribute_type)
+                .map_err(|_| tonic::Status::internal("unknown attribute type"))?;
+
+        Ok(Self {
+            id: value.id,
+            category_id: value.category_id,
+            name: value.name,
+            attribute_type: attribute_type.into(),
+            allowed_values: value.allowed_values,
+            unit: value.unit,
+            required: value.required,
+        })
+    }
+}
+
+impl From<CategoryAttribute> for sellershut_core::categories::CategoryAttribute {
+    fn from(value: CategoryAttribute) -> Self {
+        Self {
+            id: value.id,
+            category_id: value.category_id,
+            name: value.name,
+            attribute_type: sellershut_core::categories::AttributeType::from(value.attribute_type)
+                .into(),
+            allowed_values: value.allowed_values,
+            unit: value.unit,
+            required: value.required,
+        }
+    }
+}
+
+/// A category and its descendants
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub struct CategoryTreeNode {
+    pub category: Category,
+    pub children: Vec<CategoryTreeNode>,
+}
+
+impl TryFrom<sellershut_core::categories::CategoryTreeNode> for CategoryTreeNode {
+    type Error = async_graphql::Error;
+
+    fn try_from(
+        value: sellershut_core::categories::CategoryTreeNode,
+    ) -> async_graphql::Result<Self> {
+        let category = value
+            .category
+            .ok_or(tonic::Status::internal("tree node is missing a category"))?;
+
+        Ok(Self {
+            category: Category::try_from(category)?,
+            children: value
+                .children
+                .into_iter()
+                .map(CategoryTreeNode::try_from)
+                .collect::<async_graphql::Result<_>>()?,
+        })
+    }
+}
+
+/// What happens to the children of a deleted category
+#[derive(Enum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteStrategy {
+    /// Delete the children along with the category
+    #[default]
+    Cascade,
+    /// Move the children to the parent of the deleted category
+    Reparent,
+    /// Refuse to delete a category that has children
+    Restrict,
+}
+
+impl From<DeleteStrategy> for sellershut_core::categories::DeleteStrategy {
+    fn from(value: DeleteStrategy) -> Self {
+        match value {
+            DeleteStrategy::Cascade => Self::Cascade,
+            DeleteStrategy::Reparent => Self::Reparent,
+            DeleteStrategy::Restrict => Self::Restrict,
+        }
+    }
+}
+
+/// Categories affected by a delete
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub struct DeleteCategoryResponse {
+    /// Categories that were deleted
+    pub deleted: Vec<Category>,
+    /// Children that were moved to the parent of the deleted category
+    pub reparented: Vec<Category>,
+}
+
+impl TryFrom<sellershut_core::categories::DeleteCategoryResponse> for DeleteCategoryResponse {
+    type Error = async_graphql::Error;
+
+    fn try_from(
+        value: sellershut_core::categories::DeleteCategoryResponse,
+    ) -> async_graphql::Result<Self> {
+        Ok(Self {
+            deleted: value
+                .deleted
+                .into_iter()
+                .map(Category::try_from)
+                .collect::<async_graphql::Result<_>>()?,
+            reparented: value
+                .reparented
+                .into_iter()
+                .map(Category::try_from)
+                .collect::<async_graphql::Result<_>>()?,
+        })
+    }
+}
+
+/// What was done to a category in a revision
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevisionOperation {
+    /// The category was created
+    Create,
+    /// The category was updated in place
+    Update,
+    /// The category was moved under a different parent
+    Move,
+    /// The category was archived
+    Delete,
+    /// The category was restored from the archive
+    Restore,
+    /// The category was permanently deleted
+    Purge,
+    /// The category was returned to an earlier revision
+    Revert,
+}
+
+impl From<sellershut_core::categories::RevisionOperation> for RevisionOperation {
+    fn from(value: sellershut_core::categories::RevisionOperation) -> Self {
+        match value {
+            sellershut_core::categories::RevisionOperation::Create => Self::Create,
+            sellershut_core::categories::RevisionOperation::Update => Self::Update,
+            sellershut_core::categories::RevisionOperation::Move => Self::Move,
+            sellershut_core::categories::RevisionOperation::Delete => Self::Delete,
+            sellershut_core::categories::RevisionOperation::Restore => Self::Restore,
+            sellershut_core::categories::RevisionOperation::Purge => Self::Purge,
+            sellershut_core::categories::RevisionOperation::Revert => Self::Revert,
+        }
+    }
+}
+
+/// A recorded change to a category
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub struct CategoryRevision {
+    /// Later revisions have higher IDs
+    pub id: i64,
+    pub category_id: String,
+    pub operation: RevisionOperation,
+    /// The category before the change, missing when it was created
+    pub before: Option<Category>,
+    /// The category after the change, missing when it was purged
+    pub after: Option<Category>,
+    /// Who made the change, when known
+    pub actor: Option<String>,
+    pub created_at: OffsetDateTime,
+    /// The revision this change reverted to
+    pub reverted_from: Option<i64>,
+}
+
+impl TryFrom<sellershut_core::categories::CategoryRevision> for CategoryRevision {
+    type Error = async_graphql::Error;
+
+    fn try_from(
+        value: sellershut_core::categories::CategoryRevision,
+    ) -> async_graphql::Result<Self> {
+        let operation = sellershut_core::categories::RevisionOperation::try_from(value.operation)
+            .map_err(|_| tonic::Status::internal("unknown revision operation"))?;
+
+        Ok(Self {
+            id: value.id,
+            category_id: value.category_id,
+            operation: operation.into(),
+            before: value.before.map(Category::try_from).transpose()?,
+            after: value.after.map(Category::try_from).transpose()?,
+            actor: value.actor,
+            created_at: to_offset_datetime(value.created_at)?,
+            reverted_from: value.reverted_from,
+        })
+    }
+}
+
+/// What a listing of categories is ordered by
+#[derive(Enum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CategorySortField {
+    /// When the category was created
+    #[default]
+    CreatedAt,
+    /// When the category was last updated
+    UpdatedAt,
+    /// The default name of the category
+    Name,
+}
+
+impl From<CategorySortField> for sellershut_core::categories::CategorySortField {
+    fn from(value: CategorySortField) -> Self {
+        match value {
+            CategorySortField::CreatedAt => Self::CreatedAt,
+            CategorySortField::UpdatedAt => Self::UpdatedAt,
+            CategorySortField::Name => Self::Name,
+        }
+    }
+}
+
+/// The direction a listing is ordered in
+#[derive(Enum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<SortDirection> for sellershut_core::categories::SortDirection {
+    fn from(value: SortDirection) -> Self {
+        match value {
+            SortDirection::Asc => Self::Asc,
+            SortDirection::Desc => Self::Desc,
+        }
+    }
+}
+
+/// Where in the tree listed categories sit
+#[derive(Enum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CategoryLevel {
+    /// Any category
+    #[default]
+    All,
+    /// Top-level categories only
+    Root,
+    /// Categories without sub categories only
+    Leaf,
+}
+
+impl From<CategoryLevel> for sellershut_core::categories::CategoryLevel {
+    fn from(value: CategoryLevel) -> Self {
+        match value {
+            CategoryLevel::All => Self::All,
+            CategoryLevel::Root => Self::Root,
+            CategoryLevel::Leaf => Self::Leaf,
+        }
+    }
+}
+
+/// Narrows down a listing of categories
+#[derive(InputObject, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CategoryFilter {
+    /// Names starting with this, ignoring case
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    pub name_prefix: Option<String>,
+    /// Names containing this, ignoring case
+    #[graphql(validator(min_length = 1, max_length = 100))]
+    pub name_contains: Option<String>,
+    /// Whether categories have an image
+    pub has_image: Option<bool>,
+    #[graphql(default)]
+    pub level: CategoryLevel,
+}
+
+impl From<CategoryFilter> for sellershut_core::categories::CategoryFilter {
+    fn from(value: CategoryFilter) -> Self {
+        Self {
+            name_prefix: value.name_prefix,
+            name_contains: value.name_contains,
+            has_image: value.has_image,
+            level: sellershut_core::categories::CategoryLevel::from(value.level).into(),
+        }
+    }
+}
+
 fn to_timestamp(dt: OffsetDateTime) -> Timestamp {
     Timestamp {
         seconds: dt.unix_timestamp(),
@@ -67,11 +581,16 @@ impl From<Category> for sellershut_core::categories::Category {
         Self {
             id: value.id,
             name: value.name,
+            slug: value.slug,
+            description: value.description,
             sub_categories: value.sub_categories,
             image_url: value.image_url,
             parent_id: value.parent_id,
             created_at: Some(to_timestamp(value.created_at)),
             updated_at: Some(to_timestamp(value.updated_at)),
+            deleted_at: value.deleted_at.map(to_timestamp),
+            version: value.version,
+            external_id: value.external_id,
         }
     }
 }
@@ -0,0 +1,143 @@
+use async_graphql::{ErrorExtensions, Value};
+use tonic_types::StatusExt;
+
+use crate::state::VERSION_CONFLICT;
+
+/// Carries a gRPC status over to GraphQL. The extensions hold the status as `status`, the
+/// [ErrorInfo](tonic_types::ErrorInfo) reason as `code` (the status when there is none), its
+/// metadata, and the fields at fault as `fieldViolations`. A rejected stale update also carries
+/// the category's current version and its current state
+pub fn map_status(status: tonic::Status) -> async_graphql::Error {
+    let code = screaming_snake_case(&format!("{:?}", status.code()));
+    let info = status.get_details_error_info();
+    let bad_request = status.get_details_bad_request();
+    let precondition_failure = status.get_details_precondition_failure();
+
+    async_graphql::Error::new(status.message()).extend_with(|_, extensions| {
+        extensions.set("status", code.as_str());
+
+        match info {
+            Some(ref info) => {
+                extensions.set("code", info.reason.as_str());
+                extensions.set("domain", info.domain.as_str());
+                if info.reason == VERSION_CONFLICT {
+                    set_conflict(info, extensions);
+                } else if !info.metadata.is_empty() {
+                    extensions.set(
+                        "metadata",
+                        Value::from_json(serde_json::json!(info.metadata)).unwrap_or_default(),
+                    );
+                }
+            }
+            None => extensions.set("code", code.as_str()),
+        }
+
+        if let Some(ref bad_request) = bad_request {
+            let violations: Vec<_> = bad_request
+                .field_violations
+                .iter()
+                .map(|violation| {
+                    serde_json::json!({
+                        "field": violation.field,
+                        "description": violation.description,
+                    })
+                })
+                .collect();
+            extensions.set(
+                "fieldViolations",
+                Value::from_json(violations.into()).unwrap_or_default(),
+            );
+        }
+
+        if let Some(ref precondition_failure) = precondition_failure {
+            let violations: Vec<_> = precondition_failure
+                .violations
+                .iter()
+                .map(|violation| {
+                    serde_json::json!({
+                        "type": violation.r#type,
+                        "subject": violation.subject,
+                        "description": violation.description,
+                    })
+                })
+                .collect();
+            extensions.set(
+                "preconditionViolations",
+                Value::from_json(violations.into()).unwrap_or_default(),
+            );
+        }
+    })
+}
+
+fn set_conflict(
+    info: &tonic_types::ErrorInfo,
+    extensions: &mut async_graphql::ErrorExtensionValues,
+) {
+    if let Some(id) = info.metadata.get("id") {
+        extensions.set("id", id.as_str());
+    }
+    if let Some(version) = info
+        .metadata
+        .get("version")
+        .and_then(|version| version.parse::<i64>().ok())
+    {
+        extensions.set("currentVersion", version);
+    }
+    if let Some(current) = info
+        .metadata
+        .get("category")
+        .and_then(|category| serde_json::from_str(category).ok())
+        .and_then(|category| Value::from_json(category).ok())
+    {
+        extensions.set("current", current);
+    }
+}
+
+/// `NotFound` as `NOT_FOUND`, the way gRPC spells status codes
+fn screaming_snake_case(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 4);
+    for (i, c) in value.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            output.push('_');
+        }
+        output.push(c.to_ascii_uppercase());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tonic_types::{BadRequest, ErrorDetail, ErrorInfo};
+
+    use super::*;
+    use crate::state::ERROR_DOMAIN;
+
+    #[test]
+    fn map_status_details() {
+        let status = tonic::Status::with_error_details_vec(
+            tonic::Code::InvalidArgument,
+            "name is required",
+            [
+                ErrorDetail::from(ErrorInfo::new(
+                    "INVALID_VALUE",
+                    ERROR_DOMAIN,
+                    HashMap::new(),
+                )),
+                ErrorDetail::from(BadRequest::with_violation("name", "name is required")),
+            ],
+        );
+
+        let error = map_status(status);
+        let extensions = serde_json::to_value(error.extensions.unwrap()).unwrap();
+
+        assert_eq!(extensions["status"], "INVALID_ARGUMENT");
+        assert_eq!(extensions["code"], "INVALID_VALUE");
+        assert_eq!(extensions["fieldViolations"][0]["field"], "name");
+
+        let error = map_status(tonic::Status::not_found("category does not exist"));
+        let extensions = serde_json::to_value(error.extensions.unwrap()).unwrap();
+        assert_eq!(extensions["code"], "NOT_FOUND");
+    }
+}
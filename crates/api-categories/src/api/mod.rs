@@ -1,25 +1,94 @@
 pub mod entity;
+pub mod error;
 pub mod mutation;
+pub mod persisted;
 pub mod query;
+pub mod subscription;
 
-use async_graphql::{EmptySubscription, Schema};
+use async_graphql::{dataloader::DataLoader, Schema};
+use core_services::shutdown::Shutdown;
 use mutation::Mutation;
+use persisted::PersistedQueries;
 use query::Query;
-use sellershut_core::categories::{
-    mutate_categories_server::MutateCategories, query_categories_server::QueryCategories,
-};
+use subscription::Subscription;
 
-pub struct ApiSchemaBuilder {}
+use crate::state::{ApiState, CategoryLoader, Changes};
 
-pub type ApiSchema = Schema<Query, Mutation, EmptySubscription>;
+/// Builds the GraphQL schema. Queries are unlimited and persisted queries are off unless
+/// configured
+pub struct ApiSchemaBuilder {
+    state: ApiState,
+    changes: Changes,
+    max_depth: Option<usize>,
+    max_complexity: Option<usize>,
+    persisted_queries: Option<PersistedQueries>,
+    shutdown: Option<Shutdown>,
+}
+
+pub type ApiSchema = Schema<Query, Mutation, Subscription>;
 
 impl ApiSchemaBuilder {
-    pub fn build<T>(data: T) -> ApiSchema
-    where
-        T: QueryCategories + MutateCategories,
-    {
-        Schema::build(Query::default(), Mutation::default(), EmptySubscription)
-            .data(data)
-            .finish()
+    pub fn new(state: ApiState, changes: Changes) -> Self {
+        Self {
+            state,
+            changes,
+            max_depth: None,
+            max_complexity: None,
+            persisted_queries: None,
+            shutdown: None,
+        }
+    }
+
+    /// Rejects operations that nest fields deeper than `depth`
+    pub fn limit_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Rejects operations that are more complex than `complexity`. Every field counts once, and
+    /// the fields of a page as many times as the page can hold
+    pub fn limit_complexity(mut self, complexity: usize) -> Self {
+        self.max_complexity = Some(complexity);
+        self
+    }
+
+    /// Lets clients send operations by hash
+    pub fn persisted_queries(mut self, persisted_queries: PersistedQueries) -> Self {
+        self.persisted_queries = Some(persisted_queries);
+        self
+    }
+
+    /// Ends subscriptions once `shutdown` starts
+    pub fn shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn build(self) -> ApiSchema {
+        let loader = DataLoader::new(CategoryLoader::new(self.state.state.clone()), tokio::spawn);
+
+        let mut builder = Schema::build(
+            Query::default(),
+            Mutation::default(),
+            Subscription::default(),
+        )
+        .data(self.state)
+        .data(loader)
+        .data(self.changes);
+
+        if let Some(depth) = self.max_depth {
+            builder = builder.limit_depth(depth);
+        }
+        if let Some(complexity) = self.max_complexity {
+            builder = builder.limit_complexity(complexity);
+        }
+        if let Some(persisted_queries) = self.persisted_queries {
+            builder = builder.extension(persisted_queries);
+        }
+        if let Some(shutdown) = self.shutdown {
+            builder = builder.data(shutdown);
+        }
+
+        builder.finish()
     }
 }
@@ -1,12 +1,25 @@
-use async_graphql::{Context, MergedObject, Object, Result};
+use async_graphql::{Context, Guard, MergedObject, Object, Result};
 use sellershut_core::categories::{
-    mutate_categories_server::MutateCategories, CategoryEvent, DeleteCategoryRequest,
+    mutate_categories_server::MutateCategories, CategoryEvent, DeleteCategoriesRequest,
+    DeleteCategoryAttributeRequest, DeleteCategoryRequest, MoveCategoryRequest,
+    PurgeCategoryRequest, RestoreCategoryRequest, RevertToRevisionRequest,
+    SetCategoryTranslationsRequest, UpsertCategoriesRequest, UpsertCategoryAttributeRequest,
     UpsertCategoryRequest,
 };
 use tonic::IntoRequest;
 use tracing::instrument;
 
-use crate::{api::entity::Category, state::ApiState};
+use crate::{
+    api::{
+        entity::{
+            Category, CategoryAttribute, CategoryTranslation, DeleteCategoryResponse,
+            DeleteStrategy,
+        },
+        error::map_status,
+    },
+    auth::{authorize, Identity},
+    state::ApiState,
+};
 
 #[derive(Default, Debug, MergedObject)]
 pub struct Mutation(GraphqlMutation);
@@ -14,7 +27,25 @@ pub struct Mutation(GraphqlMutation);
 #[derive(Default, Debug)]
 pub struct GraphqlMutation;
 
-#[Object]
+/// Only admins change the taxonomy
+struct AdminGuard;
+
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        authorize(ctx.data_opt::<Identity>()).map_err(map_status)
+    }
+}
+
+/// A request to the service on behalf of the caller
+fn with_identity<T>(ctx: &Context<'_>, message: T) -> tonic::Request<T> {
+    let mut request = message.into_request();
+    if let Some(identity) = ctx.data_opt::<Identity>() {
+        request.extensions_mut().insert(identity.clone());
+    }
+    request
+}
+
+#[Object(guard = "AdminGuard")]
 impl GraphqlMutation {
     #[instrument(skip(self, ctx), err(Debug))]
     async fn create(&self, ctx: &Context<'_>, input: Category) -> Result<Category> {
@@ -26,7 +57,11 @@ impl GraphqlMutation {
             event: CategoryEvent::Create.into(),
         };
 
-        let res = service.create(request.into_request()).await?.into_inner();
+        let res = service
+            .create(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
 
         Category::try_from(res)
     }
@@ -41,22 +76,278 @@ impl GraphqlMutation {
             event: CategoryEvent::Update.into(),
         };
 
-        let res = service.update(request.into_request()).await?.into_inner();
+        let res = service
+            .update(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
 
         Category::try_from(res)
     }
 
     #[instrument(skip(ctx), err(Debug))]
-    async fn delete(&self, ctx: &Context<'_>, id: String) -> Result<Option<Category>> {
+    async fn delete(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        #[graphql(default)] strategy: DeleteStrategy,
+        #[graphql(default)] dry_run: bool,
+    ) -> Result<DeleteCategoryResponse> {
         let service = ctx.data::<ApiState>()?;
 
         let req = DeleteCategoryRequest {
             id,
             event: CategoryEvent::Delete.into(),
+            strategy: sellershut_core::categories::DeleteStrategy::from(strategy).into(),
+            dry_run,
+        };
+
+        let res = service
+            .delete(with_identity(ctx, req))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        DeleteCategoryResponse::try_from(res)
+    }
+
+    #[graphql(name = "move")]
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn move_category(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        #[graphql(validator(min_length = 21, max_length = 21))] parent_id: Option<String>,
+    ) -> Result<Category> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = MoveCategoryRequest { id, parent_id };
+
+        let res = service
+            .r#move(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Category::try_from(res)
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn create_many(&self, ctx: &Context<'_>, input: Vec<Category>) -> Result<Vec<Category>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = UpsertCategoriesRequest {
+            categories: input
+                .into_iter()
+                .map(sellershut_core::categories::Category::from)
+                .collect(),
+            event: CategoryEvent::Create.into(),
+        };
+
+        let res = service
+            .create_many(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn update_many(&self, ctx: &Context<'_>, input: Vec<Category>) -> Result<Vec<Category>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = UpsertCategoriesRequest {
+            categories: input
+                .into_iter()
+                .map(sellershut_core::categories::Category::from)
+                .collect(),
+            event: CategoryEvent::Update.into(),
+        };
+
+        let res = service
+            .update_many(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn delete_many(&self, ctx: &Context<'_>, ids: Vec<String>) -> Result<Vec<Category>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = DeleteCategoriesRequest {
+            ids,
+            event: CategoryEvent::Delete.into(),
+        };
+
+        let res = service
+            .delete_many(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn restore(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+    ) -> Result<Vec<Category>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = RestoreCategoryRequest { id };
+
+        let res = service
+            .restore(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn purge(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+    ) -> Result<Vec<Category>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = PurgeCategoryRequest { id };
+
+        let res = service
+            .purge(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn set_translations(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        translations: Vec<CategoryTranslation>,
+    ) -> Result<Vec<CategoryTranslation>> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = SetCategoryTranslationsRequest {
+            id,
+            translations: translations.into_iter().map(Into::into).collect(),
+        };
+
+        let res = service
+            .set_translations(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Ok(res
+            .translations
+            .into_iter()
+            .map(CategoryTranslation::from)
+            .collect())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn create_attribute(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] category_id: String,
+        input: CategoryAttribute,
+    ) -> Result<CategoryAttribute> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = UpsertCategoryAttributeRequest {
+            attribute: Some(
+                CategoryAttribute {
+                    category_id,
+                    ..input
+                }
+                .into(),
+            ),
         };
 
-        let _res = service.delete(req.into_request()).await?.into_inner();
+        let res = service
+            .create_attribute(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
 
-        Ok(None)
+        CategoryAttribute::try_from(res)
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn update_attribute(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        input: CategoryAttribute,
+    ) -> Result<CategoryAttribute> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = UpsertCategoryAttributeRequest {
+            attribute: Some(CategoryAttribute { id, ..input }.into()),
+        };
+
+        let res = service
+            .update_attribute(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        CategoryAttribute::try_from(res)
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn delete_attribute(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+    ) -> Result<CategoryAttribute> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = DeleteCategoryAttributeRequest { id };
+
+        let res = service
+            .delete_attribute(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        CategoryAttribute::try_from(res)
+    }
+
+    /// Returns a category to the state it was in after a revision. `version` is the version that
+    /// was read, the revert is rejected if the category changed in the meantime
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn revert_to_revision(
+        &self,
+        ctx: &Context<'_>,
+        revision_id: i64,
+        version: i64,
+    ) -> Result<Category> {
+        let service = ctx.data::<ApiState>()?;
+
+        let request = RevertToRevisionRequest {
+            revision_id,
+            version,
+        };
+
+        let res = service
+            .revert_to_revision(with_identity(ctx, request))
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Category::try_from(res)
     }
 }
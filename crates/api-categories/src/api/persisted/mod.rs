@@ -0,0 +1,210 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use anyhow::bail;
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    from_value, Request, ServerError, ServerResult,
+};
+use core_services::cache::{key::CacheKey, PoolLike, PooledConnectionLike, RedisPool};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, debug_span, warn, Instrument};
+
+/// How long an operation registered by a client is kept after it was last registered
+const TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The `persistedQuery` request extension
+#[derive(Deserialize)]
+struct PersistedQuery {
+    version: i32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+/// Lets clients send the hash of an operation in place of the operation
+#[derive(Clone)]
+pub enum PersistedQueries {
+    /// Automatic persisted queries. Clients send the operation along with its hash when the hash
+    /// is not known yet, the operation is then kept in the cache for every replica to look up
+    Automatic(RedisPool),
+    /// Only the operations in an allowlist run, clients send their hashes and cannot register
+    /// operations of their own
+    Only(Arc<HashMap<String, String>>),
+}
+
+impl PersistedQueries {
+    /// Reads an allowlist from a JSON object of SHA-256 hashes to the operations they are the
+    /// hashes of
+    pub fn allowlist(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let operations: HashMap<String, String> = serde_json::from_slice(&std::fs::read(path)?)?;
+
+        for (hash, query) in operations.iter() {
+            if *hash != digest(query) {
+                bail!("allowlisted operation {hash} does not match its hash");
+            }
+        }
+        debug!(count = operations.len(), "read persisted query allowlist");
+
+        Ok(Self::Only(Arc::new(operations)))
+    }
+
+    async fn lookup(&self, hash: &str) -> Option<String> {
+        match self {
+            Self::Automatic(cache) => {
+                let query = async {
+                    let mut cache = cache.get().await?;
+                    Ok::<_, anyhow::Error>(cache.get(CacheKey::PersistedQuery(hash)).await?)
+                }
+                .instrument(debug_span!("cache.get"))
+                .await;
+
+                // the client sends the operation again
+                query
+                    .inspect_err(|e| warn!(error = %e, "could not read persisted query"))
+                    .ok()
+                    .flatten()
+            }
+            Self::Only(operations) => operations.get(hash).cloned(),
+        }
+    }
+
+    async fn register(&self, hash: &str, query: &str) {
+        let Self::Automatic(cache) = self else {
+            return;
+        };
+
+        let result = async {
+            let mut cache = cache.get().await?;
+            cache
+                .pset_ex::<_, _, ()>(
+                    CacheKey::PersistedQuery(hash),
+                    query,
+                    TTL.as_millis() as u64,
+                )
+                .await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .instrument(debug_span!("cache.set"))
+        .await;
+
+        // the operation still runs, it is registered again on the next miss
+        if let Err(e) = result {
+            warn!(error = %e, "could not register persisted query");
+        }
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension(self.clone()))
+    }
+}
+
+struct PersistedQueriesExtension(PersistedQueries);
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let Some(value) = request.extensions.remove("persistedQuery") else {
+            if let PersistedQueries::Only(_) = self.0 {
+                return Err(ServerError::new("PersistedQueryRequired", None));
+            }
+            return next.run(ctx, request).await;
+        };
+
+        let persisted_query: PersistedQuery = from_value(value).map_err(|_| {
+            ServerError::new("Invalid \"persistedQuery\" extension configuration", None)
+        })?;
+        if persisted_query.version != 1 {
+            return Err(ServerError::new(
+                format!(
+                    "Only version 1 of the \"persistedQuery\" extension is supported, not {}",
+                    persisted_query.version
+                ),
+                None,
+            ));
+        }
+        let hash = persisted_query.sha256_hash;
+
+        match self.0.lookup(&hash).await {
+            Some(query) => request.query = query,
+            None if request.query.is_empty() => {
+                return Err(ServerError::new("PersistedQueryNotFound", None));
+            }
+            None => match self.0 {
+                PersistedQueries::Automatic(_) => {
+                    if hash != digest(&request.query) {
+                        return Err(ServerError::new("provided sha does not match query", None));
+                    }
+                    self.0.register(&hash, &request.query).await;
+                }
+                PersistedQueries::Only(_) => {
+                    return Err(ServerError::new("PersistedQueryNotFound", None));
+                }
+            },
+        }
+
+        next.run(ctx, request).await
+    }
+}
+
+fn digest(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+    use serde_json::json;
+
+    use super::*;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn value(&self) -> i32 {
+            100
+        }
+    }
+
+    fn persisted(query: &str, hash: &str) -> Request {
+        let mut request = Request::new(query);
+        request.extensions.insert(
+            "persistedQuery".to_string(),
+            async_graphql::Value::from_json(json!({ "version": 1, "sha256Hash": hash })).unwrap(),
+        );
+        request
+    }
+
+    #[tokio::test]
+    async fn only_run_allowlisted_operations() {
+        let allowed = "{ value }";
+        let hash = digest(allowed);
+
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(PersistedQueries::Only(Arc::new(HashMap::from([(
+                hash.clone(),
+                allowed.to_string(),
+            )]))))
+            .finish();
+
+        let response = schema.execute(persisted("", &hash)).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.into_json().unwrap(), json!({ "value": 100 }));
+
+        // clients cannot register operations
+        let other = "{ value __typename }";
+        let response = schema.execute(persisted(other, &digest(other))).await;
+        assert_eq!(response.errors[0].message, "PersistedQueryNotFound");
+
+        let response = schema.execute(Request::new(allowed)).await;
+        assert_eq!(response.errors[0].message, "PersistedQueryRequired");
+    }
+}
@@ -1,20 +1,32 @@
 use async_graphql::{
     connection::{Connection, Edge, EmptyFields},
-    Context, MergedObject, Object, Result,
+    Context, MergedObject, Object, Result, SimpleObject,
 };
 use sellershut_core::{
     categories::{
-        query_categories_server::QueryCategories, GetCategoryRequest, GetSubCategoriesRequest,
+        query_categories_server::QueryCategories, GetCategoriesRequest,
+        GetCategoryAttributesRequest, GetCategoryByPathRequest, GetCategoryBySlugRequest,
+        GetCategoryRequest, GetCategoryRevisionsRequest, GetCategoryTreeRequest,
+        GetSubCategoriesRequest,
     },
     common::pagination::{
         cursor::{cursor_value::CursorType, CursorValue, Index},
-        Cursor,
+        Cursor, Offset,
     },
 };
 use tonic::IntoRequest;
 use tracing::{instrument, trace};
 
-use crate::{api::entity::Category, state::ApiState};
+use crate::{
+    api::{
+        entity::{
+            Category, CategoryAttribute, CategoryFilter, CategoryRevision, CategorySortField,
+            CategoryTranslation, CategoryTreeNode, SortDirection,
+        },
+        error::map_status,
+    },
+    state::ApiState,
+};
 
 #[derive(Default, Debug, MergedObject)]
 pub struct Query(GraphqlQuery);
@@ -24,7 +36,9 @@ pub struct GraphqlQuery;
 
 #[Object]
 impl GraphqlQuery {
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(complexity = "page_complexity(first.or(last).or(limit), child_complexity)")]
     async fn categories(
         &self,
         ctx: &Context<'_>,
@@ -32,20 +46,46 @@ impl GraphqlQuery {
         #[graphql(validator(min_length = 1))] before: Option<String>,
         #[graphql(validator(minimum = 1, maximum = 100))] first: Option<i32>,
         #[graphql(validator(minimum = 1, maximum = 100))] last: Option<i32>,
-    ) -> Result<Connection<String, Category, EmptyFields, EmptyFields>> {
-        let pagination = Params::parse(after, before, first, last)?;
+        #[graphql(default)] include_archived: bool,
+        #[graphql(validator(min_length = 2, max_length = 35))] locale: Option<String>,
+        #[graphql(default)] sort: CategorySortField,
+        #[graphql(default)] direction: SortDirection,
+        filter: Option<CategoryFilter>,
+        #[graphql(validator(minimum = 0))] offset: Option<i32>,
+        #[graphql(validator(minimum = 1, maximum = 100))] limit: Option<i32>,
+    ) -> Result<Connection<String, Category, ConnectionFields, EmptyFields>> {
+        let (pagination, offset) =
+            Params::parse_with_offset(after, before, first, last, offset, limit)?;
 
         trace!("extracting state");
         let service = ctx.data::<ApiState>()?;
 
+        let req = GetCategoriesRequest {
+            pagination,
+            offset,
+            include_total_count: ctx.look_ahead().field("totalCount").exists(),
+            include_archived,
+            locale,
+            sort: sellershut_core::categories::CategorySortField::from(sort).into(),
+            direction: sellershut_core::categories::SortDirection::from(direction).into(),
+            filter: filter.map(Into::into),
+        };
+
         let res = service
-            .categories(pagination.into_request())
-            .await?
+            .categories(req.into_request())
+            .await
+            .map_err(map_status)?
             .into_inner();
 
         let page_info = res.page_info.as_ref().expect("page_info to be defined");
 
-        let mut conn = Connection::new(page_info.has_previous_page, page_info.has_next_page);
+        let mut conn = Connection::with_additional_fields(
+            page_info.has_previous_page,
+            page_info.has_next_page,
+            ConnectionFields {
+                total_count: page_info.total_count,
+            },
+        );
 
         trace!("mapping category types");
 
@@ -63,34 +103,52 @@ impl GraphqlQuery {
         Ok(conn)
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(complexity = "page_complexity(first.or(last).or(limit), child_complexity)")]
     async fn sub_categories(
         &self,
         ctx: &Context<'_>,
         parent_id: Option<String>,
-        #[graphql(validator(min_length = 1, max_length = 100))] after: Option<String>,
-        #[graphql(validator(min_length = 1, max_length = 100))] before: Option<String>,
+        #[graphql(validator(min_length = 1))] after: Option<String>,
+        #[graphql(validator(min_length = 1))] before: Option<String>,
         #[graphql(validator(minimum = 1, maximum = 100))] first: Option<i32>,
         #[graphql(validator(minimum = 1, maximum = 100))] last: Option<i32>,
-    ) -> Result<Connection<String, Category, EmptyFields, EmptyFields>> {
-        let pagination = Params::parse(after, before, first, last)?;
+        #[graphql(default)] include_archived: bool,
+        #[graphql(validator(min_length = 2, max_length = 35))] locale: Option<String>,
+        #[graphql(validator(minimum = 0))] offset: Option<i32>,
+        #[graphql(validator(minimum = 1, maximum = 100))] limit: Option<i32>,
+    ) -> Result<Connection<String, Category, ConnectionFields, EmptyFields>> {
+        let (pagination, offset) =
+            Params::parse_with_offset(after, before, first, last, offset, limit)?;
 
         trace!("extracting state");
         let service = ctx.data::<ApiState>()?;
 
         let req = GetSubCategoriesRequest {
             id: parent_id,
-            pagination: Some(pagination),
+            pagination,
+            offset,
+            include_total_count: ctx.look_ahead().field("totalCount").exists(),
+            include_archived,
+            locale,
         };
 
         let res = service
             .sub_categories(req.into_request())
-            .await?
+            .await
+            .map_err(map_status)?
             .into_inner();
 
         let page_info = res.page_info.as_ref().expect("page_info to be defined");
 
-        let mut conn = Connection::new(page_info.has_previous_page, page_info.has_next_page);
+        let mut conn = Connection::with_additional_fields(
+            page_info.has_previous_page,
+            page_info.has_next_page,
+            ConnectionFields {
+                total_count: page_info.total_count,
+            },
+        );
 
         let mut edges = Vec::with_capacity(res.edges.len());
 
@@ -111,25 +169,255 @@ impl GraphqlQuery {
         &self,
         ctx: &Context<'_>,
         #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        #[graphql(validator(min_length = 2, max_length = 35))] locale: Option<String>,
     ) -> async_graphql::Result<Option<Category>> {
         trace!("extracting state");
         let service = ctx.data::<ApiState>()?;
-        let request = GetCategoryRequest { id };
+        let request = GetCategoryRequest { id, locale };
 
         let res = service
             .category_by_id(request.into_request())
-            .await?
+            .await
+            .map_err(map_status)?
             .into_inner();
 
         let category = Category::try_from(res)?;
 
         Ok(Some(category))
     }
+
+    #[instrument(skip(ctx), err(Debug))]
+    async fn category_by_slug(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] parent_id: Option<String>,
+        #[graphql(validator(min_length = 1))] slug: String,
+    ) -> Result<Category> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryBySlugRequest { parent_id, slug };
+
+        let res = service
+            .category_by_slug(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Category::try_from(res)
+    }
+
+    #[instrument(skip(ctx), err(Debug))]
+    async fn category_by_path(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 1))] path: String,
+    ) -> Result<Category> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryByPathRequest { path };
+
+        let res = service
+            .category_by_path(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Category::try_from(res)
+    }
+
+    /// A category and its descendants, the whole taxonomy without `id`. At most 6 levels below
+    /// the top are read, fewer with `maxDepth`
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(complexity = "tree_complexity(max_depth, child_complexity)")]
+    async fn category_tree(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: Option<String>,
+        #[graphql(validator(minimum = 0, maximum = 6))] max_depth: Option<i32>,
+    ) -> Result<Vec<CategoryTreeNode>> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryTreeRequest {
+            id,
+            max_depth: Some(max_depth.unwrap_or(MAX_TREE_DEPTH)),
+        };
+
+        let res = service
+            .category_tree(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.roots
+            .into_iter()
+            .map(CategoryTreeNode::try_from)
+            .collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn category_ancestors(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        #[graphql(validator(min_length = 2, max_length = 35))] locale: Option<String>,
+    ) -> Result<Vec<Category>> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryRequest { id, locale };
+
+        let res = service
+            .category_ancestors(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.categories.into_iter().map(Category::try_from).collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn category_translations(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+    ) -> Result<Vec<CategoryTranslation>> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryRequest { id, locale: None };
+
+        let res = service
+            .category_translations(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        Ok(res
+            .translations
+            .into_iter()
+            .map(CategoryTranslation::from)
+            .collect())
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn category_attributes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] category_id: String,
+    ) -> Result<Vec<CategoryAttribute>> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryAttributesRequest { category_id };
+
+        let res = service
+            .category_attributes(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.attributes
+            .into_iter()
+            .map(CategoryAttribute::try_from)
+            .collect()
+    }
+
+    #[instrument(skip(self, ctx), err(Debug))]
+    async fn resolved_attributes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] category_id: String,
+    ) -> Result<Vec<CategoryAttribute>> {
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+        let request = GetCategoryAttributesRequest { category_id };
+
+        let res = service
+            .resolved_attributes(request.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        res.attributes
+            .into_iter()
+            .map(CategoryAttribute::try_from)
+            .collect()
+    }
+
+    /// Changes made to a category, newest first
+    #[instrument(skip(self, ctx), err(Debug))]
+    #[graphql(complexity = "page_complexity(first.or(last), child_complexity)")]
+    async fn category_revisions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(min_length = 21, max_length = 21))] id: String,
+        #[graphql(validator(min_length = 1))] after: Option<String>,
+        #[graphql(validator(min_length = 1))] before: Option<String>,
+        #[graphql(validator(minimum = 1, maximum = 100))] first: Option<i32>,
+        #[graphql(validator(minimum = 1, maximum = 100))] last: Option<i32>,
+    ) -> Result<Connection<String, CategoryRevision, EmptyFields, EmptyFields>> {
+        let pagination = Params::parse(after, before, first, last)?;
+
+        trace!("extracting state");
+        let service = ctx.data::<ApiState>()?;
+
+        let req = GetCategoryRevisionsRequest {
+            id,
+            pagination: Some(pagination),
+        };
+
+        let res = service
+            .category_revisions(req.into_request())
+            .await
+            .map_err(map_status)?
+            .into_inner();
+
+        let page_info = res.page_info.as_ref().expect("page_info to be defined");
+
+        let mut conn = Connection::new(page_info.has_previous_page, page_info.has_next_page);
+
+        let mut edges = Vec::with_capacity(res.edges.len());
+
+        for edge in res.edges.into_iter() {
+            let edge = Edge::new(
+                edge.cursor,
+                CategoryRevision::try_from(edge.node.expect("revision to be some"))?,
+            );
+            edges.push(edge);
+        }
+        conn.edges = edges;
+
+        Ok(conn)
+    }
 }
 
 /// Relay-compliant connection parameters to page results by cursor/page size
 pub struct Params;
 
+/// Fields of a connection besides its edges
+#[derive(SimpleObject, Debug, Default)]
+pub struct ConnectionFields {
+    /// The number of items across all pages
+    pub total_count: Option<i64>,
+}
+
+/// The largest page a connection returns
+const MAX_PAGE_SIZE: usize = 100;
+
+/// The complexity of a page of `size` items, each as complex as its selected fields
+pub fn page_complexity(size: Option<i32>, child_complexity: usize) -> usize {
+    let size = size.map_or(MAX_PAGE_SIZE, |size| {
+        size.clamp(1, MAX_PAGE_SIZE as i32) as usize
+    });
+    size.saturating_mul(child_complexity)
+}
+
+/// The deepest a category tree is read, in levels below the top
+pub const MAX_TREE_DEPTH: i32 = 6;
+
+/// The complexity of a category tree `max_depth` levels deep, each level counted as a full page
+pub fn tree_complexity(max_depth: Option<i32>, child_complexity: usize) -> usize {
+    let levels = max_depth.map_or(MAX_TREE_DEPTH, |depth| depth.clamp(0, MAX_TREE_DEPTH)) + 1;
+    page_complexity(None, child_complexity).saturating_mul(levels as usize)
+}
+
 impl Params {
     #[instrument(err(Debug))]
     pub fn parse(
@@ -174,4 +462,32 @@ impl Params {
             }),
         })
     }
+
+    /// Parses either offset pagination, when `offset` or `limit` is given, or cursor pagination
+    #[instrument(err(Debug))]
+    pub fn parse_with_offset(
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        offset: Option<i32>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<(Option<Cursor>, Option<Offset>)> {
+        if offset.is_none() && limit.is_none() {
+            return Ok((Some(Self::parse(after, before, first, last)?), None));
+        }
+
+        if after.is_some() || before.is_some() || first.is_some() || last.is_some() {
+            return Err("'offset' and 'limit' cannot be combined with cursor pagination".into());
+        }
+        let limit = limit.ok_or("'limit' should be provided with 'offset'")?;
+
+        Ok((
+            None,
+            Some(Offset {
+                offset: offset.unwrap_or_default(),
+                limit,
+            }),
+        ))
+    }
 }
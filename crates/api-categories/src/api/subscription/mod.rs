@@ -0,0 +1,87 @@
+use async_graphql::{Context, MergedSubscription, Result, Subscription};
+use core_services::shutdown::Shutdown;
+use futures_util::{future, stream, FutureExt, Stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{
+    api::entity::Category,
+    state::{ChangeKind, Changes},
+};
+
+#[derive(Default, MergedSubscription)]
+pub struct Subscription(GraphqlSubscription);
+
+#[derive(Default, Debug)]
+pub struct GraphqlSubscription;
+
+#[Subscription]
+impl GraphqlSubscription {
+    /// Categories as they are created or restored from the archive. Pass a parent ID to only
+    /// receive its sub categories
+    async fn category_created(
+        &self,
+        ctx: &Context<'_>,
+        parent_id: Option<String>,
+    ) -> Result<impl Stream<Item = Category>> {
+        changes(ctx, ChangeKind::Created, parent_id)
+    }
+
+    /// Categories as they are updated or moved. Pass a parent ID to only receive its sub
+    /// categories
+    async fn category_updated(
+        &self,
+        ctx: &Context<'_>,
+        parent_id: Option<String>,
+    ) -> Result<impl Stream<Item = Category>> {
+        changes(ctx, ChangeKind::Updated, parent_id)
+    }
+
+    /// Categories as they are archived or purged. Pass a parent ID to only receive its sub
+    /// categories
+    async fn category_deleted(
+        &self,
+        ctx: &Context<'_>,
+        parent_id: Option<String>,
+    ) -> Result<impl Stream<Item = Category>> {
+        changes(ctx, ChangeKind::Deleted, parent_id)
+    }
+}
+
+fn changes(
+    ctx: &Context<'_>,
+    kind: ChangeKind,
+    parent_id: Option<String>,
+) -> Result<impl Stream<Item = Category>> {
+    let receiver = ctx.data::<Changes>()?.subscribe();
+
+    let changes = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(change) => return Some((change, receiver)),
+                // carry on from the oldest change still held
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "subscriber fell behind, changes were dropped")
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    // ended when the server shuts down, so sessions can close
+    let shutdown = match ctx.data_opt::<Shutdown>() {
+        Some(shutdown) => shutdown.triggered().boxed(),
+        None => future::pending().boxed(),
+    };
+
+    Ok(changes.take_until(shutdown).filter_map(move |change| {
+        let category = (change.kind == kind
+            && parent_id
+                .as_ref()
+                .is_none_or(|id| change.category.parent_id.as_ref() == Some(id)))
+        .then(|| Category::try_from(change.category).ok())
+        .flatten();
+
+        async move { category }
+    }))
+}
@@ -0,0 +1,328 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonwebtoken::{jwk::JwkSet, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+/// Header a service sends its API key in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Role allowed to change the taxonomy
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Who made a request, taken from a verified token or an API key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The user a token was issued to, or the name of the service an API key belongs to
+    pub subject: String,
+    /// What the caller is allowed to do
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    /// Whether the caller can change the taxonomy
+    pub fn is_admin(&self) -> bool {
+        self.roles.iter().any(|role| role == ADMIN_ROLE)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Verifies the credentials requests carry. Bearer tokens are checked against a JWKS or a shared
+/// secret, services identify themselves with an API key
+#[derive(Clone, Default)]
+pub struct Authenticator {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    secret: Option<DecodingKey>,
+    jwks: Option<JwkSet>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    // keys are stored hashed, so looking one up does not leak how much of it matched
+    api_keys: HashMap<[u8; 32], String>,
+}
+
+impl Authenticator {
+    /// Verifies tokens signed with the keys of a local JWKS file
+    pub fn jwks(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let jwks = serde_json::from_reader(std::fs::File::open(path)?)?;
+        self.inner_mut().jwks = Some(jwks);
+        Ok(self)
+    }
+
+    /// Verifies tokens signed with a shared secret
+    pub fn secret(mut self, secret: &[u8]) -> Self {
+        self.inner_mut().secret = Some(DecodingKey::from_secret(secret));
+        self
+    }
+
+    /// Only accepts tokens issued by `issuer`
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.inner_mut().issuer = Some(issuer.into());
+        self
+    }
+
+    /// Only accepts tokens issued for `audience`
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.inner_mut().audience = Some(audience.into());
+        self
+    }
+
+    /// Accepts `key` from the service `name`. Services are trusted to change the taxonomy
+    pub fn api_key(mut self, name: impl Into<String>, key: &str) -> Self {
+        self.inner_mut()
+            .api_keys
+            .insert(Sha256::digest(key).into(), name.into());
+        self
+    }
+
+    /// Accepts the API keys in `keys`, a comma separated list of `name=key` entries
+    pub fn api_keys(self, keys: &str) -> anyhow::Result<Self> {
+        keys.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(self, |authenticator, entry| {
+                let (name, key) = entry
+                    .split_once('=')
+                    .filter(|(name, key)| !name.is_empty() && !key.is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("API keys are listed as name=key"))?;
+                Ok(authenticator.api_key(name.trim(), key.trim()))
+            })
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("authenticator to be configured before it is shared")
+    }
+
+    /// The identity the credentials in `headers` prove, none for a request without credentials
+    pub fn identify(&self, headers: &HeaderMap) -> Result<Option<Identity>, tonic::Status> {
+        if let Some(key) = headers.get(API_KEY_HEADER) {
+            let hash: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+            return match self.inner.api_keys.get(&hash) {
+                Some(name) => Ok(Some(Identity {
+                    subject: name.clone(),
+                    roles: vec![String::from(ADMIN_ROLE)],
+                })),
+                None => Err(tonic::Status::unauthenticated("unknown API key")),
+            };
+        }
+
+        let Some(authorization) = headers.get(AUTHORIZATION) else {
+            return Ok(None);
+        };
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| tonic::Status::unauthenticated("expected a bearer token"))?;
+
+        self.verify(token.trim()).map(Some)
+    }
+
+    fn verify(&self, token: &str) -> Result<Identity, tonic::Status> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            debug!(error = %e, "rejected token");
+            tonic::Status::unauthenticated(format!("invalid token: {e}"))
+        };
+
+        let header = jsonwebtoken::decode_header(token).map_err(invalid)?;
+
+        let jwks_key = match (&self.inner.jwks, header.kid.as_deref()) {
+            (Some(jwks), Some(kid)) => jwks.find(kid),
+            (Some(jwks), None) if self.inner.secret.is_none() && jwks.keys.len() == 1 => {
+                jwks.keys.first()
+            }
+            _ => None,
+        };
+        let key = match jwks_key {
+            Some(jwk) => DecodingKey::from_jwk(jwk).map_err(invalid)?,
+            None => self
+                .inner
+                .secret
+                .clone()
+                .ok_or_else(|| tonic::Status::unauthenticated("no key verifies this token"))?,
+        };
+
+        // a key only verifies the algorithms of its own family
+        let mut validation = Validation::new(header.alg);
+        match self.inner.audience {
+            Some(ref audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(ref issuer) = self.inner.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+
+        Ok(Identity {
+            subject: claims.sub,
+            roles: claims.roles,
+        })
+    }
+}
+
+/// Puts the identity of the caller in the request extensions, where tonic and GraphQL handlers
+/// find it. Requests with credentials that do not check out are turned away
+pub async fn authenticate(
+    State(authenticator): State<Authenticator>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match authenticator.identify(request.headers()) {
+        Ok(identity) => {
+            if let Some(identity) = identity {
+                request.extensions_mut().insert(identity);
+            }
+            next.run(request).await
+        }
+        Err(status) if is_grpc(request.headers()) => status.into_http().map(axum::body::Body::new),
+        Err(status) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": status.message() })),
+        )
+            .into_response(),
+    }
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/grpc"))
+}
+
+/// Lets admins through. Callers without credentials are asked for them, anyone else is denied
+pub fn authorize(identity: Option<&Identity>) -> Result<(), tonic::Status> {
+    match identity {
+        Some(identity) if identity.is_admin() => Ok(()),
+        Some(_) => Err(tonic::Status::permission_denied(format!(
+            "the {ADMIN_ROLE} role is required to make changes"
+        ))),
+        None => Err(tonic::Status::unauthenticated("sign in to make changes")),
+    }
+}
+
+/// Interceptor for the services that change the taxonomy
+pub fn require_admin(request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+    authorize(request.extensions().get::<Identity>())?;
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::Value;
+
+    use super::*;
+
+    fn token(secret: &[u8], claims: Value) -> HeaderValue {
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap();
+        format!("Bearer {token}").parse().unwrap()
+    }
+
+    fn headers(name: &'static str, value: HeaderValue) -> HeaderMap {
+        HeaderMap::from_iter([(axum::http::HeaderName::from_static(name), value)])
+    }
+
+    #[test]
+    fn verify_tokens() {
+        let authenticator = Authenticator::default().secret(b"secret");
+        let exp = jsonwebtoken::get_current_timestamp() + 60;
+
+        let identity = authenticator
+            .identify(&headers(
+                "authorization",
+                token(
+                    b"secret",
+                    json!({ "sub": "alice", "roles": ["admin"], "exp": exp }),
+                ),
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.subject, "alice");
+        assert!(identity.is_admin());
+
+        let status = authenticator
+            .identify(&headers(
+                "authorization",
+                token(b"other", json!({ "sub": "alice", "exp": exp })),
+            ))
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        // expired
+        assert!(authenticator
+            .identify(&headers(
+                "authorization",
+                token(b"secret", json!({ "sub": "alice", "exp": exp - 3600 })),
+            ))
+            .is_err());
+
+        assert_eq!(authenticator.identify(&HeaderMap::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn verify_api_keys() {
+        let authenticator = Authenticator::default()
+            .api_keys("importer=key-1, search=key-2")
+            .unwrap();
+
+        let identity = authenticator
+            .identify(&headers("x-api-key", HeaderValue::from_static("key-2")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.subject, "search");
+        assert!(identity.is_admin());
+
+        assert!(authenticator
+            .identify(&headers("x-api-key", HeaderValue::from_static("key-3")))
+            .is_err());
+        assert!(Authenticator::default().api_keys("importer").is_err());
+    }
+
+    #[test]
+    fn authorize_admins() {
+        let identity = |role: &str| Identity {
+            subject: String::from("alice"),
+            roles: vec![role.to_string()],
+        };
+
+        assert!(authorize(Some(&identity(ADMIN_ROLE))).is_ok());
+        assert_eq!(
+            authorize(Some(&identity("seller"))).unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+        assert_eq!(
+            authorize(None).unwrap_err().code(),
+            tonic::Code::Unauthenticated
+        );
+    }
+}
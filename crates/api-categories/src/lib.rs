@@ -1,9 +1,14 @@
+#![allow(clippy::result_large_err)]
+
 pub mod api;
+pub mod auth;
 pub mod routes;
 pub mod state;
 
-use api::ApiSchemaBuilder;
-use axum::{extract::Request, http::header::CONTENT_TYPE};
+use api::{persisted::PersistedQueries, ApiSchemaBuilder};
+use auth::Authenticator;
+use axum::{extract::Request, http::header::CONTENT_TYPE, middleware};
+use core_services::{shutdown::Shutdown, state::config::env_var};
 use futures_util::TryFutureExt;
 use opentelemetry::{global, trace::TraceContextExt};
 use opentelemetry_http::HeaderExtractor;
@@ -21,12 +26,45 @@ use tower_http::trace::TraceLayer;
 use tracing::{error, info, info_span, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-pub async fn run(state: ApiState, tx: oneshot::Sender<u16>) -> anyhow::Result<()> {
-    let schema = ApiSchemaBuilder::build(state.clone());
+/// Serves gRPC and GraphQL until `shutdown` starts, then stops accepting connections and gives
+/// in-flight requests until the shutdown timeout to finish
+pub async fn run(
+    state: ApiState,
+    shutdown: Shutdown,
+    tx: oneshot::Sender<u16>,
+) -> anyhow::Result<()> {
+    // events written by mutations reach jetstream through the outbox, and come back to
+    // subscribers on every replica
+    let outbox = tokio::spawn(state::relay_outbox(state.clone(), shutdown.clone()));
+    let changes = state::Changes::default();
+    tokio::spawn(state::relay_changes(
+        state.clone(),
+        changes.clone(),
+        shutdown.clone(),
+    ));
+
+    // with an allowlist, only the operations on it run
+    let persisted_queries = match std::env::var("GRAPHQL_ALLOWLIST") {
+        Ok(path) => PersistedQueries::allowlist(path)?,
+        Err(_) => PersistedQueries::Automatic(state.state.cache.clone()),
+    };
+
+    let schema = ApiSchemaBuilder::new(state.clone(), changes)
+        .limit_depth(env_var("GRAPHQL_MAX_DEPTH").parse()?)
+        .limit_complexity(env_var("GRAPHQL_MAX_COMPLEXITY").parse()?)
+        .persisted_queries(persisted_queries)
+        .shutdown(shutdown.clone())
+        .build();
 
     let addr = state.state.config.listen_address;
 
-    let web = router(schema, state.state.config.env)
+    let authenticator = authenticator()?;
+
+    let web = router(schema, state.clone(), state.state.config.env)
+        .layer(middleware::from_fn_with_state(
+            authenticator.clone(),
+            auth::authenticate,
+        ))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {
@@ -43,13 +81,30 @@ pub async fn run(state: ApiState, tx: oneshot::Sender<u16>) -> anyhow::Result<()
 
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(CATEGORY_FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
         .build_v1()?;
 
+    // serving status follows the readiness of the dependencies
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(routes::report_grpc_health(
+        state.clone(),
+        reporter,
+        shutdown.clone(),
+    ));
+
     let grpc = Routes::new(reflection_service)
+        .add_service(health_service)
         .add_service(QueryCategoriesServer::new(state.clone()))
-        .add_service(MutateCategoriesServer::new(state.clone()));
+        .add_service(MutateCategoriesServer::with_interceptor(
+            state.clone(),
+            auth::require_admin,
+        ));
     let grpc = grpc
         .into_axum_router()
+        .layer(middleware::from_fn_with_state(
+            authenticator,
+            auth::authenticate,
+        ))
         .layer(
             TraceLayer::new_for_grpc()
                 .make_span_with(|request: &Request<_>| {
@@ -90,11 +145,47 @@ pub async fn run(state: ApiState, tx: oneshot::Sender<u16>) -> anyhow::Result<()
     }
     info!(addr = ?socket_addr, "listening");
 
-    axum::serve(listener, Shared::new(service)).await?;
+    let server =
+        axum::serve(listener, Shared::new(service)).with_graceful_shutdown(shutdown.triggered());
+    if let Some(result) = shutdown.drain(server).await {
+        result?;
+    }
+    info!("stopped serving");
+
+    // events written by the last requests are sent before the connections close
+    if let Some(Err(e)) = shutdown.drain(outbox).await {
+        error!(error = %e, "outbox relay failed");
+    }
+
+    state.state.close().await;
 
     Ok(())
 }
 
+/// Verifies tokens against the JWKS file or the shared secret, whichever are set, and accepts
+/// the listed API keys. Without any of them, no one can make changes
+fn authenticator() -> anyhow::Result<Authenticator> {
+    let mut authenticator = Authenticator::default();
+
+    if let Ok(path) = std::env::var("AUTH_JWKS_FILE") {
+        authenticator = authenticator.jwks(path)?;
+    }
+    if let Ok(secret) = std::env::var("AUTH_JWT_SECRET") {
+        authenticator = authenticator.secret(secret.as_bytes());
+    }
+    if let Ok(issuer) = std::env::var("AUTH_JWT_ISSUER") {
+        authenticator = authenticator.issuer(issuer);
+    }
+    if let Ok(audience) = std::env::var("AUTH_JWT_AUDIENCE") {
+        authenticator = authenticator.audience(audience);
+    }
+    if let Ok(keys) = std::env::var("AUTH_API_KEYS") {
+        authenticator = authenticator.api_keys(&keys)?;
+    }
+
+    Ok(authenticator)
+}
+
 fn on_request<B>(request: &Request<B>, span: &Span) {
     let parent_context = global::get_text_map_propagator(|propagator| {
         propagator.extract(&HeaderExtractor(request.headers()))
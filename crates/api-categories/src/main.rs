@@ -2,6 +2,7 @@ use std::path::Path;
 
 use api_categories::state;
 use core_services::{
+    shutdown::Shutdown,
     state::config::Configuration,
     tracing::{
         config::{AppMetadata, LokiConfig},
@@ -30,12 +31,14 @@ async fn main() -> anyhow::Result<()> {
         .try_with_sentry(&config.sentry_dsn)?
         .build();
 
-    if let Some(task) = std::mem::take(&mut telemetry.loki_task) {
-        tokio::spawn(task);
-    };
+    telemetry.spawn_loki();
 
+    let shutdown = Shutdown::listen(config.shutdown_timeout);
     let state = state::ApiState::initialise(config).await?;
     let (tx, _rx) = tokio::sync::oneshot::channel();
 
-    api_categories::run(state, tx).await
+    let result = api_categories::run(state, shutdown.clone(), tx).await;
+    telemetry.shutdown(shutdown.timeout()).await;
+
+    result
 }
@@ -1,6 +1,99 @@
-use axum::{response::IntoResponse, Json};
-use serde_json::json;
+use std::time::Duration;
 
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use core_services::{shutdown::Shutdown, state::health::Check};
+use sellershut_core::categories::{
+    mutate_categories_server::MutateCategoriesServer,
+    query_categories_server::QueryCategoriesServer,
+};
+use serde_json::{json, Map, Value};
+use tonic::server::NamedService;
+use tonic_health::{server::HealthReporter, ServingStatus};
+use tracing::debug;
+
+use crate::state::ApiState;
+
+/// How long a dependency has to answer a probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the gRPC health service is brought up to date
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Liveness, the process is up and serving requests
 pub async fn health_check() -> impl IntoResponse {
     Json(json!({ "status": "ok" }))
 }
+
+/// Readiness, every dependency answers. Unavailable otherwise, with the status of each
+/// dependency in the body
+pub async fn readiness(State(state): State<ApiState>) -> impl IntoResponse {
+    let checks = state.state.probe(PROBE_TIMEOUT).await;
+    let ready = checks.iter().all(Check::is_up);
+
+    let dependencies: Map<String, Value> = checks
+        .into_iter()
+        .map(|check| {
+            let status = match check.error {
+                None => json!({ "status": "up" }),
+                Some(error) => json!({ "status": "down", "error": error }),
+            };
+            (check.name.to_string(), status)
+        })
+        .collect();
+
+    let (code, status) = match ready {
+        true => (StatusCode::OK, "ok"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+    };
+
+    (
+        code,
+        Json(json!({ "status": status, "dependencies": dependencies })),
+    )
+}
+
+/// Keeps the gRPC health service in line with the readiness of the dependencies. Once
+/// `shutdown` starts, the server is reported as not serving so traffic moves elsewhere
+pub async fn report_grpc_health(state: ApiState, mut reporter: HealthReporter, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+    let mut was_ready = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.triggered() => break,
+        }
+
+        let ready = state
+            .state
+            .probe(PROBE_TIMEOUT)
+            .await
+            .iter()
+            .all(Check::is_up);
+        if was_ready == Some(ready) {
+            continue;
+        }
+        debug!(ready, "serving status changed");
+        was_ready = Some(ready);
+
+        let status = match ready {
+            true => ServingStatus::Serving,
+            false => ServingStatus::NotServing,
+        };
+        set_status(&mut reporter, status).await;
+    }
+
+    debug!("shutting down, no longer serving");
+    set_status(&mut reporter, ServingStatus::NotServing).await;
+}
+
+async fn set_status(reporter: &mut HealthReporter, status: ServingStatus) {
+    // an empty name is the server as a whole
+    for service in [
+        "",
+        QueryCategoriesServer::<ApiState>::NAME,
+        MutateCategoriesServer::<ApiState>::NAME,
+    ] {
+        reporter.set_service_status(service, status).await;
+    }
+}
@@ -1,14 +1,22 @@
 mod health;
 
+pub use health::report_grpc_health;
+
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql_axum::{GraphQL, GraphQLSubscription};
-use axum::{response::Html, routing::get, Router};
+use async_graphql_axum::{GraphQLBatchRequest, GraphQLResponse, GraphQLSubscription};
+use axum::{extract::State, response::Html, routing::get, Extension, Router};
 use core_services::state::config::Environment;
 
-use crate::api::ApiSchema;
+use crate::{api::ApiSchema, auth::Identity, state::ApiState};
 
-pub fn router(schema: ApiSchema, env: Environment) -> Router {
-    let router = Router::new().route("/health", get(health::health_check));
+pub fn router(schema: ApiSchema, state: ApiState, env: Environment) -> Router {
+    let router = Router::new()
+        .route("/health", get(health::health_check))
+        .route("/health/live", get(health::health_check))
+        .route(
+            "/health/ready",
+            get(health::readiness).with_state(state.clone()),
+        );
 
     let router = match env {
         Environment::Development => router.route(
@@ -18,7 +26,8 @@ pub fn router(schema: ApiSchema, env: Environment) -> Router {
                     GraphQLPlaygroundConfig::new("/").subscription_endpoint("/ws"),
                 ))
             })
-            .post_service(GraphQL::new(schema.clone())),
+            .post(graphql)
+            .with_state(schema.clone()),
         ),
         Environment::Production => router.route(
             "/",
@@ -29,9 +38,23 @@ pub fn router(schema: ApiSchema, env: Environment) -> Router {
                     env!("CARGO_PKG_VERSION")
                 )
             })
-            .post_service(GraphQL::new(schema.clone())),
+            .post(graphql)
+            .with_state(schema.clone()),
         ),
     };
 
     router.route_service("/ws", GraphQLSubscription::new(schema))
 }
+
+/// Runs GraphQL operations as the caller the auth middleware identified
+async fn graphql(
+    State(schema): State<ApiSchema>,
+    identity: Option<Extension<Identity>>,
+    request: GraphQLBatchRequest,
+) -> GraphQLResponse {
+    let mut request = request.into_inner();
+    if let Some(Extension(identity)) = identity {
+        request = request.data(identity);
+    }
+    schema.execute_batch(request).await.into()
+}
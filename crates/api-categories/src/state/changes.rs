@@ -0,0 +1,166 @@
+use std::{str::FromStr, time::Duration};
+
+use async_nats::jetstream::consumer::{pull::OrderedConfig, DeliverPolicy};
+use core_services::{
+    shutdown::Shutdown,
+    state::{
+        config::env_var,
+        events::{Entity, Event},
+    },
+};
+use futures_util::StreamExt;
+use prost::Message;
+use sellershut_core::categories::{Category, CategoryList, UpsertCategoryRequest};
+use tokio::sync::broadcast;
+use tracing::{debug, trace, warn};
+
+use crate::state::ApiState;
+
+/// Changes held for subscribers that fall behind
+const CAPACITY: usize = 1024;
+
+/// Delay before watching again after a failure, doubled on every consecutive failure
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// What happened to a category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Created, or restored from the archive
+    Created,
+    /// Changed in place or moved, including parents whose sub categories changed
+    Updated,
+    /// Archived or purged
+    Deleted,
+}
+
+/// A change to a category, as published to JetStream
+#[derive(Debug, Clone)]
+pub struct CategoryChange {
+    pub kind: ChangeKind,
+    pub category: Category,
+}
+
+/// Hands category changes out to every subscriber in this process
+#[derive(Debug, Clone)]
+pub struct Changes(broadcast::Sender<CategoryChange>);
+
+impl Default for Changes {
+    fn default() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+}
+
+impl Changes {
+    /// Receives the changes made from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<CategoryChange> {
+        self.0.subscribe()
+    }
+
+    fn send(&self, kind: ChangeKind, categories: impl IntoIterator<Item = Category>) {
+        for category in categories {
+            // nobody is listening
+            let _ = self.0.send(CategoryChange { kind, category });
+        }
+    }
+}
+
+/// Reads the events mutations publish to JetStream and passes them on to `changes`. Each
+/// replica reads the stream on its own, so every replica sees every change. Runs until
+/// `shutdown` starts
+pub async fn relay_changes(state: ApiState, changes: Changes, shutdown: Shutdown) {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        let result = tokio::select! {
+            result = watch(&state, &changes, &mut backoff) => result,
+            _ = shutdown.triggered() => break,
+        };
+
+        match result {
+            Ok(_) => debug!("category changes ended, watching again"),
+            Err(e) => {
+                warn!(error = %e, retry_in = ?backoff, "could not watch category changes");
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.triggered() => break,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    debug!("stopped watching category changes");
+}
+
+async fn watch(state: &ApiState, changes: &Changes, backoff: &mut Duration) -> anyhow::Result<()> {
+    let stream = state
+        .state
+        .jetstream_context
+        .get_stream(env_var("JETSTREAM_NAME"))
+        .await?;
+
+    // an ordered consumer is not stored by the server and only reads what is published from now
+    let consumer = stream
+        .create_consumer(OrderedConfig {
+            filter_subject: format!("{}.update.index.>", Entity::Categories),
+            deliver_policy: DeliverPolicy::New,
+            ..Default::default()
+        })
+        .await?;
+
+    let mut messages = consumer.messages().await?;
+    *backoff = MIN_BACKOFF;
+    debug!("watching category changes");
+
+    while let Some(message) = messages.next().await {
+        let message = message?;
+        let subject = message.subject.as_str();
+
+        let Ok(event) = Event::from_str(subject) else {
+            warn!(
+                subject,
+                "received a message, subject cannot be mapped to event"
+            );
+            continue;
+        };
+        trace!(?event, "category change received");
+
+        if let Err(e) = dispatch(event, message.payload.as_ref(), changes) {
+            warn!(error = %e, subject, "category change cannot be decoded");
+        }
+    }
+
+    Ok(())
+}
+
+fn dispatch(event: Event, payload: &[u8], changes: &Changes) -> Result<(), prost::DecodeError> {
+    match event {
+        // creating a single category publishes the request
+        Event::SetSingle(_) => changes.send(
+            ChangeKind::Created,
+            UpsertCategoryRequest::decode(payload)?.category,
+        ),
+        Event::SetBatch(_) => changes.send(
+            ChangeKind::Created,
+            CategoryList::decode(payload)?.categories,
+        ),
+        Event::UpdateSingle(_) => {
+            changes.send(ChangeKind::Updated, Some(Category::decode(payload)?))
+        }
+        Event::UpdateBatch(_) => changes.send(
+            ChangeKind::Updated,
+            CategoryList::decode(payload)?.categories,
+        ),
+        Event::DeleteSingle(_) => {
+            changes.send(ChangeKind::Deleted, Some(Category::decode(payload)?))
+        }
+        Event::DeleteBatch(_) => changes.send(
+            ChangeKind::Deleted,
+            CategoryList::decode(payload)?.categories,
+        ),
+        _ => {}
+    }
+
+    Ok(())
+}
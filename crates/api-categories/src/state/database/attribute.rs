@@ -0,0 +1,194 @@
+use sellershut_core::categories::{AttributeType, CategoryAttribute};
+
+/// A category attribute as it is stored, with its allowed values aggregated in order
+#[derive(Debug)]
+pub struct AttributeRow {
+    pub id: String,
+    pub category_id: String,
+    pub name: String,
+    pub attribute_type: String,
+    pub allowed_values: Vec<String>,
+    pub unit: Option<String>,
+    pub required: bool,
+}
+
+impl From<AttributeRow> for CategoryAttribute {
+    fn from(value: AttributeRow) -> Self {
+        let attribute_type = match value.attribute_type.as_str() {
+            "number" => AttributeType::Number,
+            "boolean" => AttributeType::Boolean,
+            "enum" => AttributeType::Enum,
+            _ => AttributeType::Text,
+        };
+
+        Self {
+            id: value.id,
+            category_id: value.category_id,
+            name: value.name,
+            attribute_type: attribute_type.into(),
+            allowed_values: value.allowed_values,
+            unit: value.unit,
+            required: value.required,
+        }
+    }
+}
+
+/// The name an attribute type is stored under
+pub fn type_name(value: AttributeType) -> &'static str {
+    match value {
+        AttributeType::Text => "text",
+        AttributeType::Number => "number",
+        AttributeType::Boolean => "boolean",
+        AttributeType::Enum => "enum",
+    }
+}
+
+/// Checks an attribute before it is stored. Returns the attribute with its name, allowed values
+/// and unit trimmed
+pub fn validate(attribute: CategoryAttribute) -> Result<CategoryAttribute, tonic::Status> {
+    let attribute_type = AttributeType::try_from(attribute.attribute_type)
+        .map_err(|_| tonic::Status::invalid_argument("unknown attribute type"))?;
+
+    let name = attribute.name.trim().to_string();
+    if name.is_empty() {
+        return Err(tonic::Status::invalid_argument(
+            "attribute name cannot be empty",
+        ));
+    }
+
+    let mut allowed_values: Vec<String> = Vec::with_capacity(attribute.allowed_values.len());
+    for value in attribute.allowed_values.iter().map(|value| value.trim()) {
+        if value.is_empty() {
+            return Err(tonic::Status::invalid_argument(
+                "allowed values cannot be empty",
+            ));
+        }
+        if allowed_values.iter().any(|existing| existing == value) {
+            return Err(tonic::Status::invalid_argument(format!(
+                "allowed value {value} is repeated"
+            )));
+        }
+        allowed_values.push(value.to_string());
+    }
+
+    match attribute_type {
+        AttributeType::Enum if allowed_values.is_empty() => {
+            return Err(tonic::Status::invalid_argument(
+                "enum attributes need allowed values",
+            ));
+        }
+        AttributeType::Enum => {}
+        _ if !allowed_values.is_empty() => {
+            return Err(tonic::Status::invalid_argument(
+                "only enum attributes have allowed values",
+            ));
+        }
+        _ => {}
+    }
+
+    let unit = attribute
+        .unit
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    Ok(CategoryAttribute {
+        name,
+        allowed_values,
+        unit,
+        ..attribute
+    })
+}
+
+/// Merges the attributes of an ancestor chain, ordered from the top-level category down. An
+/// attribute replaces an inherited one with the same name, keeping its position
+pub fn resolve(attributes: Vec<CategoryAttribute>) -> Vec<CategoryAttribute> {
+    let mut resolved: Vec<CategoryAttribute> = Vec::with_capacity(attributes.len());
+
+    for attribute in attributes {
+        let name = attribute.name.to_lowercase();
+        match resolved
+            .iter_mut()
+            .find(|value| value.name.to_lowercase() == name)
+        {
+            Some(inherited) => *inherited = attribute,
+            None => resolved.push(attribute),
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use sellershut_core::categories::{AttributeType, CategoryAttribute};
+
+    use super::{resolve, validate};
+
+    fn attribute(category_id: &str, name: &str) -> CategoryAttribute {
+        CategoryAttribute {
+            category_id: category_id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn validate_attribute() {
+        let size = CategoryAttribute {
+            name: String::from(" Size "),
+            attribute_type: AttributeType::Enum.into(),
+            allowed_values: vec![String::from("40 "), String::from("41")],
+            unit: Some(String::from(" ")),
+            ..Default::default()
+        };
+        let size = validate(size).unwrap();
+        assert_eq!(size.name, "Size");
+        assert_eq!(size.allowed_values, vec!["40", "41"]);
+        assert_eq!(size.unit, None);
+
+        let repeated = CategoryAttribute {
+            allowed_values: vec![String::from("40"), String::from(" 40")],
+            ..size.clone()
+        };
+        assert!(validate(repeated).is_err());
+
+        let no_values = CategoryAttribute {
+            allowed_values: vec![],
+            ..size.clone()
+        };
+        assert!(validate(no_values).is_err());
+
+        let text_with_values = CategoryAttribute {
+            attribute_type: AttributeType::Text.into(),
+            ..size
+        };
+        assert!(validate(text_with_values).is_err());
+
+        assert!(validate(attribute("shoes", "  ")).is_err());
+    }
+
+    #[test]
+    fn resolve_attributes() {
+        let attributes = vec![
+            attribute("shoes", "Size"),
+            attribute("shoes", "Colour"),
+            attribute("running", "size"),
+            attribute("running", "Drop"),
+        ];
+
+        let resolved = resolve(attributes);
+        let resolved: Vec<_> = resolved
+            .iter()
+            .map(|value| (value.category_id.as_str(), value.name.as_str()))
+            .collect();
+
+        assert_eq!(
+            resolved,
+            vec![
+                ("running", "size"),
+                ("shoes", "Colour"),
+                ("running", "Drop")
+            ]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, error::Error};
+
+use async_nats::jetstream::context::PublishError;
+use core_services::cache::{RedisError, RunError};
+use sqlx::{error::ErrorKind, postgres::PgDatabaseError};
+use tonic::Code;
+use tonic_types::{BadRequest, ErrorDetail, ErrorInfo, FieldViolation, StatusExt};
+
+use super::ERROR_DOMAIN;
+
+/// [ErrorInfo] reason for a record that does not exist
+pub const NOT_FOUND: &str = "NOT_FOUND";
+
+/// [ErrorInfo] reason for a value that is already taken
+pub const ALREADY_EXISTS: &str = "ALREADY_EXISTS";
+
+/// [ErrorInfo] reason for a reference to a record that does not exist, or a record that is
+/// still referenced
+pub const INVALID_REFERENCE: &str = "INVALID_REFERENCE";
+
+/// [ErrorInfo] reason for a value the database does not accept
+pub const INVALID_VALUE: &str = "INVALID_VALUE";
+
+/// [ErrorInfo] reason for a dependency that cannot be reached, `dependency` in the metadata
+/// names it
+pub const UNAVAILABLE: &str = "UNAVAILABLE";
+
+/// Fields guarded by unique indexes, which Postgres reports by index name alone
+const UNIQUE_FIELDS: [(&str, &str); 4] = [
+    ("idx_category_external_id", "external_id"),
+    ("idx_category_parent_slug", "slug"),
+    ("idx_category_slug_alias_parent_slug", "slug"),
+    ("idx_category_attribute_name", "name"),
+];
+
+/// Maps a failure to the status a client sees. Missing records, rejected values and unavailable
+/// dependencies carry [ErrorInfo] and, when a field is at fault, [BadRequest] details. Anything
+/// else is internal
+pub fn map_err(err: impl Error + 'static) -> tonic::Status {
+    let error: &(dyn Error + 'static) = &err;
+
+    if let Some(status) = error.downcast_ref::<tonic::Status>() {
+        return status.clone();
+    }
+    if let Some(error) = error.downcast_ref::<sqlx::Error>() {
+        return map_database_err(error);
+    }
+    if error.is::<RedisError>() || error.is::<RunError<RedisError>>() {
+        return unavailable("cache", error);
+    }
+    if error.is::<PublishError>() {
+        return unavailable("jetstream", error);
+    }
+
+    tonic::Status::internal(err.to_string())
+}
+
+fn map_database_err(err: &sqlx::Error) -> tonic::Status {
+    let database_err = match err {
+        sqlx::Error::RowNotFound => {
+            return with_details(
+                Code::NotFound,
+                "record does not exist",
+                NOT_FOUND,
+                HashMap::new(),
+                None,
+            )
+        }
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+            return unavailable("postgres", err)
+        }
+        sqlx::Error::Database(database_err) => database_err,
+        _ => return tonic::Status::internal(err.to_string()),
+    };
+
+    let pg_err = database_err.try_downcast_ref::<PgDatabaseError>();
+    let constraint = database_err.constraint();
+    let field = pg_err
+        .and_then(PgDatabaseError::column)
+        .or_else(|| constraint.and_then(|value| constraint_field(value, database_err.table())))
+        .map(String::from);
+
+    let mut metadata = HashMap::new();
+    if let Some(constraint) = constraint {
+        metadata.insert(String::from("constraint"), constraint.to_string());
+    }
+    if let Some(table) = database_err.table() {
+        metadata.insert(String::from("table"), table.to_string());
+    }
+
+    let (code, message, reason) = match database_err.kind() {
+        ErrorKind::UniqueViolation => (
+            Code::AlreadyExists,
+            "value is already taken",
+            ALREADY_EXISTS,
+        ),
+        ErrorKind::ForeignKeyViolation => (
+            Code::FailedPrecondition,
+            "refers to a record that does not exist, or is still referred to",
+            INVALID_REFERENCE,
+        ),
+        ErrorKind::NotNullViolation | ErrorKind::CheckViolation => (
+            Code::InvalidArgument,
+            "value is not accepted",
+            INVALID_VALUE,
+        ),
+        _ => match database_err.code().as_deref() {
+            // serialization failure and deadlock, the request can be retried
+            Some("40001" | "40P01") => return tonic::Status::aborted(database_err.message()),
+            // value too long and malformed values
+            Some("22001" | "22P02") => (
+                Code::InvalidArgument,
+                "value is not accepted",
+                INVALID_VALUE,
+            ),
+            _ => return tonic::Status::internal(err.to_string()),
+        },
+    };
+
+    with_details(code, message, reason, metadata, field)
+}
+
+/// The field a named constraint on `table` guards. Foreign keys are named after the table and
+/// column that refer to another record
+fn constraint_field<'a>(constraint: &'a str, table: Option<&str>) -> Option<&'a str> {
+    UNIQUE_FIELDS
+        .iter()
+        .find(|(name, _)| *name == constraint)
+        .map(|(_, field)| *field)
+        .or_else(|| {
+            constraint
+                .strip_prefix(table?)?
+                .strip_prefix('_')?
+                .strip_suffix("_fkey")
+        })
+}
+
+/// Rejects a request that leaves out a required `field`
+pub fn missing_field(field: &str) -> tonic::Status {
+    with_details(
+        Code::InvalidArgument,
+        &format!("{field} is required"),
+        INVALID_VALUE,
+        HashMap::new(),
+        Some(field.to_string()),
+    )
+}
+
+/// Rejects a request with every field at fault and why
+pub fn invalid_fields(violations: Vec<FieldViolation>) -> tonic::Status {
+    let message = violations
+        .iter()
+        .map(|violation| violation.description.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let details = [
+        ErrorDetail::from(ErrorInfo::new(INVALID_VALUE, ERROR_DOMAIN, HashMap::new())),
+        ErrorDetail::from(BadRequest::new(violations)),
+    ];
+
+    tonic::Status::with_error_details_vec(Code::InvalidArgument, message, details)
+}
+
+/// Rejects a value of `field` that another record already holds
+pub fn already_taken(field: &str, message: &str) -> tonic::Status {
+    with_details(
+        Code::AlreadyExists,
+        message,
+        ALREADY_EXISTS,
+        HashMap::new(),
+        Some(field.to_string()),
+    )
+}
+
+fn unavailable(dependency: &str, err: &(dyn Error + 'static)) -> tonic::Status {
+    with_details(
+        Code::Unavailable,
+        &format!("{dependency} is unavailable: {err}"),
+        UNAVAILABLE,
+        HashMap::from([(String::from("dependency"), dependency.to_string())]),
+        None,
+    )
+}
+
+fn with_details(
+    code: Code,
+    message: &str,
+    reason: &str,
+    metadata: HashMap<String, String>,
+    field: Option<String>,
+) -> tonic::Status {
+    let mut details = vec![ErrorDetail::from(ErrorInfo::new(
+        reason,
+        ERROR_DOMAIN,
+        metadata,
+    ))];
+    if let Some(field) = field {
+        details.push(ErrorDetail::from(BadRequest::with_violation(
+            field, message,
+        )));
+    }
+
+    tonic::Status::with_error_details_vec(code, message, details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_missing_rows() {
+        let status = map_err(sqlx::Error::RowNotFound);
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.get_details_error_info().unwrap().reason, NOT_FOUND);
+    }
+
+    #[test]
+    fn map_outages() {
+        let status = map_err(sqlx::Error::PoolTimedOut);
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let status = map_err(RedisError::from(std::io::Error::from(
+            std::io::ErrorKind::ConnectionRefused,
+        )));
+        assert_eq!(status.code(), Code::Unavailable);
+        let info = status.get_details_error_info().unwrap();
+        assert_eq!(info.metadata["dependency"], "cache");
+    }
+
+    #[test]
+    fn keep_statuses() {
+        let status = map_err(tonic::Status::not_found("category does not exist"));
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "category does not exist");
+    }
+
+    #[test]
+    fn find_constraint_fields() {
+        assert_eq!(
+            constraint_field("category_parent_id_fkey", Some("category")),
+            Some("parent_id")
+        );
+        assert_eq!(
+            constraint_field(
+                "category_attribute_category_id_fkey",
+                Some("category_attribute")
+            ),
+            Some("category_id")
+        );
+        assert_eq!(
+            constraint_field("idx_category_parent_slug", Some("category")),
+            Some("slug")
+        );
+        assert_eq!(constraint_field("category_pkey", Some("category")), None);
+    }
+}
@@ -0,0 +1,241 @@
+use core_services::{
+    pagination::{self, KeyKind, KeyValue, Keyed, Keyset, PaginationError, SortKey},
+    state::ServiceState,
+};
+use prost::Message;
+use sellershut_core::{
+    categories::{
+        Category, CategoryFilter, CategoryLevel, CategorySortField, Connection, Node, SortDirection,
+    },
+    common::pagination::{Cursor, CursorSigner, Offset, PageInfo},
+};
+use sqlx::{Postgres, QueryBuilder};
+use tracing::{debug_span, Instrument};
+
+use crate::api::entity;
+
+use super::{cursor_signer, map_err};
+
+/// How a listing of categories is ordered and narrowed down
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    pub sort: CategorySortField,
+    pub direction: SortDirection,
+    pub filter: CategoryFilter,
+    pub include_archived: bool,
+    /// Only list the sub categories of this category
+    pub parent_id: Option<String>,
+}
+
+impl Keyed for entity::Category {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn key(&self, column: &str) -> Option<KeyValue> {
+        match column {
+            "created_at" => Some(KeyValue::Time(self.created_at)),
+            "updated_at" => Some(KeyValue::Time(self.updated_at)),
+            "name" => Some(KeyValue::Text(self.name.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl ListOptions {
+    /// Whether this is the listing that is cached, oldest first with no filter
+    pub fn is_default(&self) -> bool {
+        self.sort == CategorySortField::CreatedAt
+            && self.direction == SortDirection::Asc
+            && self.filter == CategoryFilter::default()
+            && self.parent_id.is_none()
+    }
+
+    fn keyset<'a>(&'a self, signer: &'a CursorSigner) -> Keyset<'a> {
+        let (column, kind) = match self.sort {
+            CategorySortField::CreatedAt => ("created_at", KeyKind::Time),
+            CategorySortField::UpdatedAt => ("updated_at", KeyKind::Time),
+            CategorySortField::Name => ("name", KeyKind::Text),
+        };
+
+        let sort = SortKey {
+            column,
+            kind,
+            descending: self.direction == SortDirection::Desc,
+        };
+
+        // cursors only carry on a listing with the same filter
+        let mut scope = format!(
+            "archived={}:parent={}:filter=",
+            self.include_archived,
+            self.parent_id.as_deref().unwrap_or_default()
+        )
+        .into_bytes();
+        scope.extend(self.filter.encode_to_vec());
+
+        Keyset::new("select * from category", sort, signer)
+            .filter(&scope, |query| self.push_filters(query))
+    }
+
+    fn push_filters(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push(" and (");
+        query.push_bind(self.include_archived);
+        query.push(" or deleted_at is null)");
+
+        if let Some(ref parent_id) = self.parent_id {
+            query.push(" and parent_id = ");
+            query.push_bind(parent_id.clone());
+        }
+
+        if let Some(ref prefix) = self.filter.name_prefix {
+            query.push(" and name ilike ");
+            query.push_bind(format!("{}%", escape_like(prefix)));
+        }
+
+        if let Some(ref value) = self.filter.name_contains {
+            query.push(" and name ilike ");
+            query.push_bind(format!("%{}%", escape_like(value)));
+        }
+
+        match self.filter.has_image {
+            Some(true) => query.push(" and image_url is not null"),
+            Some(false) => query.push(" and image_url is null"),
+            None => query,
+        };
+
+        // sub_categories only lists live children
+        match self.filter.level() {
+            CategoryLevel::All => query,
+            CategoryLevel::Root => query.push(" and parent_id is null"),
+            CategoryLevel::Leaf => query.push(" and cardinality(sub_categories) = 0"),
+        };
+    }
+}
+
+/// Escapes the wildcards of a `like` pattern so user input is matched literally
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Maps pagination errors, a cursor that cannot be used is the caller's mistake
+fn map_pagination(error: PaginationError) -> tonic::Status {
+    match error {
+        PaginationError::MissingIndex => {
+            tonic::Status::invalid_argument("missing pagination index")
+        }
+        PaginationError::InvalidCursor(error) => tonic::Status::invalid_argument(error.to_string()),
+        error => map_err(error),
+    }
+}
+
+fn to_connection(
+    edges: Vec<pagination::Edge<entity::Category>>,
+    page_info: PageInfo,
+) -> Connection {
+    Connection {
+        edges: edges
+            .into_iter()
+            .map(|edge| Node {
+                node: Some(Category::from(edge.node)),
+                cursor: edge.cursor,
+            })
+            .collect(),
+        page_info: Some(page_info),
+    }
+}
+
+/// Lists a page of at most `max` categories ordered and filtered by `options`. The cursor of each
+/// node holds the sort key, so a cursor only makes sense with the options it was returned for
+pub async fn list_categories(
+    state: &ServiceState,
+    options: &ListOptions,
+    pagination: &Cursor,
+) -> Result<Connection, tonic::Status> {
+    let signer = cursor_signer(state);
+    let connection = options
+        .keyset(&signer)
+        .paginate::<entity::Category>(&state.db_pool, pagination, state.config.query_limit)
+        .await
+        .map_err(map_pagination)?;
+
+    Ok(to_connection(connection.edges, connection.page_info))
+}
+
+/// Lists the categories from `offset`, ordered and filtered by `options`. Nodes still have
+/// cursors, so a client can carry on with cursor pagination
+pub async fn list_categories_from(
+    state: &ServiceState,
+    options: &ListOptions,
+    offset: &Offset,
+) -> Result<Connection, tonic::Status> {
+    if offset.offset < 0 {
+        return Err(tonic::Status::invalid_argument("offset cannot be negative"));
+    }
+    if offset.limit < 1 {
+        return Err(tonic::Status::invalid_argument("limit must be positive"));
+    }
+    let count = offset.limit.min(state.config.query_limit) as usize;
+
+    let signer = cursor_signer(state);
+    let keyset = options.keyset(&signer);
+    let mut query = keyset.query("select * from category");
+    keyset.push_order(&mut query, false);
+    query.push(" offset ");
+    query.push_bind(offset.offset as i64);
+    query.push(" limit ");
+    query.push_bind(count as i64 + 1);
+
+    let mut categories = query
+        .build_query_as::<entity::Category>()
+        .fetch_all(&state.db_pool)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?;
+
+    let has_next_page = categories.len() > count;
+    categories.truncate(count);
+
+    let edges = keyset.edges(categories).map_err(map_pagination)?;
+
+    Ok(to_connection(
+        edges,
+        PageInfo {
+            has_next_page,
+            has_previous_page: offset.offset > 0,
+            ..Default::default()
+        },
+    ))
+}
+
+/// The number of categories in the listing described by `options`
+pub async fn count_categories(
+    state: &ServiceState,
+    options: &ListOptions,
+) -> Result<i64, tonic::Status> {
+    let signer = cursor_signer(state);
+    let mut query = options
+        .keyset(&signer)
+        .query("select count(*) from category");
+
+    query
+        .build_query_scalar::<i64>()
+        .fetch_one(&state.db_pool)
+        .instrument(debug_span!("pg.select.count"))
+        .await
+        .map_err(map_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_like;
+
+    #[test]
+    fn escape_like_pattern() {
+        assert_eq!(escape_like("shoes"), "shoes");
+        assert_eq!(escape_like("100%_off"), "100\\%\\_off");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+}
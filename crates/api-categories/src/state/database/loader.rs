@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use async_graphql::dataloader::Loader;
+use core_services::{
+    cache::{
+        key::{CacheKey, CategoryParams},
+        PoolLike, PooledConnectionLike,
+    },
+    state::ServiceState,
+};
+use prost::Message;
+use sellershut_core::categories::Category;
+use tracing::{debug, debug_span, instrument, warn, Instrument};
+
+use crate::api::{entity, error::map_status};
+
+use super::map_err;
+
+/// Loads live categories by ID, in their default language. Categories requested together, such
+/// as the parents of every category on a page, are read with a single cache MGET and whatever the
+/// cache misses with a single query
+pub struct CategoryLoader {
+    state: ServiceState,
+}
+
+impl CategoryLoader {
+    pub fn new(state: ServiceState) -> Self {
+        Self { state }
+    }
+}
+
+impl Loader<String> for CategoryLoader {
+    type Value = entity::Category;
+    type Error = async_graphql::Error;
+
+    #[instrument(skip(self), err(Debug))]
+    async fn load(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, entity::Category>, async_graphql::Error> {
+        let mut categories = read_cached(&self.state, ids).await;
+
+        let missing: Vec<String> = ids
+            .iter()
+            .filter(|id| !categories.contains_key(*id))
+            .cloned()
+            .collect();
+
+        if !missing.is_empty() {
+            debug!(count = missing.len(), "cache miss");
+            let rows = sqlx::query_as!(
+                entity::Category,
+                "select * from category where id = any($1) and deleted_at is null",
+                &missing
+            )
+            .fetch_all(&self.state.db_pool)
+            .instrument(debug_span!("pg.select.*"))
+            .await
+            .map_err(|e| map_status(map_err(e)))?;
+
+            categories.extend(rows.into_iter().map(|row| (row.id.clone(), row)));
+        }
+
+        Ok(categories)
+    }
+}
+
+/// Reads the categories the cache holds. The cache being unavailable is the same as every
+/// category missing from it
+async fn read_cached(state: &ServiceState, ids: &[String]) -> HashMap<String, entity::Category> {
+    let keys: Vec<_> = ids
+        .iter()
+        .map(|id| CacheKey::Category(CategoryParams { id, locale: None }))
+        .collect();
+
+    let payloads = async {
+        let mut cache = state.cache.get().await.map_err(map_err)?;
+
+        // GET, used for a single key, answers with a value rather than a list
+        match keys.as_slice() {
+            [key] => cache
+                .get::<_, Option<Vec<u8>>>(key)
+                .await
+                .map(|payload| vec![payload]),
+            keys => cache.get::<_, Vec<Option<Vec<u8>>>>(keys).await,
+        }
+        .map_err(map_err)
+    }
+    .instrument(debug_span!("cache.get"))
+    .await;
+
+    let payloads = match payloads {
+        Ok(payloads) => payloads,
+        Err(e) => {
+            warn!(error = %e, "could not read categories from the cache");
+            return HashMap::new();
+        }
+    };
+
+    ids.iter()
+        .zip(payloads)
+        .filter_map(|(id, payload)| {
+            let category = Category::decode(payload?.as_slice()).ok()?;
+            // anything else under the key is a miss
+            (&category.id == id).then_some(category)
+        })
+        .filter_map(|category| entity::Category::try_from(category).ok())
+        .map(|category| (category.id.clone(), category))
+        .collect()
+}
@@ -0,0 +1,53 @@
+/// Normalises a language tag so `pt_BR`, `pt-BR` and `pt-br` are the same locale. Returns [None]
+/// if `value` is not made of dash separated subtags of up to 8 letters and digits, starting with
+/// a language
+pub fn normalise(value: &str) -> Option<String> {
+    let locale = value.trim().replace('_', "-").to_ascii_lowercase();
+
+    let mut subtags = locale.split('-');
+    let language = subtags.next()?;
+
+    let is_language =
+        (2..=8).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic());
+    let is_subtag = |value: &str| {
+        (1..=8).contains(&value.len()) && value.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+
+    (is_language && locale.len() <= 35 && subtags.all(is_subtag)).then_some(locale)
+}
+
+/// Locales to look for a translation in, most specific first: `zh-hant-tw`, then `zh-hant`,
+/// then `zh`. Expects a normalised locale
+pub fn fallbacks(locale: &str) -> Vec<String> {
+    let mut locales = vec![locale.to_string()];
+    let mut current = locale;
+
+    while let Some((parent, _)) = current.rsplit_once('-') {
+        locales.push(parent.to_string());
+        current = parent;
+    }
+
+    locales
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fallbacks, normalise};
+
+    #[test]
+    fn normalise_locale() {
+        assert_eq!(normalise("fr").as_deref(), Some("fr"));
+        assert_eq!(normalise(" pt_BR ").as_deref(), Some("pt-br"));
+        assert_eq!(normalise("zh-Hant-TW").as_deref(), Some("zh-hant-tw"));
+        assert_eq!(normalise(""), None);
+        assert_eq!(normalise("f"), None);
+        assert_eq!(normalise("fr--ca"), None);
+        assert_eq!(normalise("fr-ca!"), None);
+    }
+
+    #[test]
+    fn locale_fallbacks() {
+        assert_eq!(fallbacks("fr"), vec!["fr"]);
+        assert_eq!(fallbacks("zh-hant-tw"), vec!["zh-hant-tw", "zh-hant", "zh"]);
+    }
+}
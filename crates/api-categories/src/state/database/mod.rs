@@ -1,44 +1,113 @@
-use std::error::Error;
+use std::collections::HashMap;
 
 use async_nats::{jetstream::Context, HeaderMap, HeaderValue};
-use core_services::state::{events::Event, utils::NatsMetadataInjector};
+use core_services::state::{events::Event, ServiceState};
 use opentelemetry::global;
 use prost::Message;
+use sellershut_core::{categories::Category, common::pagination::CursorSigner};
+use tonic_types::{ErrorDetail, ErrorInfo, PreconditionFailure, StatusExt};
 use tracing::{debug_span, instrument, trace, Instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+mod attribute;
+mod error;
+mod listing;
+mod loader;
+mod locale;
 pub mod mutation;
+mod outbox;
 pub mod query;
+mod revision;
+mod slug;
+mod validate;
 
-pub fn map_err(err: impl Error) -> tonic::Status {
-    tonic::Status::new(tonic::Code::Internal, err.to_string())
+pub use error::{
+    already_taken, invalid_fields, map_err, missing_field, ALREADY_EXISTS, INVALID_REFERENCE,
+    INVALID_VALUE, NOT_FOUND, UNAVAILABLE,
+};
+pub use loader::CategoryLoader;
+pub use outbox::relay_outbox;
+
+/// [ErrorInfo](tonic_types::ErrorInfo) reason for an update carrying a stale version
+pub const VERSION_CONFLICT: &str = "VERSION_CONFLICT";
+
+/// Error domain of the categories service
+pub const ERROR_DOMAIN: &str = "categories.sellershut";
+
+/// Signs the pagination cursors handed out by the service
+pub(crate) fn cursor_signer(state: &ServiceState) -> CursorSigner {
+    CursorSigner::new(state.config.cursor_secret.as_bytes())
 }
 
-#[instrument(skip(value, event, jetstream), err(Debug))]
-async fn publish_event(
-    value: impl Message,
-    event: Event,
-    jetstream: &Context,
-) -> Result<(), tonic::Status> {
-    let mut headers = HeaderMap::new();
-    let buf = Message::encode_to_vec(&value);
+/// Rejects an update made against an older version of `current`. The current version and state
+/// of the category are sent back as error details so the caller can retry
+fn version_conflict(current: Category) -> tonic::Status {
+    let version = current.version.to_string();
+    let id = current.id.clone();
+    let category = match serde_json::to_string(&current) {
+        Ok(category) => category,
+        Err(e) => return map_err(e),
+    };
 
-    let event = event.to_string();
+    let details = [
+        ErrorDetail::from(ErrorInfo::new(
+            VERSION_CONFLICT,
+            ERROR_DOMAIN,
+            HashMap::from([
+                (String::from("id"), id),
+                (String::from("version"), version),
+                (String::from("category"), category),
+            ]),
+        )),
+        ErrorDetail::from(PreconditionFailure::with_violation(
+            VERSION_CONFLICT,
+            current.id,
+            "category was changed since it was read",
+        )),
+    ];
+
+    tonic::Status::with_error_details_vec(
+        tonic::Code::FailedPrecondition,
+        "category was changed since it was read",
+        details,
+    )
+}
+
+/// Trace context of the current span, sent along with an event so handling it joins the trace
+fn trace_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
 
     global::get_text_map_propagator(|propagator| {
         let context = Span::current().context();
         trace!("injecting opentelemetry context");
-        propagator.inject_context(&context, &mut NatsMetadataInjector(&mut headers))
+        propagator.inject_context(&context, &mut headers)
     });
 
     if let Some(span) = sentry::configure_scope(|scope| scope.get_span()) {
         trace!("updating sentry headers");
         for (k, v) in span.iter_headers() {
-            let value = HeaderValue::from(v.as_str());
-            headers.insert(k, value);
+            headers.insert(k.to_string(), v);
         }
     }
 
+    headers
+}
+
+#[instrument(skip(value, event, jetstream), err(Debug))]
+async fn publish_event(
+    value: impl Message,
+    event: Event,
+    jetstream: &Context,
+) -> Result<(), tonic::Status> {
+    let mut headers = HeaderMap::new();
+    let buf = Message::encode_to_vec(&value);
+
+    let event = event.to_string();
+
+    for (k, v) in trace_headers() {
+        headers.insert(k.as_str(), HeaderValue::from(v.as_str()));
+    }
+
     jetstream
         .publish_with_headers(event, headers, buf.into())
         .instrument(debug_span!("jetstream.publish"))
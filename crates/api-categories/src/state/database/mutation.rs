@@ -1,48 +1,93 @@
+use std::collections::HashMap;
+
 use core_services::state::events::{Entity, Event};
 use sellershut_core::{
     categories::{
-        mutate_categories_server::MutateCategories, Category, CategoryEvent, DeleteCategoryRequest,
-        UpsertCategoryRequest,
+        mutate_categories_server::MutateCategories, Category, CategoryAttribute, CategoryEvent,
+        CategoryList, CategoryTranslationList, DeleteCategoriesRequest,
+        DeleteCategoryAttributeRequest, DeleteCategoryRequest, DeleteCategoryResponse,
+        DeleteStrategy, MoveCategoryRequest, PurgeCategoryRequest, RestoreCategoryRequest,
+        RevertToRevisionRequest, SetCategoryTranslationsRequest, UpsertCategoriesRequest,
+        UpsertCategoryAttributeRequest, UpsertCategoryRequest,
     },
     common::id::generate_id,
-    google::protobuf::Empty,
 };
+use sqlx::{types::Json, PgConnection};
 use tracing::{debug, debug_span, Instrument};
 
-use crate::{
-    api::entity,
-    state::{database::publish_event, ApiState},
-};
+use crate::{api::entity, state::ApiState};
 
-use super::map_err;
+use super::{
+    already_taken, attribute, map_err, missing_field,
+    outbox::enqueue_event,
+    query::{select_attribute, select_translations},
+    revision::{begin, request_actor, select_revision},
+    slug::{deduplicate, slugify},
+    validate, version_conflict,
+};
 
 #[tonic::async_trait]
 impl MutateCategories for ApiState {
     #[doc = " Create a category"]
-    #[must_use]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn create(
         &self,
         request: tonic::Request<UpsertCategoryRequest>,
     ) -> Result<tonic::Response<Category>, tonic::Status> {
-        let category = request.into_inner().category.expect("category to exist");
+        let actor = request_actor(&request);
+        let category = request
+            .into_inner()
+            .category
+            .ok_or_else(|| missing_field("category"))?;
+        let category = validate::category(category, "category", false)?;
         let id = generate_id();
 
-        // Check if the value fits within the range of i64
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        // sub categories are derived from the parent_id of other categories, a new category has none
+        let parent = match category.parent_id {
+            Some(ref parent_id) => Some(
+                add_sub_category(&mut transaction, parent_id, &id)
+                    .await?
+                    .ok_or_else(|| tonic::Status::not_found("parent category does not exist"))?,
+            ),
+            None => None,
+        };
+
+        ensure_unique_name(
+            &mut transaction,
+            category.parent_id.as_deref(),
+            &id,
+            &category.name,
+            "category.name",
+        )
+        .await?;
+
+        let slug = slugify(if category.slug.is_empty() {
+            &category.name
+        } else {
+            &category.slug
+        });
+        let slug =
+            available_slug(&mut transaction, category.parent_id.as_deref(), &id, &slug).await?;
+        remove_alias(&mut transaction, category.parent_id.as_deref(), &slug).await?;
+
         let category = sqlx::query_as!(
             entity::Category,
-            "insert into category (id, name, sub_categories, image_url, parent_id)
-                values ($1, $2, $3, $4, $5) returning *",
+            "insert into category (id, name, slug, description, sub_categories, image_url, parent_id, external_id)
+                values ($1, $2, $3, $4, '{}', $5, $6, $7) returning *",
             &id,
             &category.name,
-            &category.sub_categories,
+            slug,
+            category.description,
             category.image_url,
-            category.parent_id
+            category.parent_id,
+            category.external_id
         )
-        .fetch_one(&self.state.db_pool)
+        .fetch_one(&mut *transaction)
         .instrument(debug_span!("pg.insert"))
         .await
-        .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        .map_err(map_err)?;
 
         let category = Category::from(category);
 
@@ -53,63 +98,1363 @@ impl MutateCategories for ApiState {
 
         let event = Event::SetSingle(Entity::Categories);
 
-        publish_event(req, event, &self.state.jetstream_context).await?;
+        enqueue_event(&mut transaction, req, event).await?;
+
+        if let Some(parent) = parent {
+            let event = Event::UpdateSingle(Entity::Categories);
+            enqueue_event(&mut transaction, Category::from(parent), event).await?;
+        }
+
+        transaction.commit().await.map_err(map_err)?;
 
         Ok(tonic::Response::new(category))
     }
 
     #[doc = " Update a category"]
-    #[must_use]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn update(
         &self,
         request: tonic::Request<UpsertCategoryRequest>,
     ) -> Result<tonic::Response<Category>, tonic::Status> {
-        let category = request.into_inner().category.expect("category to exist");
-        // Check if the value fits within the range of i64
+        let actor = request_actor(&request);
+        let category = request
+            .into_inner()
+            .category
+            .ok_or_else(|| missing_field("category"))?;
+        let category = validate::category(category, "category", true)?;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let (category, parents) = update_category(&mut transaction, category, "category").await?;
+
+        let category = Category::from(category);
+
+        for value in
+            std::iter::once(category.clone()).chain(parents.into_iter().map(Category::from))
+        {
+            let event = Event::UpdateSingle(Entity::Categories);
+            enqueue_event(&mut transaction, value, event).await?;
+        }
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(category))
+    }
+
+    #[doc = " Archive a category, its children are handled according to the strategy"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn delete(
+        &self,
+        request: tonic::Request<DeleteCategoryRequest>,
+    ) -> Result<tonic::Response<DeleteCategoryResponse>, tonic::Status> {
+        let actor = request_actor(&request);
+        let request = request.into_inner();
+        let strategy = DeleteStrategy::try_from(request.strategy)
+            .map_err(|_| tonic::Status::invalid_argument("unknown delete strategy"))?;
+        let id = request.id;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let reparented = match strategy {
+            DeleteStrategy::Cascade => vec![],
+            DeleteStrategy::Reparent => reparent_children(&mut transaction, &id).await?,
+            DeleteStrategy::Restrict => {
+                let has_children = sqlx::query_scalar!(
+                    r#"
+                        select exists(
+                            select 1 from category where parent_id = $1 and deleted_at is null
+                        ) as "has_children!"
+                    "#,
+                    id
+                )
+                .fetch_one(&mut *transaction)
+                .instrument(debug_span!("pg.select.exists"))
+                .await
+                .map_err(map_err)?;
+
+                if has_children {
+                    return Err(tonic::Status::failed_precondition(
+                        "category has sub categories",
+                    ));
+                }
+                vec![]
+            }
+        };
+
+        let (categories, parents) = archive(&mut transaction, &[id]).await?;
+
+        // a dry run goes through the same statements so the preview matches the real delete
+        if request.dry_run {
+            transaction.rollback().await.map_err(map_err)?;
+            debug!(count = categories.len(), "dry run, rows not archived");
+
+            return Ok(tonic::Response::new(DeleteCategoryResponse {
+                deleted: categories.into_iter().map(Category::from).collect(),
+                reparented: reparented.into_iter().map(Category::from).collect(),
+            }));
+        }
+
+        debug!(count = categories.len(), "rows archived");
+
+        let reparented: Vec<_> = reparented.into_iter().map(Category::from).collect();
+
+        let updated = parents
+            .into_iter()
+            .map(Category::from)
+            .chain(reparented.iter().cloned())
+            .collect();
+
+        let deleted = enqueue_archived(&mut transaction, categories, updated).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(DeleteCategoryResponse {
+            deleted: deleted.categories,
+            reparented,
+        }))
+    }
+
+    #[doc = " Move a category under a different parent"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn r#move(
+        &self,
+        request: tonic::Request<MoveCategoryRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        let actor = request_actor(&request);
+        let MoveCategoryRequest { id, parent_id } = request.into_inner();
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let current = select_for_update(&mut transaction, &id).await?;
+
+        let (category, parents) =
+            reparent(&mut transaction, &current, parent_id.as_deref()).await?;
+
+        ensure_unique_name(
+            &mut transaction,
+            parent_id.as_deref(),
+            &id,
+            &category.name,
+            "parent_id",
+        )
+        .await?;
+
+        debug!(id = id, "category moved");
+
+        let category = Category::from(category);
+
+        for value in
+            std::iter::once(category.clone()).chain(parents.into_iter().map(Category::from))
+        {
+            let event = Event::UpdateSingle(Entity::Categories);
+            enqueue_event(&mut transaction, value, event).await?;
+        }
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(category))
+    }
+
+    #[doc = " Create many categories in a single transaction"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn create_many(
+        &self,
+        request: tonic::Request<UpsertCategoriesRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let actor = request_actor(&request);
+        let categories = validate::categories(request.into_inner().categories, false)?;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let mut taken: HashMap<Option<String>, Vec<String>> = HashMap::new();
+        let mut taken_names: HashMap<Option<String>, Vec<String>> = HashMap::new();
+        {
+            let parent_ids: Vec<_> = categories
+                .iter()
+                .filter_map(|value| value.parent_id.clone())
+                .collect();
+            let has_root = categories.iter().any(|value| value.parent_id.is_none());
+
+            let rows = sqlx::query!(
+                r#"select parent_id, slug, name, deleted_at is null as "is_live!" from category
+                    where parent_id = any($1) or ($2 and parent_id is null)"#,
+                &parent_ids,
+                has_root
+            )
+            .fetch_all(&mut *transaction)
+            .instrument(debug_span!("pg.select.slug"))
+            .await
+            .map_err(map_err)?;
+
+            for row in rows {
+                if row.is_live {
+                    taken_names
+                        .entry(row.parent_id.clone())
+                        .or_default()
+                        .push(row.name.to_lowercase());
+                }
+                taken.entry(row.parent_id).or_default().push(row.slug);
+            }
+        }
+
+        let mut ids = Vec::with_capacity(categories.len());
+        let mut names = Vec::with_capacity(categories.len());
+        let mut slugs = Vec::with_capacity(categories.len());
+        let mut descriptions = Vec::with_capacity(categories.len());
+        let mut image_urls = Vec::with_capacity(categories.len());
+        let mut parent_ids = Vec::with_capacity(categories.len());
+        let mut external_ids = Vec::with_capacity(categories.len());
+
+        for (index, category) in categories.into_iter().enumerate() {
+            // siblings in the same batch cannot share a name or a slug either
+            let names_taken = taken_names.entry(category.parent_id.clone()).or_default();
+            let name = category.name.to_lowercase();
+            if names_taken.contains(&name) {
+                return Err(name_taken(
+                    &format!("categories[{index}].name"),
+                    &category.name,
+                ));
+            }
+            names_taken.push(name);
+
+            let siblings = taken.entry(category.parent_id.clone()).or_default();
+            let slug = deduplicate(
+                &slugify(if category.slug.is_empty() {
+                    &category.name
+                } else {
+                    &category.slug
+                }),
+                siblings,
+            );
+            siblings.push(slug.clone());
+
+            ids.push(generate_id());
+            names.push(category.name);
+            slugs.push(slug);
+            descriptions.push(category.description);
+            image_urls.push(category.image_url);
+            parent_ids.push(category.parent_id);
+            external_ids.push(category.external_id);
+        }
+
+        let parents = sqlx::query_as!(
+            entity::Category,
+            r#"
+                update category set sub_categories = category.sub_categories || children.ids
+                from (
+                    select parent_id, array_agg(id order by position) as ids
+                    from unnest($1::varchar[], $2::varchar[]) with ordinality as t(id, parent_id, position)
+                    where parent_id is not null
+                    group by parent_id
+                ) children
+                where category.id = children.parent_id and category.deleted_at is null
+                returning category.*
+            "#,
+            &ids,
+            &parent_ids as &[Option<String>],
+        )
+        .fetch_all(&mut *transaction)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+
+        let missing_parent = parent_ids
+            .iter()
+            .flatten()
+            .any(|parent_id| !parents.iter().any(|parent| &parent.id == parent_id));
+
+        if missing_parent {
+            return Err(tonic::Status::not_found("parent category does not exist"));
+        }
+
+        sqlx::query!(
+            "delete from category_slug_alias alias
+                using unnest($1::varchar[], $2::varchar[]) as t(parent_id, slug)
+                where alias.parent_id is not distinct from t.parent_id and alias.slug = t.slug",
+            &parent_ids as &[Option<String>],
+            &slugs,
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+
+        let categories = sqlx::query_as!(
+            entity::Category,
+            r#"
+                insert into category (id, name, slug, description, sub_categories, image_url, parent_id, external_id)
+                select id, name, slug, description, '{}', image_url, parent_id, external_id
+                from unnest(
+                    $1::varchar[], $2::varchar[], $3::varchar[], $4::varchar[], $5::varchar[],
+                    $6::varchar[], $7::varchar[]
+                ) with ordinality as t(id, name, slug, description, image_url, parent_id, external_id, position)
+                order by position
+                returning *
+            "#,
+            &ids,
+            &names,
+            &slugs,
+            &descriptions as &[Option<String>],
+            &image_urls as &[Option<String>],
+            &parent_ids as &[Option<String>],
+            &external_ids as &[Option<String>],
+        )
+        .fetch_all(&mut *transaction)
+        .instrument(debug_span!("pg.insert"))
+        .await
+        .map_err(map_err)?;
+
+        debug!(count = categories.len(), "rows inserted");
+
+        let created = CategoryList {
+            categories: categories.into_iter().map(Category::from).collect(),
+        };
+
+        let payload = CategoryList {
+            categories: created
+                .categories
+                .iter()
+                .cloned()
+                .chain(parents.into_iter().map(Category::from))
+                .collect(),
+        };
+
+        let event = Event::SetBatch(Entity::Categories);
+
+        enqueue_event(&mut transaction, payload, event).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(created))
+    }
+
+    #[doc = " Update many categories in a single transaction"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn update_many(
+        &self,
+        request: tonic::Request<UpsertCategoriesRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let actor = request_actor(&request);
+        let categories = validate::categories(request.into_inner().categories, true)?;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let mut updated = Vec::with_capacity(categories.len());
+        let mut affected: Vec<entity::Category> = vec![];
+
+        for (index, category) in categories.into_iter().enumerate() {
+            let (category, parents) =
+                update_category(&mut transaction, category, &format!("categories[{index}]"))
+                    .await?;
+
+            affected.extend(parents);
+            affected.push(category.clone());
+            updated.push(category);
+        }
+
+        debug!(count = updated.len(), "rows updated");
+
+        // a row can be touched more than once, publish the last state of each
+        let mut payload: Vec<Category> = Vec::with_capacity(affected.len());
+        for category in affected.into_iter().rev() {
+            if !payload.iter().any(|value| value.id == category.id) {
+                payload.push(Category::from(category));
+            }
+        }
+
+        let event = Event::UpdateBatch(Entity::Categories);
+
+        enqueue_event(
+            &mut transaction,
+            CategoryList {
+                categories: payload,
+            },
+            event,
+        )
+        .await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(CategoryList {
+            categories: updated.into_iter().map(Category::from).collect(),
+        }))
+    }
+
+    #[doc = " Archive many categories, and their descendants, in a single transaction"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn delete_many(
+        &self,
+        request: tonic::Request<DeleteCategoriesRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let actor = request_actor(&request);
+        let ids = request.into_inner().ids;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let (categories, parents) = archive(&mut transaction, &ids).await?;
+
+        debug!(count = categories.len(), "rows archived");
+
+        let parents = parents.into_iter().map(Category::from).collect();
+
+        let categories = enqueue_archived(&mut transaction, categories, parents).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(categories))
+    }
+
+    #[doc = " Restore an archived category and the descendants archived with it"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn restore(
+        &self,
+        request: tonic::Request<RestoreCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let actor = request_actor(&request);
+        let id = request.into_inner().id;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
         let category = sqlx::query_as!(
             entity::Category,
-            "update category set name = $2, sub_categories = $3, image_url = $4, parent_id = $5
-                where id = $1 returning *",
-            category.id,
-            category.name,
-            &category.sub_categories,
-            category.image_url,
-            category.parent_id,
+            "select * from category where id = $1 for update",
+            id
+        )
+        .fetch_optional(&mut *transaction)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| tonic::Status::not_found("category does not exist"))?;
+
+        let deleted_at = category
+            .deleted_at
+            .ok_or_else(|| tonic::Status::failed_precondition("category is not archived"))?;
+
+        // a restored category cannot sit under an archived parent
+        let parent = match category.parent_id {
+            Some(ref parent_id) => Some(
+                add_sub_category(&mut transaction, parent_id, &id)
+                    .await?
+                    .ok_or_else(|| {
+                        tonic::Status::failed_precondition(
+                            "parent category is archived, restore it first",
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        // a sibling may have taken the name while the category was archived. Slugs stay reserved,
+        // and nothing can be added under the archived descendants
+        ensure_unique_name(
+            &mut transaction,
+            category.parent_id.as_deref(),
+            &id,
+            &category.name,
+            "name",
+        )
+        .await?;
+
+        // descendants archived on their own, before this category, stay archived
+        let categories = sqlx::query_as!(
+            entity::Category,
+            r#"
+                with recursive descendants as (
+                    select id from category
+                    where id = $1
+                    union
+                    select child.id from category child
+                    inner join descendants on child.parent_id = descendants.id
+                    where child.deleted_at = $2
+                )
+                update category set deleted_at = null
+                where id in (select id from descendants)
+                returning *
+            "#,
+            id,
+            deleted_at
         )
-        .fetch_one(&self.state.db_pool)
+        .fetch_all(&mut *transaction)
         .instrument(debug_span!("pg.update"))
         .await
         .map_err(map_err)?;
 
-        let category = Category::from(category);
+        debug!(count = categories.len(), "rows restored");
+
+        let categories = CategoryList {
+            categories: categories.into_iter().map(Category::from).collect(),
+        };
+
+        let event = Event::SetBatch(Entity::Categories);
+
+        enqueue_event(&mut transaction, categories.clone(), event).await?;
+
+        if let Some(parent) = parent {
+            let event = Event::UpdateSingle(Entity::Categories);
+            enqueue_event(&mut transaction, Category::from(parent), event).await?;
+        }
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(categories))
+    }
+
+    #[doc = " Permanently delete an archived category and its descendants"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn purge(
+        &self,
+        request: tonic::Request<PurgeCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let actor = request_actor(&request);
+        let id = request.into_inner().id;
 
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let category = sqlx::query_as!(
+            entity::Category,
+            "select * from category where id = $1 for update",
+            id
+        )
+        .fetch_optional(&mut *transaction)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| tonic::Status::not_found("category does not exist"))?;
+
+        if category.deleted_at.is_none() {
+            return Err(tonic::Status::failed_precondition(
+                "only archived categories can be purged",
+            ));
+        }
+
+        // descendants are removed by the foreign key, select them so they are reported too
+        let categories = sqlx::query_as!(
+            entity::Category,
+            r#"
+                with recursive descendants as (
+                    select id from category
+                    where id = $1
+                    union
+                    select child.id from category child
+                    inner join descendants on child.parent_id = descendants.id
+                )
+                delete from category where id in (select id from descendants)
+                returning *
+            "#,
+            id
+        )
+        .fetch_all(&mut *transaction)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+
+        if let Some(ref parent_id) = category.parent_id {
+            remove_sub_category(&mut transaction, parent_id, &id).await?;
+        }
+
+        debug!(count = categories.len(), "rows deleted");
+
+        let categories = CategoryList {
+            categories: categories.into_iter().map(Category::from).collect(),
+        };
+
+        let event = Event::DeleteBatch(Entity::Categories);
+
+        enqueue_event(&mut transaction, categories.clone(), event).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(categories))
+    }
+
+    #[doc = " Replace the translations of a category"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn set_translations(
+        &self,
+        request: tonic::Request<SetCategoryTranslationsRequest>,
+    ) -> Result<tonic::Response<CategoryTranslationList>, tonic::Status> {
+        let SetCategoryTranslationsRequest { id, translations } = request.into_inner();
+
+        let translations = validate::translations(translations)?;
+
+        let mut locales = Vec::with_capacity(translations.len());
+        let mut names = Vec::with_capacity(translations.len());
+        let mut descriptions = Vec::with_capacity(translations.len());
+
+        for translation in translations {
+            locales.push(translation.locale);
+            names.push(translation.name);
+            descriptions.push(translation.description);
+        }
+
+        let mut transaction = self.state.db_pool.begin().await.map_err(map_err)?;
+
+        let category = select_for_update(&mut transaction, &id).await?;
+
+        sqlx::query!(
+            "delete from category_translation where category_id = $1 and locale <> all($2)",
+            id,
+            &locales
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+
+        sqlx::query!(
+            "insert into category_translation (category_id, locale, name, description)
+                select $1, locale, name, description
+                from unnest($2::varchar[], $3::varchar[], $4::varchar[]) as t(locale, name, description)
+                on conflict (category_id, locale)
+                do update set name = excluded.name, description = excluded.description",
+            id,
+            &locales,
+            &names,
+            &descriptions as &[Option<String>],
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.insert"))
+        .await
+        .map_err(map_err)?;
+
+        let translations = select_translations(&mut *transaction, &id).await?;
+
+        // the cache drops the localised copies of the category
         let event = Event::UpdateSingle(Entity::Categories);
-        publish_event(category.clone(), event, &self.state.jetstream_context).await?;
+        enqueue_event(&mut transaction, Category::from(category), event).await?;
 
-        Ok(tonic::Response::new(category))
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(CategoryTranslationList {
+            translations,
+        }))
     }
 
-    #[doc = " Delete a category"]
-    #[must_use]
+    #[doc = " Define an attribute on a category"]
     #[tracing::instrument(skip(self), err(Debug))]
-    async fn delete(
+    async fn create_attribute(
         &self,
-        request: tonic::Request<DeleteCategoryRequest>,
-    ) -> Result<tonic::Response<Empty>, tonic::Status> {
+        request: tonic::Request<UpsertCategoryAttributeRequest>,
+    ) -> Result<tonic::Response<CategoryAttribute>, tonic::Status> {
+        let attribute = request
+            .into_inner()
+            .attribute
+            .ok_or_else(|| missing_field("attribute"))?;
+        let attribute = attribute::validate(attribute)?;
+        let id = generate_id();
+
+        let mut transaction = self.state.db_pool.begin().await.map_err(map_err)?;
+
+        select_for_update(&mut transaction, &attribute.category_id).await?;
+        ensure_unique_attribute(
+            &mut transaction,
+            &attribute.category_id,
+            &id,
+            &attribute.name,
+        )
+        .await?;
+
+        sqlx::query!(
+            "insert into category_attribute (id, category_id, name, attribute_type, unit, required)
+                values ($1, $2, $3, $4, $5, $6)",
+            id,
+            attribute.category_id,
+            attribute.name,
+            attribute::type_name(attribute.attribute_type()),
+            attribute.unit,
+            attribute.required
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.insert"))
+        .await
+        .map_err(map_err)?;
+
+        insert_allowed_values(&mut transaction, &id, &attribute.allowed_values).await?;
+
+        let attribute = select_attribute(&mut *transaction, &id).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(attribute))
+    }
+
+    #[doc = " Update a category attribute"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn update_attribute(
+        &self,
+        request: tonic::Request<UpsertCategoryAttributeRequest>,
+    ) -> Result<tonic::Response<CategoryAttribute>, tonic::Status> {
+        let attribute = request
+            .into_inner()
+            .attribute
+            .ok_or_else(|| missing_field("attribute"))?;
+        let attribute = attribute::validate(attribute)?;
+
+        let mut transaction = self.state.db_pool.begin().await.map_err(map_err)?;
+
+        // attributes stay on the category they were defined on
+        let category_id = sqlx::query_scalar!(
+            "select category_id from category_attribute where id = $1 for update",
+            attribute.id
+        )
+        .fetch_optional(&mut *transaction)
+        .instrument(debug_span!("pg.select.attribute"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| tonic::Status::not_found("attribute does not exist"))?;
+
+        ensure_unique_attribute(
+            &mut transaction,
+            &category_id,
+            &attribute.id,
+            &attribute.name,
+        )
+        .await?;
+
+        sqlx::query!(
+            "update category_attribute set name = $2, attribute_type = $3, unit = $4, required = $5
+                where id = $1",
+            attribute.id,
+            attribute.name,
+            attribute::type_name(attribute.attribute_type()),
+            attribute.unit,
+            attribute.required
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+
+        sqlx::query!(
+            "delete from category_attribute_value where attribute_id = $1",
+            attribute.id
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+
+        insert_allowed_values(&mut transaction, &attribute.id, &attribute.allowed_values).await?;
+
+        let attribute = select_attribute(&mut *transaction, &attribute.id).await?;
+
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(attribute))
+    }
+
+    #[doc = " Delete a category attribute"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn delete_attribute(
+        &self,
+        request: tonic::Request<DeleteCategoryAttributeRequest>,
+    ) -> Result<tonic::Response<CategoryAttribute>, tonic::Status> {
         let id = request.into_inner().id;
 
-        sqlx::query!("delete from category where id = $1", id)
-            .execute(&self.state.db_pool)
+        let mut transaction = self.state.db_pool.begin().await.map_err(map_err)?;
+
+        let attribute = select_attribute(&mut *transaction, &id).await?;
+
+        sqlx::query!("delete from category_attribute where id = $1", id)
+            .execute(&mut *transaction)
             .instrument(debug_span!("pg.delete"))
             .await
             .map_err(map_err)?;
-        debug!("row deleted");
 
-        let event = Event::DeleteSingle(Entity::Categories);
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(attribute))
+    }
+
+    #[doc = " Return a category to the state it was in after a revision"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn revert_to_revision(
+        &self,
+        request: tonic::Request<RevertToRevisionRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        let actor = request_actor(&request);
+        let RevertToRevisionRequest {
+            revision_id,
+            version,
+        } = request.into_inner();
+        validate::version(version, "version")?;
+
+        let mut transaction = begin(&self.state.db_pool, actor.as_deref()).await?;
+
+        let revision = select_revision(&mut *transaction, revision_id).await?;
+
+        let Json(snapshot) = revision.after.ok_or_else(|| {
+            tonic::Status::failed_precondition("the category was purged in this revision")
+        })?;
+        let snapshot = entity::Category::try_from(snapshot)?;
+
+        if snapshot.deleted_at.is_some() {
+            return Err(tonic::Status::failed_precondition(
+                "the category was archived in this revision, restore it instead",
+            ));
+        }
+
+        sqlx::query!(
+            "select set_config('sellershut.reverted_from', $1, true)",
+            revision_id.to_string()
+        )
+        .fetch_one(&mut *transaction)
+        .instrument(debug_span!("pg.set_config"))
+        .await
+        .map_err(map_err)?;
+
+        // goes through the same checks as an update, the old parent may be gone or the old slug
+        // taken since
+        let category = Category {
+            version,
+            ..Category::from(snapshot)
+        };
+        let (category, parents) = update_category(&mut transaction, category, "category").await?;
+        debug!(id = category.id, revision_id, "category reverted");
+
+        let category = Category::from(category);
 
-        publish_event(Empty::default(), event, &self.state.jetstream_context).await?;
+        for value in
+            std::iter::once(category.clone()).chain(parents.into_iter().map(Category::from))
+        {
+            let event = Event::UpdateSingle(Entity::Categories);
+            enqueue_event(&mut transaction, value, event).await?;
+        }
 
-        Ok(tonic::Response::new(Empty::default()))
+        transaction.commit().await.map_err(map_err)?;
+
+        Ok(tonic::Response::new(category))
+    }
+}
+
+/// Attribute names are unique within a category, ignoring case
+async fn ensure_unique_attribute(
+    transaction: &mut PgConnection,
+    category_id: &str,
+    id: &str,
+    name: &str,
+) -> Result<(), tonic::Status> {
+    let exists = sqlx::query_scalar!(
+        r#"
+            select exists(
+                select 1 from category_attribute
+                where category_id = $1 and id <> $2 and lower(name) = lower($3)
+            ) as "exists!"
+        "#,
+        category_id,
+        id,
+        name
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.select.exists"))
+    .await
+    .map_err(map_err)?;
+
+    if exists {
+        Err(already_taken(
+            "attribute.name",
+            &format!("category already has an attribute named {name}"),
+        ))
+    } else {
+        Ok(())
     }
 }
+
+/// Category names are unique among live siblings, ignoring case. `field` is where the name sits
+/// in the request
+async fn ensure_unique_name(
+    transaction: &mut PgConnection,
+    parent_id: Option<&str>,
+    id: &str,
+    name: &str,
+    field: &str,
+) -> Result<(), tonic::Status> {
+    let exists = sqlx::query_scalar!(
+        r#"
+            select exists(
+                select 1 from category
+                where parent_id is not distinct from $1 and id <> $2
+                    and deleted_at is null and lower(name) = lower($3)
+            ) as "exists!"
+        "#,
+        parent_id,
+        id,
+        name
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.select.exists"))
+    .await
+    .map_err(map_err)?;
+
+    if exists {
+        Err(name_taken(field, name))
+    } else {
+        Ok(())
+    }
+}
+
+fn name_taken(field: &str, name: &str) -> tonic::Status {
+    already_taken(
+        field,
+        &format!("a sibling category is already named {name}"),
+    )
+}
+
+async fn insert_allowed_values(
+    transaction: &mut PgConnection,
+    attribute_id: &str,
+    values: &[String],
+) -> Result<(), tonic::Status> {
+    sqlx::query!(
+        "insert into category_attribute_value (attribute_id, value, position)
+            select $1, value, position from unnest($2::varchar[]) with ordinality as t(value, position)",
+        attribute_id,
+        values
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.insert"))
+    .await
+    .map_err(map_err)?;
+    Ok(())
+}
+
+async fn select_for_update(
+    transaction: &mut PgConnection,
+    id: &str,
+) -> Result<entity::Category, tonic::Status> {
+    sqlx::query_as!(
+        entity::Category,
+        "select * from category where id = $1 and deleted_at is null for update",
+        id
+    )
+    .fetch_optional(&mut *transaction)
+    .instrument(debug_span!("pg.select.*"))
+    .await
+    .map_err(map_err)?
+    .ok_or_else(|| tonic::Status::not_found("category does not exist"))
+}
+
+async fn add_sub_category(
+    transaction: &mut PgConnection,
+    parent_id: &str,
+    id: &str,
+) -> Result<Option<entity::Category>, tonic::Status> {
+    sqlx::query_as!(
+        entity::Category,
+        "update category set sub_categories = array_append(array_remove(sub_categories, $2), $2)
+            where id = $1 and deleted_at is null returning *",
+        parent_id,
+        id
+    )
+    .fetch_optional(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)
+}
+
+async fn remove_sub_category(
+    transaction: &mut PgConnection,
+    parent_id: &str,
+    id: &str,
+) -> Result<Option<entity::Category>, tonic::Status> {
+    sqlx::query_as!(
+        entity::Category,
+        "update category set sub_categories = array_remove(sub_categories, $2)
+            where id = $1 returning *",
+        parent_id,
+        id
+    )
+    .fetch_optional(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)
+}
+
+/// Applies an update to a live category, `field` is where it sits in the request. Returns the
+/// updated category and every parent that changed
+async fn update_category(
+    transaction: &mut PgConnection,
+    category: Category,
+    field: &str,
+) -> Result<(entity::Category, Vec<entity::Category>), tonic::Status> {
+    let current = select_for_update(transaction, &category.id).await?;
+
+    if category.version != current.version {
+        return Err(version_conflict(current.into()));
+    }
+
+    // sub_categories in the payload are ignored, they follow from parent_id
+    let (current, parents) = if current.parent_id != category.parent_id {
+        reparent(transaction, &current, category.parent_id.as_deref()).await?
+    } else {
+        (current, vec![])
+    };
+
+    ensure_unique_name(
+        transaction,
+        category.parent_id.as_deref(),
+        &current.id,
+        &category.name,
+        &format!("{field}.name"),
+    )
+    .await?;
+
+    // an empty slug keeps the current one, renaming a category does not break its links
+    let current = if category.slug.is_empty() {
+        current
+    } else {
+        update_slug(transaction, &current, &current, &slugify(&category.slug)).await?
+    };
+
+    let category = sqlx::query_as!(
+        entity::Category,
+        "update category set name = $2, description = $3, image_url = $4, external_id = $5
+            where id = $1 returning *",
+        current.id,
+        category.name,
+        category.description,
+        category.image_url,
+        category.external_id,
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    Ok((category, parents))
+}
+
+/// Returns `slug`, or `slug` with a numbered suffix if a sibling of the category already uses it
+async fn available_slug(
+    transaction: &mut PgConnection,
+    parent_id: Option<&str>,
+    id: &str,
+    slug: &str,
+) -> Result<String, tonic::Status> {
+    // archived siblings keep their slug so they can be restored
+    let taken = sqlx::query_scalar!(
+        "select slug from category
+            where parent_id is not distinct from $1
+                and id <> $2
+                and (slug = $3 or slug like $3 || '-%')",
+        parent_id,
+        id,
+        slug
+    )
+    .fetch_all(&mut *transaction)
+    .instrument(debug_span!("pg.select.slug"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(deduplicate(slug, &taken))
+}
+
+/// A category that takes a slug takes over any alias at the same place
+async fn remove_alias(
+    transaction: &mut PgConnection,
+    parent_id: Option<&str>,
+    slug: &str,
+) -> Result<(), tonic::Status> {
+    sqlx::query!(
+        "delete from category_slug_alias where parent_id is not distinct from $1 and slug = $2",
+        parent_id,
+        slug
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.delete"))
+    .await
+    .map_err(map_err)?;
+    Ok(())
+}
+
+/// Gives `category` a slug that is free under its current parent, starting from `slug`. Where
+/// the category was found before, `previous`, is kept as an alias so old links keep working
+async fn update_slug(
+    transaction: &mut PgConnection,
+    previous: &entity::Category,
+    category: &entity::Category,
+    slug: &str,
+) -> Result<entity::Category, tonic::Status> {
+    let parent_id = category.parent_id.as_deref();
+    let slug = available_slug(transaction, parent_id, &category.id, slug).await?;
+
+    if previous.parent_id == category.parent_id && previous.slug == slug {
+        return Ok(category.clone());
+    }
+
+    remove_alias(transaction, parent_id, &slug).await?;
+
+    sqlx::query!(
+        "insert into category_slug_alias (parent_id, slug, category_id) values ($1, $2, $3)
+            on conflict (parent_id, slug) do update set category_id = excluded.category_id",
+        previous.parent_id,
+        previous.slug,
+        category.id
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.insert"))
+    .await
+    .map_err(map_err)?;
+
+    sqlx::query_as!(
+        entity::Category,
+        "update category set slug = $2 where id = $1 returning *",
+        category.id,
+        slug
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)
+}
+
+/// Serialises hierarchy changes so concurrent moves cannot create a cycle between them
+async fn lock_hierarchy(transaction: &mut PgConnection) -> Result<(), tonic::Status> {
+    sqlx::query!("select pg_advisory_xact_lock(hashtext('category_hierarchy'))")
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.lock"))
+        .await
+        .map_err(map_err)?;
+    Ok(())
+}
+
+/// Moves the live children of a category to its parent. Returns the moved children. A child
+/// named like one of its new siblings is rejected, slugs are made unique as on a move
+async fn reparent_children(
+    transaction: &mut PgConnection,
+    id: &str,
+) -> Result<Vec<entity::Category>, tonic::Status> {
+    lock_hierarchy(transaction).await?;
+
+    let category = select_for_update(transaction, id).await?;
+    let parent_id = category.parent_id.as_deref();
+
+    let children = sqlx::query_as!(
+        entity::Category,
+        "select * from category where parent_id = $1 and deleted_at is null for update",
+        id
+    )
+    .fetch_all(&mut *transaction)
+    .instrument(debug_span!("pg.select.*"))
+    .await
+    .map_err(map_err)?;
+
+    let mut moved = Vec::with_capacity(children.len());
+    for child in children {
+        ensure_unique_name(transaction, parent_id, &child.id, &child.name, "strategy").await?;
+
+        // the slug is settled before the move, siblings cannot share one even for a moment
+        let slug = available_slug(transaction, parent_id, &child.id, &child.slug).await?;
+        let value = sqlx::query_as!(
+            entity::Category,
+            "update category set parent_id = $2, slug = $3 where id = $1 returning *",
+            child.id,
+            parent_id,
+            slug
+        )
+        .fetch_one(&mut *transaction)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+
+        moved.push(update_slug(transaction, &child, &value, &slug).await?);
+    }
+    let children = moved;
+
+    let ids: Vec<_> = children.iter().map(|value| value.id.clone()).collect();
+
+    sqlx::query!(
+        "update category set sub_categories = array(
+            select id from unnest(sub_categories) as id where id <> all($2)
+        ) where id = $1",
+        id,
+        &ids
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    if let Some(ref parent_id) = category.parent_id {
+        sqlx::query!(
+            "update category set sub_categories = sub_categories || $2 where id = $1",
+            parent_id,
+            &ids
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+    }
+
+    Ok(children)
+}
+
+/// Points `current` at a new parent, keeping `sub_categories` on the old and new parent in step.
+/// Returns the moved category and every parent that was updated
+async fn reparent(
+    transaction: &mut PgConnection,
+    current: &entity::Category,
+    parent_id: Option<&str>,
+) -> Result<(entity::Category, Vec<entity::Category>), tonic::Status> {
+    let id = current.id.as_str();
+
+    if parent_id == Some(id) {
+        return Err(tonic::Status::invalid_argument(
+            "a category cannot be its own parent",
+        ));
+    }
+
+    lock_hierarchy(transaction).await?;
+
+    if let Some(parent_id) = parent_id {
+        let hierarchy = sqlx::query!(
+            r#"
+                with recursive ancestors as (
+                    select id, parent_id from category
+                    where id = $1 and deleted_at is null
+                    union all
+                    select parent.id, parent.parent_id from category parent
+                    inner join ancestors on parent.id = ancestors.parent_id
+                )
+                select exists(select 1 from ancestors where id = $2) as "is_cycle!",
+                    exists(select 1 from ancestors where id = $1) as "parent_exists!"
+            "#,
+            parent_id,
+            id
+        )
+        .fetch_one(&mut *transaction)
+        .instrument(debug_span!("pg.select.ancestors"))
+        .await
+        .map_err(map_err)?;
+
+        if !hierarchy.parent_exists {
+            return Err(tonic::Status::not_found("parent category does not exist"));
+        }
+
+        if hierarchy.is_cycle {
+            return Err(tonic::Status::failed_precondition(
+                "a category cannot be moved under one of its descendants",
+            ));
+        }
+    }
+
+    let category = sqlx::query_as!(
+        entity::Category,
+        "update category set parent_id = $2 where id = $1 returning *",
+        id,
+        parent_id
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    // the slug may already be taken under the new parent
+    let category = update_slug(transaction, current, &category, &current.slug).await?;
+
+    let mut parents = Vec::with_capacity(2);
+
+    if let Some(ref old_parent) = current.parent_id {
+        if let Some(old_parent) = remove_sub_category(transaction, old_parent, id).await? {
+            parents.push(old_parent);
+        }
+    }
+
+    if let Some(new_parent) = parent_id {
+        if let Some(new_parent) = add_sub_category(transaction, new_parent, id).await? {
+            // the old and new parent are the same row when moving in place
+            parents.retain(|value| value.id != new_parent.id);
+            parents.push(new_parent);
+        }
+    }
+
+    Ok((category, parents))
+}
+
+/// Archives categories and their live descendants, taking them out of their parents'
+/// `sub_categories`. Returns the archived categories and every parent that was updated
+async fn archive(
+    transaction: &mut PgConnection,
+    ids: &[String],
+) -> Result<(Vec<entity::Category>, Vec<entity::Category>), tonic::Status> {
+    // now() is fixed for the transaction, restoring matches descendants on the same timestamp
+    let categories = sqlx::query_as!(
+        entity::Category,
+        r#"
+            with recursive descendants as (
+                select id from category
+                where id = any($1) and deleted_at is null
+                union
+                select child.id from category child
+                inner join descendants on child.parent_id = descendants.id
+                where child.deleted_at is null
+            )
+            update category set deleted_at = now()
+            where id in (select id from descendants)
+            returning *
+        "#,
+        ids
+    )
+    .fetch_all(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    if let Some(id) = ids
+        .iter()
+        .find(|id| !categories.iter().any(|category| &category.id == *id))
+    {
+        return Err(tonic::Status::not_found(format!(
+            "category {id} does not exist"
+        )));
+    }
+
+    let archived: Vec<_> = categories.iter().map(|value| value.id.clone()).collect();
+
+    // archived rows keep their sub_categories so a subtree can be restored as it was
+    let parents = sqlx::query_as!(
+        entity::Category,
+        r#"
+            update category set sub_categories = array(
+                select id from unnest(category.sub_categories) as id
+                where id <> all($1)
+            )
+            where deleted_at is null and sub_categories && $1
+            returning *
+        "#,
+        &archived
+    )
+    .fetch_all(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    Ok((categories, parents))
+}
+
+/// Takes archived categories out of the cache and search index, and refreshes the categories
+/// that changed around them
+async fn enqueue_archived(
+    transaction: &mut PgConnection,
+    categories: Vec<entity::Category>,
+    updated: Vec<Category>,
+) -> Result<CategoryList, tonic::Status> {
+    let categories = CategoryList {
+        categories: categories.into_iter().map(Category::from).collect(),
+    };
+
+    let event = Event::DeleteBatch(Entity::Categories);
+
+    enqueue_event(transaction, categories.clone(), event).await?;
+
+    if !updated.is_empty() {
+        let event = Event::UpdateBatch(Entity::Categories);
+        let updated = CategoryList {
+            categories: updated,
+        };
+        enqueue_event(transaction, updated, event).await?;
+    }
+
+    Ok(categories)
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_nats::{header::NATS_MESSAGE_ID, HeaderMap, HeaderValue};
+use core_services::{shutdown::Shutdown, state::events::Event};
+use prost::Message;
+use sqlx::{postgres::PgListener, types::Json, PgConnection};
+use tracing::{debug, debug_span, error, instrument, trace, warn, Instrument};
+
+use crate::state::ApiState;
+
+use super::{map_err, trace_headers};
+
+/// Postgres channel notified when events are written to the outbox
+const CHANNEL: &str = "category_outbox";
+
+/// Events published per transaction
+const BATCH_SIZE: i64 = 100;
+
+/// How often the outbox is checked when no notification arrives
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Delay before retrying after a failure, doubled on every consecutive failure
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How long sent events are kept around
+const RETENTION: &str = "1 day";
+
+/// Writes an event to the outbox as part of `transaction`. It is published once the transaction
+/// commits, and dropped with it if it rolls back
+#[instrument(skip(transaction, value, event), err(Debug))]
+pub async fn enqueue_event(
+    transaction: &mut PgConnection,
+    value: impl Message,
+    event: Event,
+) -> Result<(), tonic::Status> {
+    let payload = Message::encode_to_vec(&value);
+    let headers = Json(trace_headers());
+
+    sqlx::query!(
+        "insert into category_outbox (subject, payload, headers) values ($1, $2, $3)",
+        event.to_string(),
+        payload,
+        headers as _
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.insert"))
+    .await
+    .map_err(map_err)?;
+
+    // delivered on commit, wakes the relay up without waiting for the next poll
+    sqlx::query!("select pg_notify($1, '')", CHANNEL)
+        .fetch_one(&mut *transaction)
+        .instrument(debug_span!("pg.notify"))
+        .await
+        .map_err(map_err)?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct OutboxEvent {
+    id: i64,
+    subject: String,
+    payload: Vec<u8>,
+    headers: Json<HashMap<String, String>>,
+}
+
+/// Publishes events from the outbox to JetStream in the order they were written, and marks them
+/// as sent once JetStream acknowledges them. An event that fails is retried with a backoff, and
+/// holds back the events after it. Once `shutdown` starts, the events already written are sent
+/// and the relay stops
+pub async fn relay_outbox(state: ApiState, shutdown: Shutdown) {
+    let mut listener = None;
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        match relay_batch(&state).await {
+            // there may be more waiting
+            Ok(sent) if sent as i64 == BATCH_SIZE => continue,
+            Ok(_) => backoff = MIN_BACKOFF,
+            Err(e) => {
+                warn!(error = %e, retry_in = ?backoff, "outbox relay failed");
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.triggered() => break,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        }
+
+        if shutdown.is_triggered() {
+            break;
+        }
+
+        if listener.is_none() {
+            listener = listen(&state).await;
+        }
+
+        // a shutdown wakes the relay for a last batch
+        let wait = async {
+            match listener {
+                Some(ref mut value) => {
+                    match tokio::time::timeout(POLL_INTERVAL, value.recv()).await {
+                        Ok(Ok(_)) | Err(_) => {}
+                        Ok(Err(e)) => {
+                            // poll until the listener can be connected again
+                            warn!(error = %e, "outbox listener disconnected");
+                            listener = None;
+                        }
+                    }
+                }
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        };
+        tokio::select! {
+            _ = wait => {}
+            _ = shutdown.triggered() => {}
+        }
+    }
+
+    debug!("outbox relay stopped");
+}
+
+async fn listen(state: &ApiState) -> Option<PgListener> {
+    let listener = async {
+        let mut listener = PgListener::connect_with(&state.state.db_pool).await?;
+        listener.listen(CHANNEL).await?;
+        Ok::<_, sqlx::Error>(listener)
+    };
+
+    match listener.await {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            error!(error = %e, "could not listen for outbox events, polling instead");
+            None
+        }
+    }
+}
+
+/// Publishes the oldest unsent events. Returns how many were sent
+#[instrument(skip(state), err(Debug))]
+async fn relay_batch(state: &ApiState) -> Result<usize, tonic::Status> {
+    let mut transaction = state.state.db_pool.begin().await.map_err(map_err)?;
+
+    // a single relay at a time keeps the events in order across replicas
+    let locked = sqlx::query_scalar!(
+        r#"select pg_try_advisory_xact_lock(hashtext('category_outbox')) as "locked!""#
+    )
+    .fetch_one(&mut *transaction)
+    .instrument(debug_span!("pg.lock"))
+    .await
+    .map_err(map_err)?;
+
+    if !locked {
+        trace!("outbox is relayed elsewhere");
+        return Ok(0);
+    }
+
+    let events = sqlx::query_as!(
+        OutboxEvent,
+        r#"
+            select id, subject, payload, headers as "headers: Json<HashMap<String, String>>"
+            from category_outbox
+            where sent_at is null
+            order by id
+            limit $1
+        "#,
+        BATCH_SIZE
+    )
+    .fetch_all(&mut *transaction)
+    .instrument(debug_span!("pg.select.outbox"))
+    .await
+    .map_err(map_err)?;
+
+    let mut sent = Vec::with_capacity(events.len());
+    let mut failure = None;
+
+    for event in events {
+        match publish(state, &event).await {
+            Ok(_) => sent.push(event.id),
+            Err(e) => {
+                failure = Some((event.id, e));
+                break;
+            }
+        }
+    }
+
+    sqlx::query!(
+        "update category_outbox set sent_at = now() where id = any($1)",
+        &sent
+    )
+    .execute(&mut *transaction)
+    .instrument(debug_span!("pg.update"))
+    .await
+    .map_err(map_err)?;
+
+    if let Some((id, ref e)) = failure {
+        sqlx::query!(
+            "update category_outbox set attempts = attempts + 1, last_error = $2 where id = $1",
+            id,
+            e.to_string()
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+    } else if sent.is_empty() {
+        sqlx::query!(
+            "delete from category_outbox where sent_at < now() - $1::varchar::interval",
+            RETENTION
+        )
+        .execute(&mut *transaction)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+    }
+
+    transaction.commit().await.map_err(map_err)?;
+    debug!(count = sent.len(), "outbox events sent");
+
+    match failure {
+        Some((_, e)) => Err(e),
+        None => Ok(sent.len()),
+    }
+}
+
+/// Publishes an event and waits for JetStream to store it. The message id lets JetStream drop
+/// the event if it was published before but not marked as sent
+async fn publish(state: &ApiState, event: &OutboxEvent) -> Result<(), tonic::Status> {
+    let mut headers = HeaderMap::new();
+    for (k, v) in event.headers.iter() {
+        headers.insert(k.as_str(), HeaderValue::from(v.as_str()));
+    }
+    headers.insert(
+        NATS_MESSAGE_ID,
+        HeaderValue::from(format!("{CHANNEL}-{}", event.id).as_str()),
+    );
+
+    state
+        .state
+        .jetstream_context
+        .publish_with_headers(event.subject.clone(), headers, event.payload.clone().into())
+        .instrument(debug_span!("jetstream.publish"))
+        .await
+        .map_err(map_err)?
+        .await
+        .map_err(map_err)?;
+
+    Ok(())
+}
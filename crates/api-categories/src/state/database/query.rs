@@ -1,26 +1,45 @@
+use std::collections::HashMap;
+
 use core_services::{
     cache::{
-        key::{CacheKey, CursorParams, Index},
+        key::{CacheKey, CategoryParams, CountParams, CursorParams, Index, TreeParams},
         PoolLike, PooledConnection, PooledConnectionLike,
     },
-    state::events::{Entity, Event},
+    state::{
+        events::{Entity, Event},
+        ServiceState,
+    },
 };
 use futures_util::TryFutureExt;
 use prost::Message;
 use sellershut_core::{
     categories::{
-        query_categories_server::QueryCategories, CacheCategoriesConnectionRequest, Category,
-        Connection, GetCategoryRequest, GetSubCategoriesRequest, Node,
+        cache_categories_request::Payload, query_categories_server::QueryCategories,
+        CacheCategoriesConnectionRequest, CacheCategoriesCountRequest, CacheCategoriesRequest,
+        CacheCategoryAncestorsRequest, CacheCategoryRequest, CacheCategoryTreeRequest, Category,
+        CategoryAttribute, CategoryAttributeList, CategoryFilter, CategoryLevel, CategoryList,
+        CategoryRevision, CategoryRevisionConnection, CategoryRevisionNode, CategorySortField,
+        CategoryTranslation, CategoryTranslationList, CategoryTreeNode, CategoryTreeResponse,
+        Connection, GetCategoriesRequest, GetCategoryAttributesRequest, GetCategoryByPathRequest,
+        GetCategoryBySlugRequest, GetCategoryRequest, GetCategoryRevisionsRequest,
+        GetCategoryTreeRequest, GetSubCategoriesRequest, SortDirection,
     },
     common::pagination::{self, cursor::cursor_value::CursorType, Cursor, CursorBuilder, PageInfo},
 };
-use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use sqlx::{types::Json, PgExecutor, PgPool};
+use time::{format_description::well_known::Rfc3339, UtcOffset};
 use tracing::{debug, debug_span, info_span, instrument, trace, Instrument, Level};
 
 use crate::{
-    api::entity::{self, to_offset_datetime},
+    api::entity,
     state::{
-        database::{map_err, publish_event},
+        database::{
+            attribute::{self, AttributeRow},
+            cursor_signer,
+            listing::{count_categories, list_categories, list_categories_from, ListOptions},
+            locale, map_err, publish_event,
+            revision::{RevisionRow, Snapshot},
+        },
         ApiState,
     },
 };
@@ -28,11 +47,10 @@ use crate::{
 #[tonic::async_trait]
 impl QueryCategories for ApiState {
     #[doc = " gets all categories"]
-    #[must_use]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn categories(
         &self,
-        request: tonic::Request<pagination::Cursor>,
+        request: tonic::Request<GetCategoriesRequest>,
     ) -> Result<tonic::Response<Connection>, tonic::Status> {
         // get cache first
         trace!("getting cache state");
@@ -44,250 +62,124 @@ impl QueryCategories for ApiState {
             .await
             .map_err(map_err)?;
 
-        let pagination = request.into_inner();
-
+        let GetCategoriesRequest {
+            pagination,
+            include_archived,
+            locale,
+            sort,
+            direction,
+            filter,
+            offset,
+            include_total_count,
+        } = request.into_inner();
+        let locale = requested_locale(locale)?;
         let max = self.state.config.query_limit;
-        // get count
-        let actual_count = pagination::query_count(
-            max,
-            &pagination.index.ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "missing pagination index")
-            })?,
-        );
-        // get 1 more
-        let get_count: i64 = actual_count as i64 + 1;
-
-        // a cursor was specified
-        let (connection, cache_ok) = if let Some(ref cursor) = pagination.cursor_value {
-            // get cursor
-            let cursor_value = cursor.cursor_type.as_ref().ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "Cursor type is not set")
-            })?;
-
-            let decode_cursor = |cursor_value: &CursorType| {
-                CursorBuilder::decode(cursor_value)
-                    .map_err(|e| tonic::Status::internal(e.to_string()))
-            };
-
-            let connection = match cursor_value {
-                CursorType::After(cursor) => {
-                    // try cache first
-                    let cache_key = CacheKey::Categories(CursorParams {
-                        cursor: Some(cursor),
-                        index: core_services::cache::key::Index::First(actual_count),
-                    });
-
-                    let cache_result = read_cache(cache_key, cache).await;
-                    let is_cache_ok = cache_result.is_ok();
-
-                    let connection = if let Ok(con) = cache_result {
-                        trace!("cache ok");
-                        con
-                    } else {
-                        let cursor = decode_cursor(cursor_value)?;
-                        let id = cursor.id();
-                        trace!("converting to date {:?}", cursor.dt());
-
-                        let created_at =
-                            OffsetDateTime::parse(cursor.dt(), &Rfc3339).map_err(map_err)?;
-
-                        let fut_count = sqlx::query_scalar!(
-                            "
-                                    select count(*) from category
-                                    where 
-                                        (
-                                            created_at <> $1
-                                            or id <= $2
-                                        )
-                                        and created_at < $1
-                                ",
-                            created_at,
-                            id,
-                        )
-                        .fetch_one(&self.state.db_pool)
-                        .instrument(debug_span!("pg.select.count"))
-                        .map_err(map_err);
-
-                        let fut_categories = sqlx::query_as!(
-                            entity::Category,
-                            "
-                                    select * from category
-                                    where 
-                                        (
-                                            created_at = $1
-                                            and id > $2
-                                        )
-                                        or created_at >= $1
-                                    order by
-                                        created_at asc,
-                                        id asc
-                                    limit
-                                        $3
-                                ",
-                            created_at,
-                            id,
-                            get_count
-                        )
-                        .fetch_all(&self.state.db_pool)
-                        .instrument(debug_span!("pg.select.*"))
-                        .map_err(map_err);
-
-                        let (count_on_other_end, categories) =
-                            tokio::try_join!(fut_count, fut_categories)?;
-
-                        parse_categories(count_on_other_end, categories, &pagination, actual_count)?
-                    };
 
-                    (connection, is_cache_ok)
-                }
-                CursorType::Before(cursor) => {
-                    // try cache first
-                    let cache_key = CacheKey::Categories(CursorParams {
-                        cursor: Some(cursor),
-                        index: core_services::cache::key::Index::Last(actual_count),
-                    });
-
-                    let cache_result = read_cache(cache_key, cache).await;
-                    let is_cache_ok = cache_result.is_ok();
-
-                    let connection = if let Ok(con) = cache_result {
-                        trace!("cache ok");
-                        con
-                    } else {
-                        let cursor = decode_cursor(cursor_value)?;
-                        let id = cursor.id();
-                        let created_at = OffsetDateTime::parse(cursor.dt(), &Rfc3339)
-                            .map_err(|e| tonic::Status::internal(e.to_string()))?;
-
-                        let fut_count = sqlx::query_scalar!(
-                            "
-                                    select count(*) from category
-                                    where 
-                                        (
-                                            created_at <> $1
-                                            or id > $2
-                                        )
-                                        and created_at >= $1
-                                ",
-                            created_at,
-                            id,
-                        )
-                        .fetch_one(&self.state.db_pool)
-                        .instrument(debug_span!("pg.select.count"))
-                        .map_err(map_err);
-
-                        let fut_categories = sqlx::query_as!(
-                            entity::Category,
-                            "
-                                    select * from category
-                                    where 
-                                        (
-                                            created_at = $1
-                                            and id < $2
-                                        )
-                                        or created_at < $1
-                                    order by
-                                        created_at desc,
-                                        id desc
-                                    limit
-                                        $3
-                                ",
-                            created_at,
-                            id,
-                            get_count
-                        )
-                        .fetch_all(&self.state.db_pool)
-                        .instrument(debug_span!("pg.select.*"))
-                        .map_err(map_err);
-
-                        let (count, categories) = tokio::try_join!(fut_count, fut_categories)?;
-
-                        parse_categories(count, categories, &pagination, actual_count)?
-                    };
-                    (connection, is_cache_ok)
-                }
-            };
-            connection
-        } else {
-            // try cache first
-            let index = match pagination.index.expect("index to be available") {
-                pagination::cursor::Index::First(count) => Index::First(count),
-                pagination::cursor::Index::Last(count) => Index::Last(count),
-            };
-            let cache_key = CacheKey::Categories(CursorParams {
-                cursor: None,
-                index,
-            });
+        let options = ListOptions {
+            sort: CategorySortField::try_from(sort)
+                .map_err(|_| tonic::Status::invalid_argument("unknown sort field"))?,
+            direction: SortDirection::try_from(direction)
+                .map_err(|_| tonic::Status::invalid_argument("unknown sort direction"))?,
+            filter: filter.unwrap_or_default(),
+            include_archived,
+            ..Default::default()
+        };
 
-            let cache_result = read_cache(cache_key, cache).await;
-            let is_cache_ok = cache_result.is_ok();
+        if let Some(offset) = offset {
+            if pagination.is_some() {
+                return Err(tonic::Status::invalid_argument(
+                    "pagination and offset cannot be used together",
+                ));
+            }
 
-            let connection = if let Ok(con) = cache_result {
-                trace!("cache ok");
-                con
-            } else {
-                let categories = match index {
-                    Index::First(_) => sqlx::query_as!(
-                        entity::Category,
-                        "select * FROM category
-                            order by
-                                created_at asc
-                            limit $1",
-                        get_count
-                    )
-                    .fetch_all(&self.state.db_pool)
-                    .instrument(debug_span!("pg.select.*"))
-                    .await
-                    .map_err(map_err)?,
-                    Index::Last(_) => sqlx::query_as!(
-                        entity::Category,
-                        "select * FROM category
-                            order by
-                                created_at desc
-                            limit $1",
-                        get_count
-                    )
-                    .fetch_all(&self.state.db_pool)
-                    .instrument(debug_span!("pg.select.*"))
-                    .await
-                    .map_err(map_err)?,
-                };
+            let mut connection = list_categories_from(&self.state, &options, &offset).await?;
+            let categories = connection
+                .edges
+                .iter_mut()
+                .filter_map(|edge| edge.node.as_mut());
+            localise(&self.state.db_pool, categories, locale.as_deref()).await?;
+            if include_total_count {
+                set_total_count(&self.state, &mut connection, &options).await?;
+            }
+
+            return Ok(tonic::Response::new(connection));
+        }
 
-                parse_categories(
-                    Some(get_count - categories.len() as i64),
-                    categories,
-                    &pagination,
-                    actual_count,
-                )?
-            };
-            (connection, is_cache_ok)
+        let pagination = pagination
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination params"))?;
+        let index = pagination
+            .index
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination index"))?;
+
+        // only the default listing is cached
+        let cached = if options.is_default() {
+            let cache_key = CacheKey::Categories(cursor_params(
+                &pagination,
+                index,
+                max,
+                &options,
+                locale.as_deref(),
+            ));
+            read_cache(cache_key, cache, include_archived).await.ok()
+        } else {
+            None
         };
 
-        if !cache_ok {
-            let payload = CacheCategoriesConnectionRequest {
-                connection: Some(connection.clone()),
-                pagination: Some(pagination),
-            };
+        let mut connection = match cached {
+            Some(connection) => {
+                trace!("cache ok");
+                connection
+            }
+            None => {
+                let mut connection = list_categories(&self.state, &options, &pagination).await?;
+                let categories = connection
+                    .edges
+                    .iter_mut()
+                    .filter_map(|edge| edge.node.as_mut());
+                localise(&self.state.db_pool, categories, locale.as_deref()).await?;
+
+                if options.is_default() && !include_archived {
+                    let payload = CacheCategoriesRequest {
+                        payload: Some(Payload::Categories(CacheCategoriesConnectionRequest {
+                            connection: Some(connection.clone()),
+                            pagination: Some(pagination),
+                            locale,
+                            parent_id: options.parent_id.clone(),
+                            include_archived,
+                        })),
+                    };
+
+                    let event = Event::CacheUpdateBatch(Entity::Categories);
+
+                    publish_event(payload, event, &self.state.jetstream_context).await?;
+                }
 
-            let event = Event::UpdateBatch(Entity::Categories);
+                connection
+            }
+        };
 
-            publish_event(payload, event, &self.state.jetstream_context).await?;
+        // counts are cached on their own, never as part of a page
+        if include_total_count {
+            set_total_count(&self.state, &mut connection, &options).await?;
         }
 
         Ok(tonic::Response::new(connection))
     }
 
     #[doc = " get category by id"]
-    #[must_use]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn category_by_id(
         &self,
         request: tonic::Request<GetCategoryRequest>,
     ) -> Result<tonic::Response<Category>, tonic::Status> {
         let state = &self.state;
-        let id = request.into_inner().id;
+        let GetCategoryRequest { id, locale } = request.into_inner();
+        let locale = requested_locale(locale)?;
 
-        let cache_key = CacheKey::Category(&id);
+        let cache_key = CacheKey::Category(CategoryParams {
+            id: &id,
+            locale: locale.as_deref(),
+        });
 
         let s = info_span!("cache call");
 
@@ -322,19 +214,27 @@ impl QueryCategories for ApiState {
             }
             Err(_e) => {
                 debug!("cache miss");
-                let category =
-                    sqlx::query_as!(entity::Category, "select * from category where id = $1", id)
-                        .fetch_one(&state.db_pool)
-                        .instrument(debug_span!("pg.select.*"))
-                        .await
-                        .map_err(map_err)?;
-
-                // update cache
-                let category = Category::from(category);
-
-                let event = Event::UpdateSingle(Entity::Categories);
-
-                publish_event(category.clone(), event, &self.state.jetstream_context).await?;
+                let category = sqlx::query_as!(
+                    entity::Category,
+                    "select * from category where id = $1 and deleted_at is null",
+                    id
+                )
+                .fetch_optional(&state.db_pool)
+                .instrument(debug_span!("pg.select.*"))
+                .await
+                .map_err(map_err)?
+                .ok_or_else(|| tonic::Status::not_found("category does not exist"))?;
+
+                let mut category = Category::from(category);
+                localise(&state.db_pool, [&mut category], locale.as_deref()).await?;
+
+                // update cache only, an update event would reach subscribers and the search index
+                let payload = CacheCategoryRequest {
+                    category: Some(category.clone()),
+                    locale,
+                };
+                let event = Event::CacheUpdateSingle(Entity::Categories);
+                publish_event(payload, event, &self.state.jetstream_context).await?;
                 category
             }
         };
@@ -342,8 +242,48 @@ impl QueryCategories for ApiState {
         Ok(tonic::Response::new(category))
     }
 
+    #[doc = " get category by slug, previous slugs resolve to the category they belonged to"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_by_slug(
+        &self,
+        request: tonic::Request<GetCategoryBySlugRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        let GetCategoryBySlugRequest { parent_id, slug } = request.into_inner();
+
+        let category = select_by_slug(&self.state.db_pool, parent_id.as_deref(), &slug)
+            .await?
+            .ok_or_else(|| tonic::Status::not_found("category does not exist"))?;
+
+        Ok(tonic::Response::new(Category::from(category)))
+    }
+
+    #[doc = " get category by its slug path, previous slugs resolve to the category they belonged to"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_by_path(
+        &self,
+        request: tonic::Request<GetCategoryByPathRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        let path = request.into_inner().path;
+
+        let mut category: Option<entity::Category> = None;
+
+        for slug in path.split('/').filter(|slug| !slug.is_empty()) {
+            // follow the resolved category, an alias may point somewhere else in the tree
+            let parent_id = category.as_ref().map(|value| value.id.as_str());
+            category = Some(
+                select_by_slug(&self.state.db_pool, parent_id, slug)
+                    .await?
+                    .ok_or_else(|| tonic::Status::not_found("category does not exist"))?,
+            );
+        }
+
+        let category =
+            category.ok_or_else(|| tonic::Status::invalid_argument("path cannot be empty"))?;
+
+        Ok(tonic::Response::new(Category::from(category)))
+    }
+
     #[doc = " get subcategories"]
-    #[must_use]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn sub_categories(
         &self,
@@ -361,257 +301,788 @@ impl QueryCategories for ApiState {
 
         let request = request.into_inner();
 
-        let pagination = request.pagination.expect("missing pagination params");
         let parent_id = request.id;
-
+        let include_archived = request.include_archived;
+        let include_total_count = request.include_total_count;
+        let locale = requested_locale(request.locale)?;
         let max = self.state.config.query_limit;
-        // get count
-        let actual_count = pagination::query_count(
-            max,
-            &pagination.index.ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "missing pagination index")
-            })?,
-        );
-        // get 1 more
-        let get_count: i64 = actual_count as i64 + 1;
-
-        // a cursor was specified
-        let (connection, cache_ok) = if let Some(ref cursor) = pagination.cursor_value {
-            // get cursor
-            let cursor_value = cursor.cursor_type.as_ref().ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "Cursor type is not set")
-            })?;
-
-            let decode_cursor = |cursor_value: &CursorType| {
-                CursorBuilder::decode(cursor_value)
-                    .map_err(|e| tonic::Status::internal(e.to_string()))
-            };
-
-            let connection = match cursor_value {
-                CursorType::After(cursor) => {
-                    // try cache first
-                    let cache_key = CacheKey::Categories(CursorParams {
-                        cursor: Some(cursor),
-                        index: core_services::cache::key::Index::First(actual_count),
-                    });
-
-                    let cache_result = read_cache(cache_key, cache).await;
-                    let is_cache_ok = cache_result.is_ok();
-
-                    let connection = if let Ok(con) = cache_result {
-                        trace!("cache ok");
-                        con
-                    } else {
-                        let cursor = decode_cursor(cursor_value)?;
-                        let id = cursor.id();
-                        debug!("converting to date {:?}", cursor.dt());
-
-                        let created_at =
-                            OffsetDateTime::parse(cursor.dt(), &Rfc3339).map_err(map_err)?;
-
-                        let fut_count = sqlx::query_scalar!(
-                                "
-                                    select count(*) from category
-                                    where 
-                                        ((
-                                            created_at <> $1
-                                            or id <= $2
-                                        )
-                                        and created_at < $1) and  (($3::text is not null and parent_id = $3) or parent_id is null )
-                                ",
-                                created_at,
-                                id,
-                                parent_id
-                            )
-                            .fetch_one(&self.state.db_pool)
-                            .instrument(debug_span!("pg.select.count"))
-                            .map_err(map_err);
-
-                        let fut_categories = sqlx::query_as!(
-                                entity::Category,
-                                "
-                                    select * from category
-                                    where 
-                                        ((
-                                            created_at = $1
-                                            and id > $2
-                                        )
-                                        or created_at >= $1) and  (($4::text is not null and parent_id = $4) or parent_id is null )
-                                    order by
-                                        created_at asc,
-                                        id asc
-                                    limit
-                                        $3
-                                ",
-                                created_at,
-                                id,
-                                get_count,
-                                parent_id
-                            )
-                            .fetch_all(&self.state.db_pool)
-                            .instrument(debug_span!("pg.select.*"))
-                            .map_err(map_err);
-
-                        let (count_on_other_end, categories) =
-                            tokio::try_join!(fut_count, fut_categories)?;
-
-                        parse_categories(count_on_other_end, categories, &pagination, actual_count)?
-                    };
-                    (connection, is_cache_ok)
+
+        let options = ListOptions {
+            include_archived,
+            parent_id: parent_id.clone(),
+            filter: CategoryFilter {
+                level: match parent_id {
+                    Some(_) => CategoryLevel::All,
+                    None => CategoryLevel::Root,
                 }
-                CursorType::Before(cursor) => {
-                    // try cache first
-                    let cache_key = CacheKey::Categories(CursorParams {
-                        cursor: Some(cursor),
-                        index: core_services::cache::key::Index::Last(actual_count),
-                    });
-
-                    let cache_result = read_cache(cache_key, cache).await;
-                    let is_cache_ok = cache_result.is_ok();
-
-                    let connection = if let Ok(con) = cache_result {
-                        trace!("cache ok");
-                        con
-                    } else {
-                        let cursor = decode_cursor(cursor_value)?;
-                        let id = cursor.id();
-                        let created_at = OffsetDateTime::parse(cursor.dt(), &Rfc3339)
-                            .map_err(|e| tonic::Status::internal(e.to_string()))?;
-
-                        let fut_count = sqlx::query_scalar!(
-                                    "
-                                    select count(*) from category
-                                    where 
-                                        ((
-                                            created_at <> $1
-                                            or id > $2
-                                        )
-                                        and created_at >= $1) and (($3::text is not null and parent_id = $3) or parent_id is null )
-                                ",
-                                    created_at,
-                                    id,
-                                    parent_id
-                                )
-                                .fetch_one(&self.state.db_pool)
-                                .instrument(debug_span!("pg.select.count"))
-                                .map_err(map_err)
-                            ;
-
-                        let fut_categories = sqlx::query_as!(
-                                entity::Category,
-                                "
-                                    select * from category
-                                    where 
-                                        ((
-                                            created_at = $1
-                                            and id < $2
-                                        )
-                                        or created_at < $1) and (($4::text is not null and parent_id = $4) or parent_id is null)
-                                    order by
-                                        created_at desc,
-                                        id desc
-                                    limit
-                                        $3
-                                ",
-                                created_at,
-                                id,
-                                get_count,
-                                parent_id
-                            )
-                            .fetch_all(&self.state.db_pool)
-                            .instrument(debug_span!("pg.select.*"))
-                            .map_err(map_err);
-
-                        let (count, categories) = tokio::try_join!(fut_count, fut_categories)?;
-
-                        parse_categories(count, categories, &pagination, actual_count)?
+                .into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        if let Some(offset) = request.offset {
+            if request.pagination.is_some() {
+                return Err(tonic::Status::invalid_argument(
+                    "pagination and offset cannot be used together",
+                ));
+            }
+
+            let mut connection = list_categories_from(&self.state, &options, &offset).await?;
+            let categories = connection
+                .edges
+                .iter_mut()
+                .filter_map(|edge| edge.node.as_mut());
+            localise(&self.state.db_pool, categories, locale.as_deref()).await?;
+            if include_total_count {
+                set_total_count(&self.state, &mut connection, &options).await?;
+            }
+
+            return Ok(tonic::Response::new(connection));
+        }
+
+        let pagination = request
+            .pagination
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination params"))?;
+        let index = pagination
+            .index
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination index"))?;
+
+        let cache_key = CacheKey::CategoriesSubCategory(cursor_params(
+            &pagination,
+            index,
+            max,
+            &options,
+            locale.as_deref(),
+        ));
+
+        let mut connection = match read_cache(cache_key, cache, include_archived).await {
+            Ok(connection) => {
+                trace!("cache ok");
+                connection
+            }
+            Err(_) => {
+                let mut connection = list_categories(&self.state, &options, &pagination).await?;
+                let categories = connection
+                    .edges
+                    .iter_mut()
+                    .filter_map(|edge| edge.node.as_mut());
+                localise(&self.state.db_pool, categories, locale.as_deref()).await?;
+
+                if !include_archived {
+                    let payload = CacheCategoriesRequest {
+                        payload: Some(Payload::SubCategories(CacheCategoriesConnectionRequest {
+                            connection: Some(connection.clone()),
+                            pagination: Some(pagination),
+                            locale,
+                            parent_id: options.parent_id.clone(),
+                            include_archived,
+                        })),
                     };
-                    (connection, is_cache_ok)
+
+                    let event = Event::CacheUpdateBatch(Entity::Categories);
+
+                    publish_event(payload, event, &self.state.jetstream_context).await?;
                 }
-            };
-            connection
-        } else {
-            // try cache first
-            let index = match pagination.index.expect("index to be available") {
-                pagination::cursor::Index::First(count) => Index::First(count),
-                pagination::cursor::Index::Last(count) => Index::Last(count),
-            };
-            let cache_key = CacheKey::CategoriesSubCategory(CursorParams {
-                cursor: None,
-                index,
-            });
 
-            let cache_result = read_cache(cache_key, cache).await;
-            let is_cache_ok = cache_result.is_ok();
+                connection
+            }
+        };
+
+        // counts are cached on their own, never as part of a page
+        if include_total_count {
+            set_total_count(&self.state, &mut connection, &options).await?;
+        }
 
-            let connection = if let Ok(con) = cache_result {
-                trace!("cache ok");
-                con
-            } else {
-                let categories = match index {
-                    Index::First(_) => sqlx::query_as!(
-                        entity::Category,
-                        "select * FROM category
-                            where
-                                 ($2::text is not null and parent_id = $2) or parent_id is null
-                            order by
-                                created_at asc
-                            limit $1",
-                        get_count,
-                        parent_id
-                    )
-                    .fetch_all(&self.state.db_pool)
-                    .instrument(debug_span!("pg.select.count"))
-                    .await
-                    .map_err(map_err)?,
-                    Index::Last(_) => sqlx::query_as!(
-                        entity::Category,
-                        "select * FROM category
-                            where
-                                 ($2::text is not null and parent_id = $2) or parent_id is null
-                            order by
-                                created_at desc
-                            limit $1",
-                        get_count,
-                        parent_id
-                    )
-                    .fetch_all(&self.state.db_pool)
-                    .instrument(debug_span!("pg.select.*"))
-                    .await
+        Ok(tonic::Response::new(connection))
+    }
+
+    #[doc = " get a nested tree of categories"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_tree(
+        &self,
+        request: tonic::Request<GetCategoryTreeRequest>,
+    ) -> Result<tonic::Response<CategoryTreeResponse>, tonic::Status> {
+        let request = request.into_inner();
+
+        if request.max_depth.is_some_and(|depth| depth < 0) {
+            return Err(tonic::Status::invalid_argument(
+                "max_depth cannot be negative",
+            ));
+        }
+
+        // get cache first
+        trace!("getting cache state");
+        let cache = self
+            .state
+            .cache
+            .get()
+            .instrument(debug_span!("cache.get.pool"))
+            .await
+            .map_err(map_err)?;
+
+        let cache_key = CacheKey::CategoryTree(TreeParams {
+            id: request.id.as_deref(),
+            max_depth: request.max_depth,
+        });
+
+        if let Ok(tree) = read_cache_message::<CategoryTreeResponse>(cache_key, cache).await {
+            trace!("cache ok");
+            return Ok(tonic::Response::new(tree));
+        }
+
+        debug!("cache miss");
+        let categories = sqlx::query_as!(
+            entity::Category,
+            r#"
+                with recursive tree as (
+                    select category.*, 0 as depth from category
+                    where
+                        (($1::text is null and parent_id is null) or id = $1)
+                        and deleted_at is null
+                    union all
+                    select child.*, tree.depth + 1 from category child
+                    inner join tree on child.parent_id = tree.id
+                    where
+                        ($2::int is null or tree.depth < $2)
+                        and child.deleted_at is null
+                )
+                select
+                    id as "id!",
+                    name as "name!",
+                    slug as "slug!",
+                    description,
+                    sub_categories as "sub_categories!",
+                    image_url,
+                    parent_id,
+                    created_at as "created_at!",
+                    updated_at as "updated_at!",
+                    deleted_at,
+                    version as "version!",
+                    external_id
+                from tree
+                order by
+                    depth asc,
+                    created_at asc,
+                    id asc
+            "#,
+            request.id,
+            request.max_depth,
+        )
+        .fetch_all(&self.state.db_pool)
+        .instrument(debug_span!("pg.select.tree"))
+        .await
+        .map_err(map_err)?;
+
+        if request.id.is_some() && categories.is_empty() {
+            return Err(tonic::Status::not_found("category does not exist"));
+        }
+
+        let tree = build_tree(categories, request.id.as_deref());
+
+        let payload = CacheCategoriesRequest {
+            payload: Some(Payload::Tree(CacheCategoryTreeRequest {
+                request: Some(request),
+                tree: Some(tree.clone()),
+            })),
+        };
+
+        let event = Event::CacheUpdateBatch(Entity::Categories);
+
+        publish_event(payload, event, &self.state.jetstream_context).await?;
+
+        Ok(tonic::Response::new(tree))
+    }
+
+    #[doc = " get the path from the root category to a category"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_ancestors(
+        &self,
+        request: tonic::Request<GetCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        let GetCategoryRequest { id, locale } = request.into_inner();
+        let locale = requested_locale(locale)?;
+
+        // get cache first
+        trace!("getting cache state");
+        let cache = self
+            .state
+            .cache
+            .get()
+            .instrument(debug_span!("cache.get.pool"))
+            .await
+            .map_err(map_err)?;
+
+        let cache_key = CacheKey::CategoryAncestors(CategoryParams {
+            id: &id,
+            locale: locale.as_deref(),
+        });
+
+        if let Ok(ancestors) = read_cache_message::<CategoryList>(cache_key, cache).await {
+            trace!("cache ok");
+            return Ok(tonic::Response::new(ancestors));
+        }
+
+        debug!("cache miss");
+        let categories = sqlx::query_as!(
+            entity::Category,
+            r#"
+                with recursive ancestors as (
+                    select category.*, 0 as depth from category
+                    where id = $1 and deleted_at is null
+                    union all
+                    select parent.*, ancestors.depth + 1 from category parent
+                    inner join ancestors on parent.id = ancestors.parent_id
+                )
+                select
+                    id as "id!",
+                    name as "name!",
+                    slug as "slug!",
+                    description,
+                    sub_categories as "sub_categories!",
+                    image_url,
+                    parent_id,
+                    created_at as "created_at!",
+                    updated_at as "updated_at!",
+                    deleted_at,
+                    version as "version!",
+                    external_id
+                from ancestors
+                order by
+                    depth desc
+            "#,
+            id,
+        )
+        .fetch_all(&self.state.db_pool)
+        .instrument(debug_span!("pg.select.ancestors"))
+        .await
+        .map_err(map_err)?;
+
+        if categories.is_empty() {
+            return Err(tonic::Status::not_found("category does not exist"));
+        }
+
+        let mut ancestors = CategoryList {
+            categories: categories.into_iter().map(Category::from).collect(),
+        };
+        localise(
+            &self.state.db_pool,
+            &mut ancestors.categories,
+            locale.as_deref(),
+        )
+        .await?;
+
+        let payload = CacheCategoriesRequest {
+            payload: Some(Payload::Ancestors(CacheCategoryAncestorsRequest {
+                id,
+                ancestors: Some(ancestors.clone()),
+                locale,
+            })),
+        };
+
+        let event = Event::CacheUpdateBatch(Entity::Categories);
+
+        publish_event(payload, event, &self.state.jetstream_context).await?;
+
+        Ok(tonic::Response::new(ancestors))
+    }
+
+    #[doc = " get the translations of a category"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_translations(
+        &self,
+        request: tonic::Request<GetCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryTranslationList>, tonic::Status> {
+        let id = request.into_inner().id;
+
+        ensure_exists(&self.state.db_pool, &id).await?;
+
+        let translations = select_translations(&self.state.db_pool, &id).await?;
+
+        Ok(tonic::Response::new(CategoryTranslationList {
+            translations,
+        }))
+    }
+
+    #[doc = " get the attributes defined on a category"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_attributes(
+        &self,
+        request: tonic::Request<GetCategoryAttributesRequest>,
+    ) -> Result<tonic::Response<CategoryAttributeList>, tonic::Status> {
+        let category_id = request.into_inner().category_id;
+
+        ensure_exists(&self.state.db_pool, &category_id).await?;
+
+        let attributes = sqlx::query_as!(
+            AttributeRow,
+            r#"
+                select
+                    attribute.id,
+                    attribute.category_id,
+                    attribute.name,
+                    attribute.attribute_type,
+                    array_remove(array_agg(value.value order by value.position), null) as "allowed_values!",
+                    attribute.unit,
+                    attribute.required
+                from category_attribute attribute
+                left join category_attribute_value value on value.attribute_id = attribute.id
+                where attribute.category_id = $1
+                group by attribute.id
+                order by attribute.created_at, attribute.id
+            "#,
+            category_id
+        )
+        .fetch_all(&self.state.db_pool)
+        .instrument(debug_span!("pg.select.attribute"))
+        .await
+        .map_err(map_err)?;
+
+        Ok(tonic::Response::new(CategoryAttributeList {
+            attributes: attributes
+                .into_iter()
+                .map(CategoryAttribute::from)
+                .collect(),
+        }))
+    }
+
+    #[doc = " get the attributes of a category, including those inherited from its ancestors"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn resolved_attributes(
+        &self,
+        request: tonic::Request<GetCategoryAttributesRequest>,
+    ) -> Result<tonic::Response<CategoryAttributeList>, tonic::Status> {
+        let category_id = request.into_inner().category_id;
+
+        ensure_exists(&self.state.db_pool, &category_id).await?;
+
+        // live categories always have live ancestors
+        let attributes = sqlx::query_as!(
+            AttributeRow,
+            r#"
+                with recursive ancestors as (
+                    select id, parent_id, 0 as depth from category
+                    where id = $1
+                    union all
+                    select parent.id, parent.parent_id, ancestors.depth + 1 from category parent
+                    inner join ancestors on parent.id = ancestors.parent_id
+                )
+                select
+                    attribute.id,
+                    attribute.category_id,
+                    attribute.name,
+                    attribute.attribute_type,
+                    array_remove(array_agg(value.value order by value.position), null) as "allowed_values!",
+                    attribute.unit,
+                    attribute.required
+                from category_attribute attribute
+                inner join ancestors on ancestors.id = attribute.category_id
+                left join category_attribute_value value on value.attribute_id = attribute.id
+                group by attribute.id, ancestors.depth
+                order by ancestors.depth desc, attribute.created_at, attribute.id
+            "#,
+            category_id
+        )
+        .fetch_all(&self.state.db_pool)
+        .instrument(debug_span!("pg.select.attribute"))
+        .await
+        .map_err(map_err)?;
+
+        let attributes = attribute::resolve(
+            attributes
+                .into_iter()
+                .map(CategoryAttribute::from)
+                .collect(),
+        );
+
+        Ok(tonic::Response::new(CategoryAttributeList { attributes }))
+    }
+
+    #[doc = " get the history of a category, newest first"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_revisions(
+        &self,
+        request: tonic::Request<GetCategoryRevisionsRequest>,
+    ) -> Result<tonic::Response<CategoryRevisionConnection>, tonic::Status> {
+        let GetCategoryRevisionsRequest { id, pagination } = request.into_inner();
+        let pagination = pagination
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination params"))?;
+        let index = pagination
+            .index
+            .ok_or_else(|| tonic::Status::invalid_argument("missing pagination index"))?;
+        let count = pagination::query_count(self.state.config.query_limit, &index) as usize;
+
+        // revisions are only ever appended, their id alone orders them
+        let signer = cursor_signer(&self.state);
+        let scope = format!("revisions:{id}");
+        let cursor = pagination
+            .cursor_value
+            .as_ref()
+            .and_then(|value| value.cursor_type.as_ref())
+            .map(|cursor| {
+                let cursor = CursorBuilder::decode(cursor, &signer, scope.as_bytes())
+                    .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+                cursor
+                    .id()
+                    .parse::<i64>()
+                    .map_err(|_| tonic::Status::invalid_argument("cursor is malformed"))
+            })
+            .transpose()?;
+
+        // first pages towards older revisions, last towards newer ones
+        let towards_older = matches!(index, pagination::cursor::Index::First(_));
+
+        let revisions = sqlx::query_as!(
+            RevisionRow,
+            r#"
+                select
+                    id,
+                    category_id,
+                    operation,
+                    before as "before: Json<Snapshot>",
+                    after as "after: Json<Snapshot>",
+                    actor,
+                    reverted_from,
+                    created_at
+                from category_revision
+                where
+                    category_id = $1
+                    and ($2::bigint is null or ($3 and id < $2) or (not $3 and id > $2))
+                order by
+                    case when $3 then id end desc,
+                    case when not $3 then id end asc
+                limit $4
+            "#,
+            id,
+            cursor,
+            towards_older,
+            count as i64 + 1
+        )
+        .fetch_all(&self.state.db_pool)
+        .instrument(debug_span!("pg.select.revision"))
+        .await
+        .map_err(map_err)?;
+
+        // everything on the other side of the cursor, including the revision it points at
+        let behind_cursor = match cursor {
+            Some(cursor) => sqlx::query_scalar!(
+                r#"
+                    select exists(
+                        select 1 from category_revision
+                        where category_id = $1 and (($3 and id >= $2) or (not $3 and id <= $2))
+                    ) as "exists!"
+                "#,
+                id,
+                cursor,
+                towards_older
+            )
+            .fetch_one(&self.state.db_pool)
+            .instrument(debug_span!("pg.select.exists"))
+            .await
+            .map_err(map_err)?,
+            None => false,
+        };
+
+        let has_more = revisions.len() > count;
+        let mut edges = Vec::with_capacity(count);
+        for revision in revisions.into_iter().take(count) {
+            let cursor = CursorBuilder::new(
+                &revision.id.to_string(),
+                &revision
+                    .created_at
+                    .to_offset(UtcOffset::UTC)
+                    .format(&Rfc3339)
                     .map_err(map_err)?,
-                };
+            );
+            edges.push(CategoryRevisionNode {
+                node: Some(CategoryRevision::try_from(revision)?),
+                cursor: cursor.encode(&signer, scope.as_bytes()),
+            });
+        }
+        if !towards_older {
+            edges.reverse();
+        }
 
-                parse_categories(
-                    Some(get_count - categories.len() as i64),
-                    categories,
-                    &pagination,
-                    actual_count,
-                )?
-            };
-            (connection, is_cache_ok)
+        let (has_next_page, has_previous_page) = if towards_older {
+            (has_more, behind_cursor)
+        } else {
+            (behind_cursor, has_more)
         };
 
-        if !cache_ok {
-            let payload = CacheCategoriesConnectionRequest {
-                connection: Some(connection.clone()),
-                pagination: Some(pagination),
-            };
+        Ok(tonic::Response::new(CategoryRevisionConnection {
+            edges,
+            page_info: Some(PageInfo {
+                has_next_page,
+                has_previous_page,
+                ..Default::default()
+            }),
+        }))
+    }
+}
+
+/// Normalises the locale a query asked for. No locale, or an empty one, is the default language
+fn requested_locale(value: Option<String>) -> Result<Option<String>, tonic::Status> {
+    match value.filter(|value| !value.trim().is_empty()) {
+        Some(value) => locale::normalise(&value).map(Some).ok_or_else(|| {
+            tonic::Status::invalid_argument(format!("{value} is not a valid locale"))
+        }),
+        None => Ok(None),
+    }
+}
 
-            let event = Event::UpdateBatch(Entity::Categories);
+/// Replaces names with the closest translation to `locale`. A translation without a description
+/// keeps the default one, categories without a translation keep their default name
+async fn localise<'a>(
+    pool: &PgPool,
+    categories: impl IntoIterator<Item = &'a mut Category>,
+    locale: Option<&str>,
+) -> Result<(), tonic::Status> {
+    let Some(locale) = locale else {
+        return Ok(());
+    };
 
-            publish_event(payload, event, &self.state.jetstream_context).await?;
+    let categories: Vec<_> = categories.into_iter().collect();
+    if categories.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<_> = categories.iter().map(|value| value.id.clone()).collect();
+
+    let translations: HashMap<_, _> = sqlx::query!(
+        "select distinct on (category_id) category_id, name, description
+            from category_translation
+            where category_id = any($1) and locale = any($2)
+            order by category_id, array_position($2, locale)",
+        &ids,
+        &locale::fallbacks(locale)
+    )
+    .fetch_all(pool)
+    .instrument(debug_span!("pg.select.translation"))
+    .await
+    .map_err(map_err)?
+    .into_iter()
+    .map(|row| (row.category_id, (row.name, row.description)))
+    .collect();
+
+    for category in categories {
+        if let Some((name, description)) = translations.get(&category.id) {
+            category.name.clone_from(name);
+            if description.is_some() {
+                category.description.clone_from(description);
+            }
         }
+    }
 
-        Ok(tonic::Response::new(connection))
+    Ok(())
+}
+
+/// Fails with [tonic::Code::NotFound] unless `id` is a live category
+async fn ensure_exists(pool: &PgPool, id: &str) -> Result<(), tonic::Status> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from category where id = $1 and deleted_at is null) as "exists!""#,
+        id
+    )
+    .fetch_one(pool)
+    .instrument(debug_span!("pg.select.exists"))
+    .await
+    .map_err(map_err)?;
+
+    if exists {
+        Ok(())
+    } else {
+        Err(tonic::Status::not_found("category does not exist"))
+    }
+}
+
+/// An attribute with its allowed values
+pub(super) async fn select_attribute<'c>(
+    executor: impl PgExecutor<'c>,
+    id: &str,
+) -> Result<CategoryAttribute, tonic::Status> {
+    sqlx::query_as!(
+        AttributeRow,
+        r#"
+            select
+                attribute.id,
+                attribute.category_id,
+                attribute.name,
+                attribute.attribute_type,
+                array_remove(array_agg(value.value order by value.position), null) as "allowed_values!",
+                attribute.unit,
+                attribute.required
+            from category_attribute attribute
+            left join category_attribute_value value on value.attribute_id = attribute.id
+            where attribute.id = $1
+            group by attribute.id
+        "#,
+        id
+    )
+    .fetch_optional(executor)
+    .instrument(debug_span!("pg.select.attribute"))
+    .await
+    .map_err(map_err)?
+    .map(CategoryAttribute::from)
+    .ok_or_else(|| tonic::Status::not_found("attribute does not exist"))
+}
+
+/// Translations of a category, ordered by locale
+pub(super) async fn select_translations<'c>(
+    executor: impl PgExecutor<'c>,
+    id: &str,
+) -> Result<Vec<CategoryTranslation>, tonic::Status> {
+    sqlx::query_as!(
+        CategoryTranslation,
+        "select locale, name, description from category_translation
+            where category_id = $1
+            order by locale",
+        id
+    )
+    .fetch_all(executor)
+    .instrument(debug_span!("pg.select.translation"))
+    .await
+    .map_err(map_err)
+}
+
+/// Finds a live category by its current slug, falling back to the slugs it had before
+async fn select_by_slug(
+    pool: &PgPool,
+    parent_id: Option<&str>,
+    slug: &str,
+) -> Result<Option<entity::Category>, tonic::Status> {
+    sqlx::query_as!(
+        entity::Category,
+        r#"
+            select
+                id as "id!",
+                name as "name!",
+                slug as "slug!",
+                description,
+                sub_categories as "sub_categories!",
+                image_url,
+                parent_id,
+                created_at as "created_at!",
+                updated_at as "updated_at!",
+                deleted_at,
+                version as "version!",
+                external_id
+            from (
+                select category.*, 0 as rank from category
+                where parent_id is not distinct from $1 and slug = $2 and deleted_at is null
+                union all
+                select category.*, 1 as rank from category_slug_alias alias
+                inner join category on category.id = alias.category_id
+                where
+                    alias.parent_id is not distinct from $1
+                    and alias.slug = $2
+                    and category.deleted_at is null
+            ) matches
+            order by rank
+            limit 1
+        "#,
+        parent_id,
+        slug
+    )
+    .fetch_optional(pool)
+    .instrument(debug_span!("pg.select.slug"))
+    .await
+    .map_err(map_err)
+}
+
+/// The cache parameters of a page. Pages after a cursor are cached as read from the start, pages
+/// before one as read from the end. Pages of different parents, or with archived categories, are
+/// kept apart
+fn cursor_params<'a>(
+    pagination: &'a Cursor,
+    index: pagination::cursor::Index,
+    max: i32,
+    options: &'a ListOptions,
+    locale: Option<&'a str>,
+) -> CursorParams<'a> {
+    let count = pagination::query_count(max, &index);
+    let (cursor, index) = match pagination
+        .cursor_value
+        .as_ref()
+        .and_then(|value| value.cursor_type.as_ref())
+    {
+        Some(CursorType::After(cursor)) => (Some(cursor.as_str()), Index::First(count)),
+        Some(CursorType::Before(cursor)) => (Some(cursor.as_str()), Index::Last(count)),
+        None => match index {
+            pagination::cursor::Index::First(count) => (None, Index::First(count)),
+            pagination::cursor::Index::Last(count) => (None, Index::Last(count)),
+        },
+    };
+
+    CursorParams {
+        parent_id: options.parent_id.as_deref(),
+        include_archived: options.include_archived,
+        cursor,
+        index,
+        locale,
     }
 }
 
+/// Sets the number of categories across all pages of a listing, read through the cache
+async fn set_total_count(
+    state: &ServiceState,
+    connection: &mut Connection,
+    options: &ListOptions,
+) -> Result<(), tonic::Status> {
+    let filter = options.filter.encode_to_vec();
+    let cache_key = CacheKey::CategoriesCount(CountParams {
+        parent_id: options.parent_id.as_deref(),
+        filter: &filter,
+    });
+
+    // archived categories never reach the cache
+    let cached = if options.include_archived {
+        None
+    } else {
+        let cache = state
+            .cache
+            .get()
+            .instrument(debug_span!("cache.get.pool"))
+            .await
+            .map_err(map_err)?;
+        read_cache_message::<CacheCategoriesCountRequest>(cache_key, cache)
+            .await
+            .ok()
+    };
+
+    let count = match cached {
+        Some(cached) => cached.count,
+        None => {
+            let count = count_categories(state, options).await?;
+
+            if !options.include_archived {
+                let payload = CacheCategoriesRequest {
+                    payload: Some(Payload::Count(CacheCategoriesCountRequest {
+                        parent_id: options.parent_id.clone(),
+                        filter: Some(options.filter.clone()),
+                        count,
+                    })),
+                };
+                let event = Event::CacheUpdateBatch(Entity::Categories);
+                publish_event(payload, event, &state.jetstream_context).await?;
+            }
+
+            count
+        }
+    };
+
+    connection
+        .page_info
+        .get_or_insert_with(PageInfo::default)
+        .total_count = Some(count);
+
+    Ok(())
+}
+
 #[instrument(skip(cache), err(level = Level::TRACE))]
-async fn read_cache(
+async fn read_cache_message<T: Message + Default>(
     cache_key: CacheKey<'_>,
     mut cache: PooledConnection<'_>,
-) -> Result<Connection, tonic::Status> {
-    let cache_connection = cache
+) -> Result<T, tonic::Status> {
+    cache
         .get::<_, Vec<u8>>(&cache_key)
         .map_err(|e| tonic::Status::internal(e.to_string()))
         .and_then(|payload| async move {
@@ -619,87 +1090,125 @@ async fn read_cache(
                 let err = "cache miss, empty bytes";
                 Err(tonic::Status::internal(err))
             } else {
-                CacheCategoriesConnectionRequest::decode(payload.as_ref())
-                    .map_err(|e| tonic::Status::internal(e.to_string()))
+                T::decode(payload.as_ref()).map_err(|e| tonic::Status::internal(e.to_string()))
             }
         })
         .await
-        .map_err(map_err)?;
+}
+
+#[instrument(skip(cache), err(level = Level::TRACE))]
+async fn read_cache(
+    cache_key: CacheKey<'_>,
+    cache: PooledConnection<'_>,
+    include_archived: bool,
+) -> Result<Connection, tonic::Status> {
+    // archived categories never reach the cache, admin listings always go to the database
+    if include_archived {
+        return Err(tonic::Status::not_found(
+            "archived categories are not cached",
+        ));
+    }
+
+    let cache_connection =
+        read_cache_message::<CacheCategoriesConnectionRequest>(cache_key, cache).await?;
 
     cache_connection
         .connection
         .ok_or_else(|| tonic::Status::internal("corrupted cache"))
 }
 
-#[instrument(err)]
-fn parse_categories(
-    count_on_other_end: Option<i64>,
-    categories: Vec<entity::Category>,
-    pagination: &Cursor,
-    actual_count: i32,
-) -> Result<Connection, tonic::Status> {
-    let user_count = actual_count as usize;
+/// Nests categories under their parents. `root` is the ID of the category at the top of the
+/// tree, or [None] if the tree starts at the top-level categories
+fn build_tree(categories: Vec<entity::Category>, root: Option<&str>) -> CategoryTreeResponse {
+    let mut roots = Vec::new();
+    let mut children: HashMap<String, Vec<Category>> = HashMap::new();
 
-    let count_on_other_end = count_on_other_end
-        .ok_or_else(|| tonic::Status::new(tonic::Code::Internal, "count returned no items"))?;
-    let left_side = CursorBuilder::is_paginating_from_left(pagination);
-    let cursor_unavailable = CursorBuilder::is_cursor_unavailable(pagination);
+    for category in categories.into_iter().map(Category::from) {
+        let is_root = match root {
+            Some(id) => category.id == id,
+            None => category.parent_id.is_none(),
+        };
 
-    let len = categories.len();
+        if is_root {
+            roots.push(category);
+        } else if let Some(parent_id) = category.parent_id.clone() {
+            children.entry(parent_id).or_default().push(category);
+        }
+    }
 
-    let has_more = len > user_count;
+    fn attach(
+        category: Category,
+        children: &mut HashMap<String, Vec<Category>>,
+    ) -> CategoryTreeNode {
+        let nodes = children
+            .remove(&category.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| attach(child, children))
+            .collect();
 
-    let to_node = |category: entity::Category| -> Result<Node, tonic::Status> {
-        let category = Category::from(category);
-        to_offset_datetime(category.created_at)
-            .map_err(|_| tonic::Status::invalid_argument("timestamp is invalid"))
-            .and_then(|result| {
-                result
-                    .to_offset(UtcOffset::UTC)
-                    .format(&Rfc3339)
-                    .map(|dt| {
-                        let cursor = CursorBuilder::new(&category.id, &dt);
-                        Node {
-                            node: Some(category),
-                            cursor: cursor.encode(),
-                        }
-                    })
-                    .map_err(map_err)
-            })
-    };
+        CategoryTreeNode {
+            category: Some(category),
+            children: nodes,
+        }
+    }
 
-    let categories: Result<Vec<_>, _> = if has_more {
-        categories
+    CategoryTreeResponse {
+        roots: roots
             .into_iter()
-            .rev() // need to take from the right hand side
-            .take(user_count)
-            .rev() // restore the order
-            .map(&to_node)
-            .collect()
-    } else {
-        categories.into_iter().map(&to_node).collect()
-    };
+            .map(|category| attach(category, &mut children))
+            .collect(),
+    }
+}
 
-    let connection = Connection {
-        edges: categories?,
-        page_info: Some(PageInfo {
-            has_next_page: {
-                if cursor_unavailable && left_side {
-                    has_more
-                } else {
-                    count_on_other_end > 0
-                }
-            },
-            has_previous_page: {
-                if left_side {
-                    count_on_other_end > 0
-                } else {
-                    has_more
-                }
-            },
-            ..Default::default() // other props calculated by async-graphql
-        }),
-    };
+#[cfg(test)]
+mod tests {
+    use fake::{Fake, Faker};
+
+    use crate::api::entity::Category;
+
+    use super::build_tree;
 
-    Ok(connection)
+    fn category(id: &str, parent_id: Option<&str>) -> Category {
+        Category {
+            id: id.to_string(),
+            parent_id: parent_id.map(String::from),
+            ..Faker.fake()
+        }
+    }
+
+    #[test]
+    fn nest_categories() {
+        let categories = vec![
+            category("root", None),
+            category("other_root", None),
+            category("child", Some("root")),
+            category("grandchild", Some("child")),
+        ];
+
+        let tree = build_tree(categories, None);
+        assert_eq!(tree.roots.len(), 2);
+
+        let root = &tree.roots[0];
+        assert_eq!(root.category.as_ref().unwrap().id, "root");
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].children.len(), 1);
+        assert_eq!(
+            root.children[0].children[0].category.as_ref().unwrap().id,
+            "grandchild"
+        );
+        assert!(tree.roots[1].children.is_empty());
+    }
+
+    #[test]
+    fn nest_subtree() {
+        let categories = vec![
+            category("child", Some("root")),
+            category("grandchild", Some("child")),
+        ];
+
+        let tree = build_tree(categories, Some("child"));
+        assert_eq!(tree.roots.len(), 1);
+        assert_eq!(tree.roots[0].children.len(), 1);
+    }
 }
@@ -0,0 +1,197 @@
+use sellershut_core::{
+    categories::{Category, CategoryRevision, RevisionOperation},
+    google::protobuf::Timestamp,
+};
+use serde::Deserialize;
+use sqlx::{types::Json, PgExecutor, PgPool, Postgres, Transaction};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{debug_span, Instrument};
+
+use crate::{api::entity, auth::Identity};
+
+use super::map_err;
+
+/// Who made `request`, recorded with the revisions it creates
+pub fn request_actor<T>(request: &tonic::Request<T>) -> Option<String> {
+    request
+        .extensions()
+        .get::<Identity>()
+        .map(|identity| identity.subject.clone())
+}
+
+/// Starts a transaction whose changes to categories are recorded as made by `actor`
+pub async fn begin(
+    pool: &PgPool,
+    actor: Option<&str>,
+) -> Result<Transaction<'static, Postgres>, tonic::Status> {
+    let mut transaction = pool.begin().await.map_err(map_err)?;
+
+    if let Some(actor) = actor {
+        sqlx::query!("select set_config('sellershut.actor', $1, true)", actor)
+            .fetch_one(&mut *transaction)
+            .instrument(debug_span!("pg.set_config"))
+            .await
+            .map_err(map_err)?;
+    }
+
+    Ok(transaction)
+}
+
+/// A category as the revision trigger stores it, timestamps are RFC 3339 strings
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    id: String,
+    name: String,
+    slug: String,
+    description: Option<String>,
+    sub_categories: Vec<String>,
+    image_url: Option<String>,
+    parent_id: Option<String>,
+    created_at: String,
+    updated_at: String,
+    deleted_at: Option<String>,
+    version: i64,
+    external_id: Option<String>,
+}
+
+impl TryFrom<Snapshot> for entity::Category {
+    type Error = tonic::Status;
+
+    fn try_from(value: Snapshot) -> Result<Self, Self::Error> {
+        let parse = |value: &str| OffsetDateTime::parse(value, &Rfc3339).map_err(map_err);
+
+        Ok(Self {
+            created_at: parse(&value.created_at)?,
+            updated_at: parse(&value.updated_at)?,
+            deleted_at: value.deleted_at.as_deref().map(parse).transpose()?,
+            id: value.id,
+            name: value.name,
+            slug: value.slug,
+            description: value.description,
+            sub_categories: value.sub_categories,
+            image_url: value.image_url,
+            parent_id: value.parent_id,
+            version: value.version,
+            external_id: value.external_id,
+        })
+    }
+}
+
+/// A revision as it is stored
+#[derive(Debug)]
+pub struct RevisionRow {
+    pub id: i64,
+    pub category_id: String,
+    pub operation: String,
+    pub before: Option<Json<Snapshot>>,
+    pub after: Option<Json<Snapshot>>,
+    pub actor: Option<String>,
+    pub reverted_from: Option<i64>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<RevisionRow> for CategoryRevision {
+    type Error = tonic::Status;
+
+    fn try_from(value: RevisionRow) -> Result<Self, Self::Error> {
+        let snapshot = |value: Option<Json<Snapshot>>| {
+            value
+                .map(|Json(snapshot)| entity::Category::try_from(snapshot).map(Category::from))
+                .transpose()
+        };
+
+        Ok(Self {
+            id: value.id,
+            category_id: value.category_id,
+            operation: operation(&value.operation).into(),
+            before: snapshot(value.before)?,
+            after: snapshot(value.after)?,
+            actor: value.actor,
+            created_at: Some(Timestamp {
+                seconds: value.created_at.unix_timestamp(),
+                nanos: value.created_at.nanosecond() as i32,
+            }),
+            reverted_from: value.reverted_from,
+        })
+    }
+}
+
+/// A single revision of any category
+pub async fn select_revision<'c>(
+    executor: impl PgExecutor<'c>,
+    id: i64,
+) -> Result<RevisionRow, tonic::Status> {
+    sqlx::query_as!(
+        RevisionRow,
+        r#"
+            select
+                id,
+                category_id,
+                operation,
+                before as "before: Json<Snapshot>",
+                after as "after: Json<Snapshot>",
+                actor,
+                reverted_from,
+                created_at
+            from category_revision
+            where id = $1
+        "#,
+        id
+    )
+    .fetch_optional(executor)
+    .instrument(debug_span!("pg.select.revision"))
+    .await
+    .map_err(map_err)?
+    .ok_or_else(|| tonic::Status::not_found("revision does not exist"))
+}
+
+/// The operation a revision is stored under
+fn operation(value: &str) -> RevisionOperation {
+    match value {
+        "create" => RevisionOperation::Create,
+        "move" => RevisionOperation::Move,
+        "delete" => RevisionOperation::Delete,
+        "restore" => RevisionOperation::Restore,
+        "purge" => RevisionOperation::Purge,
+        "revert" => RevisionOperation::Revert,
+        _ => RevisionOperation::Update,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::entity;
+
+    use super::Snapshot;
+
+    #[test]
+    fn parse_snapshot() {
+        // as written by to_jsonb in the revision trigger
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "id": "9ckyrhcx6jun6n_7a8adq",
+                "name": "Shoes",
+                "slug": "shoes",
+                "description": null,
+                "sub_categories": [],
+                "image_url": null,
+                "parent_id": null,
+                "created_at": "2024-10-18T17:00:00.123456+00:00",
+                "updated_at": "2024-10-18T19:30:00+02:00",
+                "deleted_at": null,
+                "version": 3
+            }"#,
+        )
+        .unwrap();
+
+        let category = entity::Category::try_from(snapshot).unwrap();
+        assert_eq!(category.name, "Shoes");
+        assert_eq!(category.version, 3);
+        assert_eq!(category.created_at.nanosecond(), 123_456_000);
+        assert_eq!(
+            category.updated_at.unix_timestamp(),
+            category.created_at.unix_timestamp() + 30 * 60
+        );
+        assert_eq!(category.deleted_at, None);
+    }
+}
@@ -0,0 +1,54 @@
+/// Turns a name into a URL friendly slug: lowercase ASCII letters and digits separated by single
+/// dashes
+pub fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+
+    for c in value.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() {
+        String::from("category")
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Returns `slug`, or `slug` with the first numbered suffix that is not already `taken`
+pub fn deduplicate(slug: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|value| value == slug) {
+        return slug.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{slug}-{n}"))
+        .find(|candidate| !taken.contains(candidate))
+        .expect("an unused suffix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deduplicate, slugify};
+
+    #[test]
+    fn slugify_name() {
+        assert_eq!(slugify("Phones & Tablets"), "phones-tablets");
+        assert_eq!(slugify("  --Home & Garden--  "), "home-garden");
+        assert_eq!(slugify("TV's 4K"), "tv-s-4k");
+        assert_eq!(slugify("!!!"), "category");
+    }
+
+    #[test]
+    fn deduplicate_slug() {
+        let taken = vec![String::from("phones"), String::from("phones-2")];
+
+        assert_eq!(deduplicate("tablets", &taken), "tablets");
+        assert_eq!(deduplicate("phones", &taken), "phones-3");
+    }
+}
@@ -0,0 +1,363 @@
+use sellershut_core::{
+    categories::{Category, CategoryTranslation},
+    common::id::is_valid_id,
+};
+use tonic_types::FieldViolation;
+use url::Url;
+
+use super::{invalid_fields, locale};
+
+/// Longest name a category can have, in characters
+const NAME_MAX_LENGTH: usize = 100;
+
+/// Longest slug a category can have, in characters
+const SLUG_MAX_LENGTH: usize = 100;
+
+/// Longest image URL a category can have, in characters
+const URL_MAX_LENGTH: usize = 2048;
+
+/// Updates carry the version that was read, so changes made since are not overwritten
+const VERSION_REQUIRED: &str =
+    "version is required, pass the version of the category that was read";
+
+/// Checks a category before it is stored, `field` is where it sits in the request. Returns the
+/// category with its name trimmed. `id` is only checked on updates, new categories get one
+/// generated
+pub fn category(
+    category: Category,
+    field: &str,
+    is_update: bool,
+) -> Result<Category, tonic::Status> {
+    let mut violations = vec![];
+    let category = check_category(category, field, is_update, &mut violations);
+
+    match violations.is_empty() {
+        true => Ok(category),
+        false => Err(invalid_fields(violations)),
+    }
+}
+
+/// Checks the version an update was made against, `field` is where it sits in the request
+pub fn version(version: i64, field: &str) -> Result<(), tonic::Status> {
+    match version > 0 {
+        true => Ok(()),
+        false => Err(invalid_fields(vec![FieldViolation::new(
+            field,
+            VERSION_REQUIRED,
+        )])),
+    }
+}
+
+/// Checks every category of a batch, reporting the violations of all of them at once
+pub fn categories(
+    categories: Vec<Category>,
+    is_update: bool,
+) -> Result<Vec<Category>, tonic::Status> {
+    let mut violations = vec![];
+    let categories: Vec<_> = categories
+        .into_iter()
+        .enumerate()
+        .map(|(index, category)| {
+            check_category(
+                category,
+                &format!("categories[{index}]"),
+                is_update,
+                &mut violations,
+            )
+        })
+        .collect();
+
+    match violations.is_empty() {
+        true => Ok(categories),
+        false => Err(invalid_fields(violations)),
+    }
+}
+
+fn check_category(
+    category: Category,
+    field: &str,
+    is_update: bool,
+    violations: &mut Vec<FieldViolation>,
+) -> Category {
+    let mut violate = |name: &str, description: String| {
+        violations.push(FieldViolation::new(format!("{field}.{name}"), description))
+    };
+
+    if is_update && !is_valid_id(&category.id) {
+        violate("id", format!("{} is not a valid id", category.id));
+    }
+
+    if is_update && category.version <= 0 {
+        violate("version", String::from(VERSION_REQUIRED));
+    }
+
+    let name = category.name.trim().to_string();
+    if let Err(description) = check_name(&name) {
+        violate("name", description);
+    }
+
+    if category.slug.chars().count() > SLUG_MAX_LENGTH {
+        violate(
+            "slug",
+            format!("slug cannot be longer than {SLUG_MAX_LENGTH} characters"),
+        );
+    }
+
+    if let Some(ref image_url) = category.image_url {
+        if let Err(description) = check_url(image_url) {
+            violate("image_url", description);
+        }
+    }
+
+    if let Some(ref parent_id) = category.parent_id {
+        if !is_valid_id(parent_id) {
+            violate("parent_id", format!("{parent_id} is not a valid id"));
+        }
+    }
+
+    for (index, id) in category.sub_categories.iter().enumerate() {
+        if !is_valid_id(id) {
+            violate(
+                &format!("sub_categories[{index}]"),
+                format!("{id} is not a valid id"),
+            );
+        }
+    }
+
+    Category { name, ..category }
+}
+
+/// Checks the translations of a category, reporting the violations of all of them at once.
+/// Returns them with their locale normalised and their name trimmed
+pub fn translations(
+    translations: Vec<CategoryTranslation>,
+) -> Result<Vec<CategoryTranslation>, tonic::Status> {
+    let mut violations = vec![];
+    let mut locales: Vec<String> = Vec::with_capacity(translations.len());
+
+    let translations: Vec<_> = translations
+        .into_iter()
+        .enumerate()
+        .map(|(index, translation)| {
+            let mut violate = |name: &str, description: String| {
+                violations.push(FieldViolation::new(
+                    format!("translations[{index}].{name}"),
+                    description,
+                ))
+            };
+
+            let locale = match locale::normalise(&translation.locale) {
+                Some(locale) if locales.contains(&locale) => {
+                    violate(
+                        "locale",
+                        format!("locale {locale} is translated more than once"),
+                    );
+                    locale
+                }
+                Some(locale) => {
+                    locales.push(locale.clone());
+                    locale
+                }
+                None => {
+                    violate(
+                        "locale",
+                        format!("{} is not a valid locale", translation.locale),
+                    );
+                    translation.locale
+                }
+            };
+
+            let name = translation.name.trim().to_string();
+            if let Err(description) = check_name(&name) {
+                violate("name", description);
+            }
+
+            CategoryTranslation {
+                locale,
+                name,
+                ..translation
+            }
+        })
+        .collect();
+
+    match violations.is_empty() {
+        true => Ok(translations),
+        false => Err(invalid_fields(violations)),
+    }
+}
+
+/// Names are trimmed before they are checked
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err(String::from("name cannot be empty"))
+    } else if name.chars().count() > NAME_MAX_LENGTH {
+        Err(format!(
+            "name cannot be longer than {NAME_MAX_LENGTH} characters"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Image URLs are absolute and served over http or https
+fn check_url(value: &str) -> Result<(), String> {
+    if value.chars().count() > URL_MAX_LENGTH {
+        return Err(format!(
+            "image_url cannot be longer than {URL_MAX_LENGTH} characters"
+        ));
+    }
+
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        Ok(_) => Err(format!("{value} is not an http or https url")),
+        Err(e) => Err(format!("{value} is not a valid url: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sellershut_core::common::id::generate_id;
+    use tonic_types::StatusExt;
+
+    use super::*;
+
+    #[test]
+    fn trim_names() {
+        let value = Category {
+            name: String::from("  Shoes "),
+            ..Default::default()
+        };
+        let value = category(value, "category", false).unwrap();
+        assert_eq!(value.name, "Shoes");
+    }
+
+    #[test]
+    fn report_every_field() {
+        let value = Category {
+            id: String::from("not-an-id"),
+            name: String::from("   "),
+            image_url: Some(String::from("ftp://example.com/shoes.png")),
+            parent_id: Some(generate_id()),
+            sub_categories: vec![generate_id(), String::from("shoes")],
+            ..Default::default()
+        };
+        let status = category(value, "category", true).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let fields: Vec<_> = status
+            .get_details_bad_request()
+            .unwrap()
+            .field_violations
+            .into_iter()
+            .map(|violation| violation.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "category.id",
+                "category.version",
+                "category.name",
+                "category.image_url",
+                "category.sub_categories[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn require_versions() {
+        let value = Category {
+            id: generate_id(),
+            name: String::from("Shoes"),
+            ..Default::default()
+        };
+        let status = category(value.clone(), "category", true).unwrap_err();
+        let violations = status.get_details_bad_request().unwrap().field_violations;
+        assert_eq!(violations[0].field, "category.version");
+
+        let value = Category {
+            version: 3,
+            ..value
+        };
+        assert!(category(value, "category", true).is_ok());
+
+        assert!(version(0, "version").is_err());
+        assert!(version(1, "version").is_ok());
+    }
+
+    #[test]
+    fn bound_lengths() {
+        let value = Category {
+            name: "a".repeat(NAME_MAX_LENGTH + 1),
+            ..Default::default()
+        };
+        assert!(category(value, "category", false).is_err());
+
+        let value = Category {
+            name: "a".repeat(NAME_MAX_LENGTH),
+            image_url: Some(String::from("https://example.com/shoes.png")),
+            ..Default::default()
+        };
+        assert!(category(value, "category", false).is_ok());
+    }
+
+    #[test]
+    fn check_translations() {
+        let translation = |locale: &str, name: String| CategoryTranslation {
+            locale: locale.to_string(),
+            name,
+            description: None,
+        };
+
+        let values = translations(vec![
+            translation("pt_BR", String::from(" Sapatos ")),
+            translation("fr", String::from("Chaussures")),
+        ])
+        .unwrap();
+        assert_eq!(values[0].locale, "pt-br");
+        assert_eq!(values[0].name, "Sapatos");
+
+        let status = translations(vec![
+            translation("fr", "a".repeat(NAME_MAX_LENGTH + 1)),
+            translation("FR", String::from("Chaussures")),
+            translation("not a locale", String::from("  ")),
+        ])
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let fields: Vec<_> = status
+            .get_details_bad_request()
+            .unwrap()
+            .field_violations
+            .into_iter()
+            .map(|violation| violation.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "translations[0].name",
+                "translations[1].locale",
+                "translations[2].locale",
+                "translations[2].name",
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_batch_fields() {
+        let values = vec![
+            Category {
+                name: String::from("Shoes"),
+                ..Default::default()
+            },
+            Category {
+                image_url: Some(String::from("shoes.png")),
+                name: String::from("Boots"),
+                ..Default::default()
+            },
+        ];
+        let status = categories(values, false).unwrap_err();
+        let violations = status.get_details_bad_request().unwrap().field_violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "categories[1].image_url");
+    }
+}
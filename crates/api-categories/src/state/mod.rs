@@ -1,5 +1,13 @@
+mod changes;
 mod database;
 
+pub use changes::{relay_changes, CategoryChange, ChangeKind, Changes};
+pub(crate) use database::cursor_signer;
+pub use database::{
+    relay_outbox, CategoryLoader, ALREADY_EXISTS, ERROR_DOMAIN, INVALID_REFERENCE, INVALID_VALUE,
+    NOT_FOUND, UNAVAILABLE, VERSION_CONFLICT,
+};
+
 use std::str::FromStr;
 
 use async_nats::jetstream::stream;
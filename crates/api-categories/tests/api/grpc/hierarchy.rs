@@ -0,0 +1,150 @@
+use fake::{locales::EN, Fake};
+use sellershut_core::{
+    categories::{
+        Category, CategoryEvent, DeleteCategoryRequest, MoveCategoryRequest, UpsertCategoryRequest,
+    },
+    common::id::generate_id,
+};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+use tonic::IntoRequest;
+
+use crate::utils::{mutate_client, TestApp};
+
+async fn assert_sub_categories_consistent(pg_pool: &PgPool) {
+    let rows = sqlx::query!(
+        r#"
+            select c.id, c.sub_categories,
+                coalesce(array(
+                    select child.id from category child
+                    where child.parent_id = c.id and child.deleted_at is null
+                ), '{}') as "children!"
+            from category c
+            where c.deleted_at is null
+        "#
+    )
+    .fetch_all(pg_pool)
+    .await
+    .unwrap();
+
+    for row in rows {
+        let mut sub_categories = row.sub_categories;
+        let mut children = row.children;
+        sub_categories.sort();
+        children.sort();
+        assert_eq!(sub_categories, children, "sub_categories of {}", row.id);
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_sub_categories_invariant(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool.clone(), tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client = mutate_client(address).await;
+
+    let mut categories: Vec<Category> = vec![];
+
+    // names are unique among siblings, numbering them keeps random names apart
+    let mut name = {
+        let mut count = 0;
+        move || {
+            count += 1;
+            format!(
+                "{} {count}",
+                fake::faker::name::raw::Name(EN).fake::<String>()
+            )
+        }
+    };
+
+    for _ in 0..50 {
+        let parent_id = match categories.len() {
+            0 => None,
+            len => {
+                let index: usize = (0..len + 1).fake();
+                categories.get(index).map(|value| value.id.clone())
+            }
+        };
+
+        let operation: u8 = if categories.is_empty() {
+            0
+        } else {
+            (0..4).fake()
+        };
+
+        match operation {
+            0 => {
+                let category = Category {
+                    name: name(),
+                    // ignored by the api, sub categories follow from parent_id
+                    sub_categories: vec![generate_id()],
+                    parent_id,
+                    ..Default::default()
+                };
+                let request = UpsertCategoryRequest {
+                    category: Some(category),
+                    event: CategoryEvent::Create.into(),
+                };
+                let category = client.create(request.into_request()).await.unwrap();
+                categories.push(category.into_inner());
+            }
+            1 | 2 => {
+                let index: usize = (0..categories.len()).fake();
+                let id = categories[index].id.clone();
+                let result = if operation == 1 {
+                    // moves and renames of other categories leave this one as it is
+                    let version =
+                        sqlx::query_scalar!("select version from category where id = $1", id)
+                            .fetch_one(&pg_pool)
+                            .await?;
+                    let category = Category {
+                        id,
+                        name: name(),
+                        parent_id,
+                        version,
+                        ..Default::default()
+                    };
+                    let request = UpsertCategoryRequest {
+                        category: Some(category),
+                        event: CategoryEvent::Update.into(),
+                    };
+                    client.update(request.into_request()).await
+                } else {
+                    let request = MoveCategoryRequest { id, parent_id };
+                    client.r#move(request.into_request()).await
+                };
+
+                match result {
+                    Ok(category) => categories[index] = category.into_inner(),
+                    Err(status) => assert!(matches!(
+                        status.code(),
+                        tonic::Code::FailedPrecondition | tonic::Code::InvalidArgument
+                    )),
+                }
+            }
+            _ => {
+                let index: usize = (0..categories.len()).fake();
+                let request = DeleteCategoryRequest {
+                    id: categories[index].id.clone(),
+                    event: CategoryEvent::Delete.into(),
+                    ..Default::default()
+                };
+                client.delete(request.into_request()).await.unwrap();
+
+                // children are archived with their parent
+                let remaining =
+                    sqlx::query_scalar!("select id from category where deleted_at is null")
+                        .fetch_all(&pg_pool)
+                        .await?;
+                categories.retain(|value| remaining.contains(&value.id));
+            }
+        }
+
+        assert_sub_categories_consistent(&pg_pool).await;
+    }
+
+    Ok(())
+}
@@ -1,17 +1,34 @@
+mod hierarchy;
+
+use api_categories::{auth::API_KEY_HEADER, state::VERSION_CONFLICT};
 use fake::{locales::EN, Fake};
 use sellershut_core::{
     categories::{
         mutate_categories_client::MutateCategoriesClient,
-        query_categories_client::QueryCategoriesClient, Category, CategoryEvent,
-        DeleteCategoryRequest, GetCategoryRequest, UpsertCategoryRequest,
+        query_categories_client::QueryCategoriesClient, AttributeType, Category, CategoryAttribute,
+        CategoryEvent, CategoryFilter, CategoryLevel, CategorySortField, CategoryTranslation,
+        DeleteCategoriesRequest, DeleteCategoryAttributeRequest, DeleteCategoryRequest,
+        DeleteStrategy, GetCategoriesRequest, GetCategoryAttributesRequest,
+        GetCategoryByPathRequest, GetCategoryBySlugRequest, GetCategoryRequest,
+        GetCategoryRevisionsRequest, GetCategoryTreeRequest, GetSubCategoriesRequest,
+        MoveCategoryRequest, PurgeCategoryRequest, RestoreCategoryRequest, RevertToRevisionRequest,
+        RevisionOperation, SetCategoryTranslationsRequest, SortDirection, UpsertCategoriesRequest,
+        UpsertCategoryAttributeRequest, UpsertCategoryRequest,
+    },
+    common::{
+        id::generate_id,
+        pagination::{
+            cursor::{cursor_value::CursorType, CursorValue, Index},
+            Cursor, CursorBuilder, CursorSigner, Offset,
+        },
     },
-    common::pagination::{cursor::Index, Cursor},
 };
 use sqlx::PgPool;
 use tokio::sync::oneshot;
 use tonic::IntoRequest;
+use tonic_types::StatusExt;
 
-use crate::utils::TestApp;
+use crate::utils::{mutate_client, TestApp};
 
 #[sqlx::test(migrations = "./migrations")]
 async fn check_grpc_categories(pg_pool: PgPool) -> sqlx::Result<()> {
@@ -23,12 +40,17 @@ async fn check_grpc_categories(pg_pool: PgPool) -> sqlx::Result<()> {
 
     let mut client = QueryCategoriesClient::connect(address).await.unwrap();
 
-    let cursor = Cursor {
-        cursor_value: None,
-        index: Some(Index::First(10)),
+    let request = GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(10)),
+        }),
+        include_archived: false,
+        locale: None,
+        ..Default::default()
     };
 
-    let response = client.categories(cursor.into_request()).await.unwrap();
+    let response = client.categories(request.into_request()).await.unwrap();
 
     let connection = response.into_inner();
     assert!(connection.page_info.is_some());
@@ -44,9 +66,7 @@ async fn check_grpc_crud(pg_pool: PgPool) -> sqlx::Result<()> {
     let port = rx.await.unwrap();
     let address = format!("http://127.0.0.1:{port}");
 
-    let mut client_mut = MutateCategoriesClient::connect(address.clone())
-        .await
-        .unwrap();
+    let mut client_mut = mutate_client(address.clone()).await;
     let mut client = QueryCategoriesClient::connect(address).await.unwrap();
 
     let name: String = fake::faker::name::raw::Name(EN).fake();
@@ -68,6 +88,7 @@ async fn check_grpc_crud(pg_pool: PgPool) -> sqlx::Result<()> {
 
     let req_by_id = GetCategoryRequest {
         id: create_result.id.clone(),
+        locale: None,
     };
 
     let read_result = client
@@ -105,6 +126,7 @@ async fn check_grpc_crud(pg_pool: PgPool) -> sqlx::Result<()> {
     let delete_req = DeleteCategoryRequest {
         id: update_result.id,
         event: CategoryEvent::Delete.into(),
+        ..Default::default()
     }
     .into_request();
     client_mut.delete(delete_req).await.unwrap().into_inner();
@@ -117,3 +139,1838 @@ async fn check_grpc_crud(pg_pool: PgPool) -> sqlx::Result<()> {
 
     Ok(())
 }
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_category_tree(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut parent_id = None;
+    let mut ids = vec![];
+    for _ in 0..3 {
+        let name: String = fake::faker::name::raw::Name(EN).fake();
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name,
+                parent_id: parent_id.clone(),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        }
+        .into_request();
+
+        let category = client_mut.create(request).await.unwrap().into_inner();
+        parent_id = Some(category.id.clone());
+        ids.push(category.id);
+    }
+
+    let request = GetCategoryTreeRequest {
+        id: Some(ids[0].clone()),
+        max_depth: None,
+    };
+    let tree = client
+        .category_tree(request.into_request())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(tree.roots.len(), 1);
+    let child = &tree.roots[0].children[0];
+    assert_eq!(child.category.as_ref().unwrap().id, ids[1]);
+    assert_eq!(child.children[0].category.as_ref().unwrap().id, ids[2]);
+
+    let request = GetCategoryTreeRequest {
+        id: Some(ids[0].clone()),
+        max_depth: Some(1),
+    };
+    let tree = client
+        .category_tree(request.into_request())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(tree.roots[0].children[0].children.is_empty());
+
+    let request = GetCategoryRequest {
+        id: ids[2].clone(),
+        locale: None,
+    };
+    let ancestors = client
+        .category_ancestors(request.into_request())
+        .await
+        .unwrap()
+        .into_inner();
+
+    let ancestors: Vec<_> = ancestors
+        .categories
+        .into_iter()
+        .map(|category| category.id)
+        .collect();
+    assert_eq!(ancestors, ids);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_move(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut categories: Vec<Category> = vec![];
+    for parent_id in [None, Some(0), None] {
+        let name: String = fake::faker::name::raw::Name(EN).fake();
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name,
+                parent_id: parent_id.map(|index: usize| categories[index].id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        }
+        .into_request();
+
+        categories.push(client_mut.create(request).await.unwrap().into_inner());
+    }
+    let (root, child, other_root) = (&categories[0], &categories[1], &categories[2]);
+
+    // a category cannot be moved under its own descendant
+    let request = MoveCategoryRequest {
+        id: root.id.clone(),
+        parent_id: Some(child.id.clone()),
+    };
+    let status = client_mut.r#move(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let request = MoveCategoryRequest {
+        id: child.id.clone(),
+        parent_id: Some("does_not_exist_123456".to_string()),
+    };
+    let status = client_mut.r#move(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    let request = MoveCategoryRequest {
+        id: child.id.clone(),
+        parent_id: Some(other_root.id.clone()),
+    };
+    let moved = client_mut.r#move(request).await.unwrap().into_inner();
+    assert_eq!(moved.parent_id.as_ref(), Some(&other_root.id));
+
+    let request = GetCategoryRequest {
+        id: other_root.id.clone(),
+        locale: None,
+    };
+    let new_parent = client
+        .category_by_id(request.into_request())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(new_parent.sub_categories, vec![child.id.clone()]);
+
+    let request = GetCategoryRequest {
+        id: root.id.clone(),
+        locale: None,
+    };
+    let old_parent = client
+        .category_by_id(request.into_request())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!old_parent.sub_categories.contains(&child.id));
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_batch(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let category = |parent_id: Option<String>| Category {
+        name: fake::faker::name::raw::Name(EN).fake(),
+        parent_id,
+        ..Default::default()
+    };
+
+    let request = UpsertCategoriesRequest {
+        categories: vec![category(None), category(None)],
+        event: CategoryEvent::Create.into(),
+    };
+    let roots = client_mut
+        .create_many(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .categories;
+    assert_eq!(roots.len(), 2);
+
+    let request = UpsertCategoriesRequest {
+        categories: vec![
+            category(Some(roots[0].id.clone())),
+            category(Some(roots[0].id.clone())),
+            category(Some(generate_id())),
+        ],
+        event: CategoryEvent::Create.into(),
+    };
+    let status = client_mut.create_many(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    // nothing from the failed batch was written
+    let request = GetCategoryRequest {
+        id: roots[0].id.clone(),
+        locale: None,
+    };
+    let parent = client.category_by_id(request).await.unwrap().into_inner();
+    assert!(parent.sub_categories.is_empty());
+
+    let request = UpsertCategoriesRequest {
+        categories: vec![
+            category(Some(roots[0].id.clone())),
+            category(Some(roots[0].id.clone())),
+        ],
+        event: CategoryEvent::Create.into(),
+    };
+    let children = client_mut
+        .create_many(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .categories;
+
+    let request = GetCategoryRequest {
+        id: roots[0].id.clone(),
+        locale: None,
+    };
+    let parent = client.category_by_id(request).await.unwrap().into_inner();
+    assert_eq!(
+        parent.sub_categories,
+        children
+            .iter()
+            .map(|value| value.id.clone())
+            .collect::<Vec<_>>()
+    );
+
+    let mut updates = children.clone();
+    for value in updates.iter_mut() {
+        value.name = fake::faker::name::raw::Name(EN).fake();
+        value.parent_id = Some(roots[1].id.clone());
+    }
+    let request = UpsertCategoriesRequest {
+        categories: updates.clone(),
+        event: CategoryEvent::Update.into(),
+    };
+    let updated = client_mut
+        .update_many(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .categories;
+    for (update, result) in updates.iter().zip(updated.iter()) {
+        assert_eq!(update.name, result.name);
+        assert_eq!(result.parent_id.as_ref(), Some(&roots[1].id));
+    }
+
+    let request = DeleteCategoriesRequest {
+        ids: vec![roots[1].id.clone()],
+        event: CategoryEvent::Delete.into(),
+    };
+    let deleted = client_mut
+        .delete_many(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .categories;
+    // descendants are deleted with their parent
+    assert_eq!(deleted.len(), 3);
+
+    for value in deleted {
+        let request = GetCategoryRequest {
+            id: value.id,
+            locale: None,
+        };
+        let status = client.category_by_id(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_archive(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut categories: Vec<Category> = vec![];
+    for parent_id in [None, Some(0), Some(1)] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: fake::faker::name::raw::Name(EN).fake(),
+                parent_id: parent_id.map(|index: usize| categories[index].id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        categories.push(client_mut.create(request).await.unwrap().into_inner());
+    }
+    let (root, child, grandchild) = (&categories[0], &categories[1], &categories[2]);
+
+    let sub_categories = |include_archived| GetSubCategoriesRequest {
+        id: Some(root.id.clone()),
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(10)),
+        }),
+        include_archived,
+        locale: None,
+        ..Default::default()
+    };
+
+    // archiving a category archives its descendants
+    let request = DeleteCategoryRequest {
+        id: child.id.clone(),
+        event: CategoryEvent::Delete.into(),
+        ..Default::default()
+    };
+    client_mut.delete(request).await.unwrap();
+
+    for id in [&child.id, &grandchild.id] {
+        let request = GetCategoryRequest {
+            id: id.clone(),
+            locale: None,
+        };
+        let status = client.category_by_id(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    let request = GetCategoryRequest {
+        id: root.id.clone(),
+        locale: None,
+    };
+    let parent = client.category_by_id(request).await.unwrap().into_inner();
+    assert!(parent.sub_categories.is_empty());
+
+    let connection = client
+        .sub_categories(sub_categories(false))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(connection
+        .edges
+        .iter()
+        .all(|edge| edge.node.as_ref().unwrap().id != child.id));
+
+    let connection = client
+        .sub_categories(sub_categories(true))
+        .await
+        .unwrap()
+        .into_inner();
+    let archived = connection
+        .edges
+        .iter()
+        .find_map(|edge| edge.node.as_ref().filter(|node| node.id == child.id))
+        .unwrap();
+    assert!(archived.deleted_at.is_some());
+
+    // live categories cannot be purged
+    let request = PurgeCategoryRequest {
+        id: root.id.clone(),
+    };
+    let status = client_mut.purge(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    // the name is free while the category is archived, and taken back on restore
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: child.name.clone(),
+            parent_id: Some(root.id.clone()),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let namesake = client_mut.create(request).await.unwrap().into_inner();
+
+    let request = RestoreCategoryRequest {
+        id: child.id.clone(),
+    };
+    let status = client_mut.restore(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "name");
+
+    let request = DeleteCategoryRequest {
+        id: namesake.id,
+        event: CategoryEvent::Delete.into(),
+        ..Default::default()
+    };
+    client_mut.delete(request).await.unwrap();
+
+    let request = RestoreCategoryRequest {
+        id: child.id.clone(),
+    };
+    let restored = client_mut.restore(request).await.unwrap().into_inner();
+    assert_eq!(restored.categories.len(), 2);
+    assert!(restored
+        .categories
+        .iter()
+        .all(|category| category.deleted_at.is_none()));
+
+    let request = GetCategoryRequest {
+        id: root.id.clone(),
+        locale: None,
+    };
+    let parent = client.category_by_id(request).await.unwrap().into_inner();
+    assert_eq!(parent.sub_categories, vec![child.id.clone()]);
+
+    let request = DeleteCategoryRequest {
+        id: child.id.clone(),
+        event: CategoryEvent::Delete.into(),
+        ..Default::default()
+    };
+    client_mut.delete(request).await.unwrap();
+
+    let request = PurgeCategoryRequest {
+        id: child.id.clone(),
+    };
+    let purged = client_mut.purge(request).await.unwrap().into_inner();
+    assert_eq!(purged.categories.len(), 2);
+
+    let request = RestoreCategoryRequest {
+        id: child.id.clone(),
+    };
+    let status = client_mut.restore(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_sub_categories_per_parent(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut children = vec![];
+    for _ in 0..2 {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: fake::faker::name::raw::Name(EN).fake(),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        let parent = client_mut.create(request).await.unwrap().into_inner();
+
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: fake::faker::name::raw::Name(EN).fake(),
+                parent_id: Some(parent.id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        let child = client_mut.create(request).await.unwrap().into_inner();
+        children.push((parent.id, child.id));
+    }
+
+    // the first page of one parent is cached apart from the first page of the other
+    for (parent_id, child_id) in children {
+        let request = GetSubCategoriesRequest {
+            id: Some(parent_id),
+            pagination: Some(Cursor {
+                cursor_value: None,
+                index: Some(Index::First(5)),
+            }),
+            ..Default::default()
+        };
+        let connection = client.sub_categories(request).await.unwrap().into_inner();
+        let ids: Vec<_> = connection
+            .edges
+            .into_iter()
+            .map(|edge| edge.node.unwrap().id)
+            .collect();
+        assert_eq!(ids, vec![child_id]);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_delete_strategy(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut categories: Vec<Category> = vec![];
+    for parent_id in [None, Some(0), Some(1), Some(1)] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: fake::faker::name::raw::Name(EN).fake(),
+                parent_id: parent_id.map(|index: usize| categories[index].id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        categories.push(client_mut.create(request).await.unwrap().into_inner());
+    }
+    let (root, child) = (&categories[0], &categories[1]);
+
+    let delete = |strategy: DeleteStrategy, dry_run| DeleteCategoryRequest {
+        id: child.id.clone(),
+        event: CategoryEvent::Delete.into(),
+        strategy: strategy.into(),
+        dry_run,
+    };
+
+    let status = client_mut
+        .delete(delete(DeleteStrategy::Restrict, false))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    // a dry run reports the subtree without touching it
+    let response = client_mut
+        .delete(delete(DeleteStrategy::Cascade, true))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.deleted.len(), 3);
+    assert!(response.reparented.is_empty());
+
+    let request = GetCategoryRequest {
+        id: child.id.clone(),
+        locale: None,
+    };
+    assert!(client.category_by_id(request).await.is_ok());
+
+    let response = client_mut
+        .delete(delete(DeleteStrategy::Reparent, false))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.deleted.len(), 1);
+    assert_eq!(response.reparented.len(), 2);
+    assert!(response
+        .reparented
+        .iter()
+        .all(|category| category.parent_id.as_ref() == Some(&root.id)));
+
+    let request = GetCategoryRequest {
+        id: root.id.clone(),
+        locale: None,
+    };
+    let parent = client.category_by_id(request).await.unwrap().into_inner();
+    assert_eq!(
+        parent.sub_categories,
+        categories[2..]
+            .iter()
+            .map(|value| value.id.clone())
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_reparent_siblings(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut create = async |name: &str, parent_id: Option<String>| {
+        client_mut
+            .create(UpsertCategoryRequest {
+                category: Some(Category {
+                    name: name.to_string(),
+                    parent_id,
+                    ..Default::default()
+                }),
+                event: CategoryEvent::Create.into(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+    };
+
+    let apparel = create("Apparel", None).await;
+    let sandals = create("Sandals", Some(apparel.id.clone())).await;
+    create("Boots!", Some(apparel.id.clone())).await;
+    let shoes = create("Shoes", Some(apparel.id.clone())).await;
+    create("sandals", Some(shoes.id.clone())).await;
+    create("Boots", Some(shoes.id.clone())).await;
+
+    let delete = |id: &str, strategy: DeleteStrategy| DeleteCategoryRequest {
+        id: id.to_string(),
+        event: CategoryEvent::Delete.into(),
+        strategy: strategy.into(),
+        dry_run: false,
+    };
+
+    // the sandals of shoes would end up next to the sandals of apparel
+    let status = client_mut
+        .delete(delete(&shoes.id, DeleteStrategy::Reparent))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "strategy");
+
+    let request = GetCategoryRequest {
+        id: shoes.id.clone(),
+        locale: None,
+    };
+    let value = client.category_by_id(request).await.unwrap().into_inner();
+    assert_eq!(value.sub_categories.len(), 2);
+
+    client_mut
+        .delete(delete(&sandals.id, DeleteStrategy::Cascade))
+        .await
+        .unwrap();
+
+    // names no longer clash, the slug of boots does and is made unique
+    let response = client_mut
+        .delete(delete(&shoes.id, DeleteStrategy::Reparent))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.reparented.len(), 2);
+    let boots = response
+        .reparented
+        .iter()
+        .find(|category| category.name == "Boots")
+        .unwrap();
+    assert_ne!(boots.slug, "boots");
+    assert!(boots.slug.starts_with("boots"));
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_slugs(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut categories: Vec<Category> = vec![];
+    for (name, parent_id) in [
+        ("Electronics", None),
+        ("Mobile Phones", Some(0)),
+        // names are unique among siblings, slugs made from them need not be
+        ("Mobile-Phones", Some(0)),
+        ("Home & Garden", None),
+    ] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: name.to_string(),
+                parent_id: parent_id.map(|index: usize| categories[index].id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        categories.push(client_mut.create(request).await.unwrap().into_inner());
+    }
+
+    assert_eq!(categories[0].slug, "electronics");
+    assert_eq!(categories[1].slug, "mobile-phones");
+    // siblings cannot share a slug
+    assert_eq!(categories[2].slug, "mobile-phones-2");
+    assert_eq!(categories[3].slug, "home-garden");
+
+    let request = GetCategoryByPathRequest {
+        path: String::from("/electronics/mobile-phones"),
+    };
+    let category = client.category_by_path(request).await.unwrap().into_inner();
+    assert_eq!(category.id, categories[1].id);
+
+    let mut update = categories[1].clone();
+    update.slug = String::from("Phones");
+    let request = UpsertCategoryRequest {
+        category: Some(update),
+        event: CategoryEvent::Update.into(),
+    };
+    let updated = client_mut.update(request).await.unwrap().into_inner();
+    assert_eq!(updated.slug, "phones");
+
+    let request = MoveCategoryRequest {
+        id: categories[1].id.clone(),
+        parent_id: Some(categories[3].id.clone()),
+    };
+    client_mut.r#move(request).await.unwrap();
+
+    // old slugs and paths redirect to the category
+    for path in [
+        "electronics/mobile-phones",
+        "electronics/phones",
+        "home-garden/phones",
+    ] {
+        let request = GetCategoryByPathRequest {
+            path: path.to_string(),
+        };
+        let category = client.category_by_path(request).await.unwrap().into_inner();
+        assert_eq!(category.id, categories[1].id);
+    }
+
+    let request = GetCategoryBySlugRequest {
+        parent_id: Some(categories[0].id.clone()),
+        slug: String::from("mobile-phones-2"),
+    };
+    let category = client.category_by_slug(request).await.unwrap().into_inner();
+    assert_eq!(category.id, categories[2].id);
+
+    let request = GetCategoryByPathRequest {
+        path: String::from("electronics/tablets"),
+    };
+    let status = client.category_by_path(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_translations(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Phones"),
+            description: Some(String::from("Mobile phones")),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let category = client_mut.create(request).await.unwrap().into_inner();
+
+    let translation = |locale: &str, name: &str, description: Option<&str>| CategoryTranslation {
+        locale: locale.to_string(),
+        name: name.to_string(),
+        description: description.map(String::from),
+    };
+
+    let request = SetCategoryTranslationsRequest {
+        id: category.id.clone(),
+        translations: vec![
+            translation("pt_BR", "Celulares", Some("Telefones celulares")),
+            translation("fr", "Téléphones", None),
+        ],
+    };
+    let translations = client_mut
+        .set_translations(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .translations;
+    let locales: Vec<_> = translations
+        .iter()
+        .map(|value| value.locale.as_str())
+        .collect();
+    assert_eq!(locales, vec!["fr", "pt-br"]);
+
+    let by_id = |locale: &str| GetCategoryRequest {
+        id: category.id.clone(),
+        locale: Some(locale.to_string()),
+    };
+
+    // falls back to the language, and to the default description
+    let localised = client
+        .category_by_id(by_id("fr-CA"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(localised.name, "Téléphones");
+    assert_eq!(localised.description.as_deref(), Some("Mobile phones"));
+
+    let localised = client
+        .category_by_id(by_id("pt-BR"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(localised.name, "Celulares");
+    assert_eq!(
+        localised.description.as_deref(),
+        Some("Telefones celulares")
+    );
+
+    // falls back to the default name
+    let localised = client
+        .category_by_id(by_id("de"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(localised.name, "Phones");
+
+    let status = client
+        .category_by_id(by_id("not a locale"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let request = GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(10)),
+        }),
+        include_archived: false,
+        locale: Some(String::from("fr")),
+        ..Default::default()
+    };
+    let connection = client.categories(request).await.unwrap().into_inner();
+    assert_eq!(
+        connection.edges[0].node.as_ref().unwrap().name,
+        "Téléphones"
+    );
+
+    let request = SetCategoryTranslationsRequest {
+        id: category.id.clone(),
+        translations: vec![
+            translation("fr", "Téléphones", None),
+            translation("FR", "Portables", None),
+        ],
+    };
+    let status = client_mut.set_translations(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // locales that are left out are removed
+    let request = SetCategoryTranslationsRequest {
+        id: category.id.clone(),
+        translations: vec![translation("fr", "Portables", None)],
+    };
+    client_mut.set_translations(request).await.unwrap();
+
+    let request = GetCategoryRequest {
+        id: category.id.clone(),
+        locale: None,
+    };
+    let translations = client
+        .category_translations(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .translations;
+    assert_eq!(translations, vec![translation("fr", "Portables", None)]);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_attributes(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut categories: Vec<Category> = vec![];
+    for (name, parent_id) in [("Shoes", None), ("Running shoes", Some(0))] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: name.to_string(),
+                parent_id: parent_id.map(|index: usize| categories[index].id.clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        categories.push(client_mut.create(request).await.unwrap().into_inner());
+    }
+    let (shoes, running) = (&categories[0], &categories[1]);
+
+    let mut attributes = vec![];
+    for attribute in [
+        CategoryAttribute {
+            category_id: shoes.id.clone(),
+            name: String::from("Size"),
+            attribute_type: AttributeType::Enum.into(),
+            allowed_values: vec![String::from("40"), String::from("41")],
+            unit: Some(String::from("EU")),
+            required: true,
+            ..Default::default()
+        },
+        CategoryAttribute {
+            category_id: shoes.id.clone(),
+            name: String::from("Colour"),
+            ..Default::default()
+        },
+        CategoryAttribute {
+            category_id: running.id.clone(),
+            name: String::from("Drop"),
+            attribute_type: AttributeType::Number.into(),
+            unit: Some(String::from("mm")),
+            ..Default::default()
+        },
+    ] {
+        let request = UpsertCategoryAttributeRequest {
+            attribute: Some(attribute),
+        };
+        attributes.push(
+            client_mut
+                .create_attribute(request)
+                .await
+                .unwrap()
+                .into_inner(),
+        );
+    }
+    assert_eq!(attributes[0].allowed_values, vec!["40", "41"]);
+
+    // names are unique within a category
+    let request = UpsertCategoryAttributeRequest {
+        attribute: Some(CategoryAttribute {
+            category_id: shoes.id.clone(),
+            name: String::from("size"),
+            ..Default::default()
+        }),
+    };
+    let status = client_mut.create_attribute(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+
+    let resolved = |category_id: &str| GetCategoryAttributesRequest {
+        category_id: category_id.to_string(),
+    };
+
+    let inherited = client
+        .resolved_attributes(resolved(&running.id))
+        .await
+        .unwrap()
+        .into_inner()
+        .attributes;
+    let names: Vec<_> = inherited.iter().map(|value| value.name.as_str()).collect();
+    assert_eq!(names, vec!["Size", "Colour", "Drop"]);
+
+    // a sub category can narrow an inherited attribute
+    let request = UpsertCategoryAttributeRequest {
+        attribute: Some(CategoryAttribute {
+            category_id: running.id.clone(),
+            name: String::from("Size"),
+            attribute_type: AttributeType::Enum.into(),
+            allowed_values: vec![String::from("41")],
+            required: true,
+            ..Default::default()
+        }),
+    };
+    let size = client_mut
+        .create_attribute(request)
+        .await
+        .unwrap()
+        .into_inner();
+
+    let inherited = client
+        .resolved_attributes(resolved(&running.id))
+        .await
+        .unwrap()
+        .into_inner()
+        .attributes;
+    assert_eq!(inherited.len(), 3);
+    assert_eq!(inherited[0], size);
+
+    let own = client
+        .category_attributes(resolved(&shoes.id))
+        .await
+        .unwrap()
+        .into_inner()
+        .attributes;
+    assert_eq!(own, attributes[..2]);
+
+    let request = UpsertCategoryAttributeRequest {
+        attribute: Some(CategoryAttribute {
+            allowed_values: vec![String::from("42"), String::from("43")],
+            ..attributes[0].clone()
+        }),
+    };
+    let updated = client_mut
+        .update_attribute(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(updated.allowed_values, vec!["42", "43"]);
+
+    let request = DeleteCategoryAttributeRequest {
+        id: size.id.clone(),
+    };
+    client_mut.delete_attribute(request).await.unwrap();
+
+    let inherited = client
+        .resolved_attributes(resolved(&running.id))
+        .await
+        .unwrap()
+        .into_inner()
+        .attributes;
+    assert_eq!(inherited[0], updated);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_version_conflict(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let shoes = client_mut.create(request).await.unwrap().into_inner();
+    assert_eq!(shoes.version, 1);
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Footwear"),
+            ..shoes.clone()
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let footwear = client_mut.update(request).await.unwrap().into_inner();
+    assert!(footwear.version > shoes.version);
+
+    // still carries the version read before the rename
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Trainers"),
+            ..shoes.clone()
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let status = client_mut.update(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, VERSION_CONFLICT);
+    assert_eq!(
+        info.metadata.get("version"),
+        Some(&footwear.version.to_string())
+    );
+    let current: Category = serde_json::from_str(&info.metadata["category"]).unwrap();
+    assert_eq!(current.name, "Footwear");
+
+    // leaving out the version does not overwrite blindly
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Trainers"),
+            version: 0,
+            ..shoes
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let status = client_mut.update(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "category.version");
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Trainers"),
+            ..footwear
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let trainers = client_mut.update(request).await.unwrap().into_inner();
+    assert_eq!(trainers.name, "Trainers");
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_external_id(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let request = UpsertCategoriesRequest {
+        categories: vec![
+            Category {
+                name: String::from("Apparel"),
+                external_id: Some(String::from("166")),
+                ..Default::default()
+            },
+            Category {
+                name: String::from("Home"),
+                ..Default::default()
+            },
+        ],
+        event: CategoryEvent::Create.into(),
+    };
+    let created = client_mut.create_many(request).await.unwrap().into_inner();
+    assert_eq!(created.categories[0].external_id.as_deref(), Some("166"));
+    assert_eq!(created.categories[1].external_id, None);
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            external_id: Some(String::from("536")),
+            ..created.categories[1].clone()
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let home = client_mut.update(request).await.unwrap().into_inner();
+    assert_eq!(home.external_id.as_deref(), Some("536"));
+
+    // external IDs are unique
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Clothing"),
+            external_id: Some(String::from("166")),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    assert!(client_mut.create(request).await.is_err());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_revisions(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let shoes = client_mut.create(request).await.unwrap().into_inner();
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Footwear"),
+            ..shoes.clone()
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    client_mut.update(request).await.unwrap();
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Clothing"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let clothing = client_mut.create(request).await.unwrap().into_inner();
+
+    let request = MoveCategoryRequest {
+        id: shoes.id.clone(),
+        parent_id: Some(clothing.id.clone()),
+    };
+    client_mut.r#move(request).await.unwrap();
+
+    let revisions = |pagination: Cursor| GetCategoryRevisionsRequest {
+        id: shoes.id.clone(),
+        pagination: Some(pagination),
+    };
+
+    let request = revisions(Cursor {
+        cursor_value: None,
+        index: Some(Index::First(10)),
+    });
+    let res = client
+        .category_revisions(request)
+        .await
+        .unwrap()
+        .into_inner();
+    let history: Vec<_> = res
+        .edges
+        .iter()
+        .filter_map(|edge| edge.node.as_ref())
+        .collect();
+
+    assert_eq!(
+        history
+            .iter()
+            .map(|revision| revision.operation())
+            .collect::<Vec<_>>(),
+        vec![
+            RevisionOperation::Move,
+            RevisionOperation::Update,
+            RevisionOperation::Create
+        ]
+    );
+    // adding a sub category to clothing is not a change to shoes
+    assert!(history
+        .iter()
+        .all(|revision| revision.category_id == shoes.id));
+
+    let created = history[2];
+    assert_eq!(created.before, None);
+    assert_eq!(created.after.as_ref().unwrap().name, "Shoes");
+    // changes are recorded as made by the signed in caller
+    assert_eq!(created.actor.as_deref(), Some("admin"));
+
+    let renamed = history[1];
+    assert_eq!(renamed.before.as_ref().unwrap().name, "Shoes");
+    assert_eq!(renamed.after.as_ref().unwrap().name, "Footwear");
+    assert_eq!(renamed.actor.as_deref(), Some("admin"));
+
+    let moved = history[0];
+    assert_eq!(
+        moved.after.as_ref().unwrap().parent_id.as_ref(),
+        Some(&clothing.id)
+    );
+
+    // page through the same history one revision at a time
+    let request = revisions(Cursor {
+        cursor_value: None,
+        index: Some(Index::First(1)),
+    });
+    let page = client
+        .category_revisions(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(page.edges.len(), 1);
+    assert!(page.page_info.as_ref().unwrap().has_next_page);
+
+    let request = revisions(Cursor {
+        cursor_value: Some(CursorValue {
+            cursor_type: Some(CursorType::After(page.edges[0].cursor.clone())),
+        }),
+        index: Some(Index::First(5)),
+    });
+    let page = client
+        .category_revisions(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(page.edges.len(), 2);
+    let page_info = page.page_info.unwrap();
+    assert!(page_info.has_previous_page);
+    assert!(!page_info.has_next_page);
+
+    let request = RevertToRevisionRequest {
+        revision_id: created.id,
+        version: 0,
+    };
+    let status = client_mut.revert_to_revision(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let request = GetCategoryRequest {
+        id: shoes.id.clone(),
+        locale: None,
+    };
+    let current = client.category_by_id(request).await.unwrap().into_inner();
+
+    let request = RevertToRevisionRequest {
+        revision_id: created.id,
+        version: current.version,
+    };
+    let reverted = client_mut
+        .revert_to_revision(request)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reverted.name, "Shoes");
+    assert_eq!(reverted.parent_id, None);
+
+    let request = revisions(Cursor {
+        cursor_value: None,
+        index: Some(Index::First(1)),
+    });
+    let res = client
+        .category_revisions(request)
+        .await
+        .unwrap()
+        .into_inner();
+    let revert = res.edges[0].node.as_ref().unwrap();
+    assert_eq!(revert.operation(), RevisionOperation::Revert);
+    assert_eq!(revert.reverted_from, Some(created.id));
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_outbox(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool.clone(), tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let shoes = client_mut.create(request).await.unwrap().into_inner();
+
+    // the event is written with the category, whether or not it was relayed yet
+    let subjects = sqlx::query_scalar!("select subject from category_outbox order by id")
+        .fetch_all(&pg_pool)
+        .await?;
+    assert_eq!(subjects, vec!["categories.update.index.set.single"]);
+
+    // a failed mutation writes no event
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Running shoes"),
+            parent_id: Some(String::from("does-not-exist")),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    client_mut.create(request).await.unwrap_err();
+
+    let request = MoveCategoryRequest {
+        id: shoes.id.clone(),
+        parent_id: None,
+    };
+    client_mut.r#move(request).await.unwrap();
+
+    // translating a category updates it, the cache drops its localised copies
+    let request = SetCategoryTranslationsRequest {
+        id: shoes.id,
+        translations: vec![CategoryTranslation {
+            locale: String::from("fr"),
+            name: String::from("Chaussures"),
+            description: None,
+        }],
+    };
+    client_mut.set_translations(request).await.unwrap();
+
+    let subjects = sqlx::query_scalar!("select subject from category_outbox order by id")
+        .fetch_all(&pg_pool)
+        .await?;
+    assert_eq!(
+        subjects,
+        vec![
+            "categories.update.index.set.single",
+            "categories.update.index.update.single",
+            "categories.update.index.update.single"
+        ]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_category_sorting(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut ids: Vec<String> = Vec::new();
+    for (name, parent) in [
+        ("Books", None),
+        ("Shoes", None),
+        ("Sandals", Some(1)),
+        ("Bags", None),
+        ("Sneakers", Some(1)),
+    ] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: String::from(name),
+                parent_id: parent.map(|index: usize| ids[index].clone()),
+                image_url: (name == "Shoes").then(|| String::from("https://example.com/shoes.png")),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        let category = client_mut.create(request).await.unwrap().into_inner();
+        ids.push(category.id);
+    }
+
+    let list = |index: Index, cursor: Option<CursorType>, filter: Option<CategoryFilter>| {
+        GetCategoriesRequest {
+            pagination: Some(Cursor {
+                cursor_value: cursor.map(|value| CursorValue {
+                    cursor_type: Some(value),
+                }),
+                index: Some(index),
+            }),
+            sort: CategorySortField::Name.into(),
+            direction: SortDirection::Desc.into(),
+            filter,
+            ..Default::default()
+        }
+    };
+    let names = |connection: &sellershut_core::categories::Connection| {
+        connection
+            .edges
+            .iter()
+            .map(|edge| edge.node.as_ref().unwrap().name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let first = client
+        .categories(list(Index::First(2), None, None))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&first), vec!["Sneakers", "Shoes"]);
+    let page_info = first.page_info.clone().unwrap();
+    assert!(page_info.has_next_page);
+    assert!(!page_info.has_previous_page);
+
+    // the cursor carries the name, so the next page continues in name order
+    let cursor = first.edges[1].cursor.clone();
+    let second = client
+        .categories(list(Index::First(2), Some(CursorType::After(cursor)), None))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&second), vec!["Sandals", "Books"]);
+    let page_info = second.page_info.unwrap();
+    assert!(page_info.has_next_page);
+    assert!(page_info.has_previous_page);
+
+    let cursor = second.edges[0].cursor.clone();
+    let previous = client
+        .categories(list(Index::Last(2), Some(CursorType::Before(cursor)), None))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&previous), names(&first));
+
+    let last = client
+        .categories(list(Index::Last(2), None, None))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&last), vec!["Books", "Bags"]);
+    assert!(!last.page_info.unwrap().has_next_page);
+
+    let filter = |filter: CategoryFilter| list(Index::First(10), None, Some(filter));
+
+    let starting = client
+        .categories(filter(CategoryFilter {
+            name_prefix: Some(String::from("s")),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&starting), vec!["Sneakers", "Shoes", "Sandals"]);
+
+    let containing = client
+        .categories(filter(CategoryFilter {
+            name_contains: Some(String::from("OO")),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&containing), vec!["Books"]);
+
+    let with_image = client
+        .categories(filter(CategoryFilter {
+            has_image: Some(true),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&with_image), vec!["Shoes"]);
+
+    let roots = client
+        .categories(filter(CategoryFilter {
+            level: CategoryLevel::Root.into(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&roots), vec!["Shoes", "Books", "Bags"]);
+
+    let leaves = client
+        .categories(filter(CategoryFilter {
+            level: CategoryLevel::Leaf.into(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(names(&leaves), vec!["Sneakers", "Sandals", "Books", "Bags"]);
+
+    // a cursor only carries on the listing it was issued for
+    let request = GetCategoriesRequest {
+        sort: CategorySortField::UpdatedAt.into(),
+        ..list(
+            Index::First(2),
+            Some(CursorType::After(first.edges[0].cursor.clone())),
+            None,
+        )
+    };
+    let status = client.categories(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let request = list(
+        Index::First(2),
+        Some(CursorType::After(first.edges[0].cursor.clone())),
+        Some(CategoryFilter {
+            has_image: Some(true),
+            ..Default::default()
+        }),
+    );
+    let status = client.categories(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // cursors cannot be forged
+    let forged = CursorBuilder::new(&ids[2], "Sandals")
+        .encode(&CursorSigner::new(b"not the secret"), b"name:desc");
+    let request = list(Index::First(2), Some(CursorType::After(forged)), None);
+    let status = client.categories(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_offset_pagination(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    let mut ids: Vec<String> = Vec::new();
+    for (name, parent) in [
+        ("Books", None),
+        ("Shoes", None),
+        ("Sandals", Some(1)),
+        ("Sneakers", Some(1)),
+        ("Boots", Some(1)),
+    ] {
+        let request = UpsertCategoryRequest {
+            category: Some(Category {
+                name: String::from(name),
+                parent_id: parent.map(|index: usize| ids[index].clone()),
+                ..Default::default()
+            }),
+            event: CategoryEvent::Create.into(),
+        };
+        let category = client_mut.create(request).await.unwrap().into_inner();
+        ids.push(category.id);
+    }
+
+    let names = |connection: &sellershut_core::categories::Connection| {
+        connection
+            .edges
+            .iter()
+            .map(|edge| edge.node.as_ref().unwrap().name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let request = GetCategoriesRequest {
+        offset: Some(Offset {
+            offset: 2,
+            limit: 2,
+        }),
+        sort: CategorySortField::Name.into(),
+        include_total_count: true,
+        ..Default::default()
+    };
+    let page = client.categories(request).await.unwrap().into_inner();
+    assert_eq!(names(&page), vec!["Sandals", "Shoes"]);
+    let page_info = page.page_info.unwrap();
+    assert!(page_info.has_previous_page);
+    assert!(page_info.has_next_page);
+    assert_eq!(page_info.total_count, Some(5));
+
+    // counts are only made when asked for
+    let request = GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(2)),
+        }),
+        ..Default::default()
+    };
+    let page = client.categories(request).await.unwrap().into_inner();
+    assert_eq!(page.page_info.unwrap().total_count, None);
+
+    let request = GetSubCategoriesRequest {
+        id: Some(ids[1].clone()),
+        offset: Some(Offset {
+            offset: 2,
+            limit: 10,
+        }),
+        include_total_count: true,
+        ..Default::default()
+    };
+    let page = client.sub_categories(request).await.unwrap().into_inner();
+    assert_eq!(names(&page), vec!["Boots"]);
+    let page_info = page.page_info.unwrap();
+    assert!(!page_info.has_next_page);
+    assert_eq!(page_info.total_count, Some(3));
+
+    let request = GetSubCategoriesRequest {
+        include_total_count: true,
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(10)),
+        }),
+        ..Default::default()
+    };
+    let page = client.sub_categories(request).await.unwrap().into_inner();
+    assert_eq!(page.page_info.unwrap().total_count, Some(2));
+
+    let request = GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(2)),
+        }),
+        offset: Some(Offset {
+            offset: 0,
+            limit: 2,
+        }),
+        ..Default::default()
+    };
+    let status = client.categories(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let request = GetCategoriesRequest {
+        offset: Some(Offset {
+            offset: -1,
+            limit: 2,
+        }),
+        ..Default::default()
+    };
+    let status = client.categories(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_error_details(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+
+    // leaving out the category is rejected rather than failing the server
+    let request = UpsertCategoryRequest {
+        category: None,
+        event: CategoryEvent::Create.into(),
+    };
+    let status = client_mut.create(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "category");
+
+    let request = GetCategoryRequest {
+        id: "does_not_exist_123456".to_string(),
+        locale: None,
+    };
+    let status = client.category_by_id(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_validation(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("  "),
+            image_url: Some(String::from("not a url")),
+            sub_categories: vec![String::from("shoes")],
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let status = client_mut.create(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let fields: Vec<_> = status
+        .get_details_bad_request()
+        .unwrap()
+        .field_violations
+        .into_iter()
+        .map(|violation| violation.field)
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            "category.name",
+            "category.image_url",
+            "category.sub_categories[0]"
+        ]
+    );
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from(" Shoes "),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let shoes = client_mut.create(request).await.unwrap().into_inner();
+    assert_eq!(shoes.name, "Shoes");
+
+    // siblings cannot share a name, whatever the case
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+    let status = client_mut.create(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "category.name");
+
+    let request = UpsertCategoriesRequest {
+        categories: vec![
+            Category {
+                name: String::from("Boots"),
+                parent_id: Some(shoes.id.clone()),
+                ..Default::default()
+            },
+            Category {
+                name: String::from("BOOTS"),
+                parent_id: Some(shoes.id.clone()),
+                ..Default::default()
+            },
+        ],
+        event: CategoryEvent::Create.into(),
+    };
+    let status = client_mut.create_many(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations[0].field, "categories[1].name");
+
+    let request = UpsertCategoryRequest {
+        category: Some(Category {
+            id: String::from("not-an-id"),
+            name: String::from("Shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Update.into(),
+    };
+    let status = client_mut.update(request).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_auth(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let request = || UpsertCategoryRequest {
+        category: Some(Category {
+            name: String::from("Shoes"),
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+
+    let mut anonymous = MutateCategoriesClient::connect(address.clone())
+        .await
+        .unwrap();
+    let status = anonymous.create(request()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let mut request_with_key = request().into_request();
+    request_with_key
+        .metadata_mut()
+        .insert(API_KEY_HEADER, "not-a-key".parse().unwrap());
+    let status = anonymous.create(request_with_key).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let mut client_mut = mutate_client(address.clone()).await;
+    client_mut.create(request()).await.unwrap();
+
+    // queries stay public
+    let mut client = QueryCategoriesClient::connect(address).await.unwrap();
+    client
+        .categories(GetCategoriesRequest::default())
+        .await
+        .unwrap();
+
+    Ok(())
+}
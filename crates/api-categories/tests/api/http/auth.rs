@@ -0,0 +1,90 @@
+use api_categories::auth::Identity;
+use async_graphql::{Request, Value};
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+
+use crate::utils::{admin_token, TestApp};
+
+const CREATE: &str = r#"mutation { create(input: { name: "Shoes" }) { name } }"#;
+
+fn error_code(response: &async_graphql::Response) -> Option<&Value> {
+    response.errors[0].extensions.as_ref()?.get("code")
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_mutations_need_admins(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, _rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let response = app.schema.execute(CREATE).await;
+    assert_eq!(error_code(&response), Some(&Value::from("UNAUTHENTICATED")));
+
+    let seller = Identity {
+        subject: String::from("seller"),
+        roles: vec![String::from("seller")],
+    };
+    let response = app.schema.execute(Request::new(CREATE).data(seller)).await;
+    assert_eq!(
+        error_code(&response),
+        Some(&Value::from("PERMISSION_DENIED"))
+    );
+
+    let admin = Identity {
+        subject: String::from("admin"),
+        roles: vec![String::from("admin")],
+    };
+    let response = app.schema.execute(Request::new(CREATE).data(admin)).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    // queries stay public
+    let response = app
+        .schema
+        .execute("{ categories(first: 5) { totalCount } }")
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap()["categories"]["totalCount"],
+        1
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_bearer_tokens(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let client = reqwest::Client::new();
+    let body = json!({ "query": CREATE }).to_string();
+
+    let response = client
+        .post(&address)
+        .header("content-type", "application/json")
+        .header("authorization", "Bearer not-a-token")
+        .body(body.clone())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(&address)
+        .header("content-type", "application/json")
+        .header("authorization", format!("Bearer {}", admin_token()))
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(response.status().is_success());
+
+    let response: serde_json::Value =
+        serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(response["data"]["create"]["name"], "Shoes");
+
+    Ok(())
+}
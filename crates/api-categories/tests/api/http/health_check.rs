@@ -1,5 +1,15 @@
+use std::time::Duration;
+
+use sellershut_core::categories::query_categories_server::QueryCategoriesServer;
+use serde_json::Value;
 use sqlx::PgPool;
 use tokio::sync::oneshot;
+use tonic::{server::NamedService, transport::Channel};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+
+use api_categories::state::ApiState;
 
 use crate::utils::TestApp;
 
@@ -21,5 +31,70 @@ async fn check_health_endpoint(pg_pool: PgPool) -> sqlx::Result<()> {
 
     assert!(response.status().is_success());
 
+    let response = client
+        .get(format!("{address}/health/live"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_readiness_endpoint(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let response = reqwest::Client::new()
+        .get(format!("{address}/health/ready"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+
+    let body: Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(body["status"], "ok");
+    for dependency in ["postgres", "redis", "jetstream"] {
+        assert_eq!(body["dependencies"][dependency]["status"], "up");
+    }
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_grpc_health(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let _app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let channel = Channel::from_shared(address)
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    let mut client = HealthClient::new(channel);
+
+    // the first probe runs as the server starts
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    for service in ["", QueryCategoriesServer::<ApiState>::NAME] {
+        let response = client
+            .check(HealthCheckRequest {
+                service: service.to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status(), ServingStatus::Serving);
+    }
+
     Ok(())
 }
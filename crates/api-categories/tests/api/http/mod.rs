@@ -1 +1,6 @@
+mod auth;
 mod health_check;
+mod pagination;
+mod persisted;
+mod relations;
+mod subscription;
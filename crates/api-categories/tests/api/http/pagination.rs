@@ -0,0 +1,75 @@
+use sellershut_core::categories::{Category, CategoryEvent, UpsertCategoryRequest};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+
+use crate::utils::{mutate_client, TestApp};
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_sub_category_pages(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let mut create = async |name: &str, parent_id: Option<String>| {
+        client_mut
+            .create(UpsertCategoryRequest {
+                category: Some(Category {
+                    name: name.to_string(),
+                    parent_id,
+                    ..Default::default()
+                }),
+                event: CategoryEvent::Create.into(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+    };
+
+    let shoes = create("Shoes", None).await;
+    for name in ["Sneakers", "Boots", "Sandals"] {
+        create(name, Some(shoes.id.clone())).await;
+    }
+
+    let page = async |after: Option<&str>| {
+        let after = after
+            .map(|cursor| format!(r#", after: "{cursor}""#))
+            .unwrap_or_default();
+        let response = app
+            .schema
+            .execute(format!(
+                r#"{{ subCategories(parentId: "{}", first: 2{after}) {{
+                    pageInfo {{ hasNextPage endCursor }}
+                    edges {{ node {{ name }} }}
+                }} }}"#,
+                shoes.id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()["subCategories"].clone()
+    };
+
+    let first = page(None).await;
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+    assert_eq!(first["edges"].as_array().unwrap().len(), 2);
+
+    // signed cursors are longer than ids, the service takes back the ones it hands out
+    let after = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second = page(Some(after)).await;
+    assert_eq!(second["pageInfo"]["hasNextPage"], false);
+
+    let names: Vec<_> = [first, second]
+        .iter()
+        .flat_map(|page| page["edges"].as_array().unwrap().clone())
+        .map(|edge| edge["node"]["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(names.len(), 3);
+    for name in ["Sneakers", "Boots", "Sandals"] {
+        assert!(names.iter().any(|value| value == name));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use async_graphql::{Request, Value};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+
+use crate::utils::TestApp;
+
+fn persisted(query: &str, hash: &str) -> Request {
+    let mut request = Request::new(query);
+    request.extensions.insert(
+        "persistedQuery".to_string(),
+        Value::from_json(json!({ "version": 1, "sha256Hash": hash })).unwrap(),
+    );
+    request
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_persisted_queries(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, _rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let query = "{ categories(first: 5) { totalCount } }";
+    let hash = format!("{:x}", Sha256::digest(query.as_bytes()));
+
+    // an unknown hash has the client send the query
+    let response = app.schema.execute(persisted("", &hash)).await;
+    assert_eq!(response.errors[0].message, "PersistedQueryNotFound");
+
+    let response = app.schema.execute(persisted(query, &"0".repeat(64))).await;
+    assert!(!response.errors.is_empty());
+
+    let response = app.schema.execute(persisted(query, &hash)).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    // registered, the hash is enough
+    let response = app.schema.execute(persisted("", &hash)).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap()["categories"]["totalCount"],
+        0
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_query_limits(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, _rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let nested = (0..20).fold(String::from("name"), |fields, _| {
+        format!("parent {{ {fields} }}")
+    });
+    let response = app
+        .schema
+        .execute(format!(
+            r#"{{ categoryById(id: "{}") {{ {nested} }} }}"#,
+            "a".repeat(21)
+        ))
+        .await;
+    assert_eq!(response.errors[0].message, "Query is nested too deep.");
+
+    // every page of children multiplies the fields below it
+    let response = app
+        .schema
+        .execute(
+            "{ categories(first: 100) { edges { node { children(first: 100) { edges { node { id name slug } } } } } } }",
+        )
+        .await;
+    assert_eq!(response.errors[0].message, "Query is too complex.");
+
+    // every level of a tree counts as a page, however few categories there are
+    let tree = (0..6).fold(String::from("category { id name slug }"), |fields, _| {
+        format!("category {{ id name slug }} children {{ {fields} }}")
+    });
+    let response = app
+        .schema
+        .execute(format!("{{ categoryTree {{ {tree} }} }}"))
+        .await;
+    assert_eq!(response.errors[0].message, "Query is too complex.");
+
+    let response = app
+        .schema
+        .execute("{ categoryTree(maxDepth: 7) { category { id } } }")
+        .await;
+    assert!(!response.errors.is_empty());
+
+    let response = app
+        .schema
+        .execute(format!(
+            r#"{{ categoryRevisions(id: "{}", first: 100) {{
+                edges {{ node {{ id before {{ children(first: 20) {{ edges {{ node {{ id name }} }} }} }} }} }}
+            }} }}"#,
+            "a".repeat(21)
+        ))
+        .await;
+    assert_eq!(response.errors[0].message, "Query is too complex.");
+
+    Ok(())
+}
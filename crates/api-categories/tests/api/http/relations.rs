@@ -0,0 +1,115 @@
+use async_graphql::Value;
+use sellershut_core::categories::{Category, CategoryEvent, UpsertCategoryRequest};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+
+use crate::utils::{mutate_client, TestApp};
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_category_relations(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let mut create = async |name: &str, parent_id: Option<String>| {
+        client_mut
+            .create(UpsertCategoryRequest {
+                category: Some(Category {
+                    name: name.to_string(),
+                    parent_id,
+                    ..Default::default()
+                }),
+                event: CategoryEvent::Create.into(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+    };
+
+    let apparel = create("Apparel", None).await;
+    let shoes = create("Shoes", Some(apparel.id.clone())).await;
+    for name in ["Sneakers", "Boots", "Sandals"] {
+        create(name, Some(shoes.id.clone())).await;
+    }
+
+    let response = app
+        .schema
+        .execute(format!(
+            r#"{{ categoryById(id: "{}") {{
+                parent {{ name }}
+                ancestors {{ name }}
+                children(first: 2) {{
+                    totalCount
+                    pageInfo {{ hasNextPage endCursor }}
+                    edges {{ node {{ name parent {{ id }} }} }}
+                }}
+            }} }}"#,
+            shoes.id
+        ))
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    let data = response.data.into_json().unwrap();
+    let category = &data["categoryById"];
+    assert_eq!(category["parent"]["name"], "Apparel");
+    assert_eq!(category["ancestors"][0]["name"], "Apparel");
+
+    let children = &category["children"];
+    assert_eq!(children["totalCount"], 3);
+    assert_eq!(children["pageInfo"]["hasNextPage"], true);
+    assert_eq!(children["edges"][0]["node"]["name"], "Sneakers");
+    assert_eq!(children["edges"][1]["node"]["parent"]["id"], shoes.id);
+
+    let after = children["pageInfo"]["endCursor"].as_str().unwrap();
+    let response = app
+        .schema
+        .execute(format!(
+            r#"{{ categoryById(id: "{}") {{
+                children(first: 10, after: "{after}") {{
+                    pageInfo {{ hasNextPage hasPreviousPage }}
+                    edges {{ node {{ name ancestors {{ name }} }} }}
+                }}
+            }} }}"#,
+            shoes.id
+        ))
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    let data = response.data.into_json().unwrap();
+    let children = &data["categoryById"]["children"];
+    assert_eq!(children["pageInfo"]["hasNextPage"], false);
+    assert_eq!(children["pageInfo"]["hasPreviousPage"], true);
+    assert_eq!(children["edges"][0]["node"]["name"], "Sandals");
+    assert_eq!(
+        children["edges"][0]["node"]["ancestors"][1]["name"],
+        "Shoes"
+    );
+
+    // cursors are signed for the category they page through
+    for cursor in [after, "1"] {
+        let response = app
+            .schema
+            .execute(format!(
+                r#"{{ categoryById(id: "{}") {{
+                    children(first: 10, after: "{cursor}") {{ edges {{ node {{ name }} }} }}
+                }} }}"#,
+                apparel.id
+            ))
+            .await;
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(
+            response.errors[0]
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("status"),
+            Some(&Value::from("INVALID_ARGUMENT"))
+        );
+    }
+
+    Ok(())
+}
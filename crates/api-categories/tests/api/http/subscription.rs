@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use sellershut_core::categories::{Category, CategoryEvent, UpsertCategoryRequest};
+use sqlx::PgPool;
+use tokio::sync::oneshot;
+
+use crate::utils::{mutate_client, TestApp};
+
+#[sqlx::test(migrations = "./migrations")]
+async fn check_category_subscriptions(pg_pool: PgPool) -> sqlx::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    let app = TestApp::new(pg_pool, tx).await;
+
+    let port = rx.await.unwrap();
+    let address = format!("http://127.0.0.1:{port}");
+
+    let mut client_mut = mutate_client(address).await;
+
+    let create = |name: &str, parent_id: Option<String>| UpsertCategoryRequest {
+        category: Some(Category {
+            name: name.to_string(),
+            parent_id,
+            ..Default::default()
+        }),
+        event: CategoryEvent::Create.into(),
+    };
+
+    let shoes = client_mut
+        .create(create("Shoes", None))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut created = app.schema.execute_stream(format!(
+        r#"subscription {{ categoryCreated(parentId: "{}") {{ name parentId }} }}"#,
+        shoes.id
+    ));
+    let mut updated = app
+        .schema
+        .execute_stream("subscription { categoryUpdated { id name } }");
+
+    // subscriptions start listening once they are polled
+    let created = tokio::spawn(async move { created.next().await });
+    let updated = tokio::spawn(async move { updated.next().await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    client_mut.create(create("Bags", None)).await.unwrap();
+    client_mut
+        .create(create("Sneakers", Some(shoes.id.clone())))
+        .await
+        .unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(10), created)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["categoryCreated"]["name"], "Sneakers");
+    assert_eq!(data["categoryCreated"]["parentId"], shoes.id);
+
+    // adding a sub category updates its parent
+    let response = tokio::time::timeout(Duration::from_secs(10), updated)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["categoryUpdated"]["id"], shoes.id);
+
+    Ok(())
+}
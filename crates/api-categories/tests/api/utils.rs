@@ -1,25 +1,35 @@
 use axum::{body::Body, http::Request, http::Response, Router};
 use core_services::{
     cache::new_redis_pool_helper,
+    shutdown::Shutdown,
     state::{
         config::{env_var, Configuration, Environment},
         ServiceState,
     },
     tracing::TelemetryBuilder,
 };
+use sellershut_core::categories::mutate_categories_client::MutateCategoriesClient;
 use sqlx::PgPool;
+use tonic::{service::interceptor::InterceptedService, transport::Channel};
 use tracing::trace;
 
 use api_categories::{
-    api::{ApiSchema, ApiSchemaBuilder},
+    api::{persisted::PersistedQueries, ApiSchema, ApiSchemaBuilder},
+    auth::ADMIN_ROLE,
     routes::router,
-    state::ApiState,
+    state::{relay_changes, ApiState, Changes},
 };
-use std::sync::Once;
+use std::{sync::Once, time::Duration};
 use tower::util::ServiceExt;
 
 static TRACING: Once = Once::new();
 
+type SignIn = fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status>;
+
+/// A client for the mutations, signed in as an admin
+pub type MutateClient = MutateCategoriesClient<InterceptedService<Channel, SignIn>>;
+
+#[allow(dead_code)]
 pub struct TestApp {
     pub router: Router,
     pub state: ApiState,
@@ -56,11 +66,22 @@ impl TestApp {
         };
 
         trace!("building schema");
-        let schema = ApiSchemaBuilder::build(state.clone());
+        let shutdown = Shutdown::new(Duration::from_secs(5));
+        let changes = Changes::default();
+        tokio::spawn(relay_changes(
+            state.clone(),
+            changes.clone(),
+            shutdown.clone(),
+        ));
+        let schema = ApiSchemaBuilder::new(state.clone(), changes)
+            .limit_depth(env_var("GRAPHQL_MAX_DEPTH").parse().unwrap())
+            .limit_complexity(env_var("GRAPHQL_MAX_COMPLEXITY").parse().unwrap())
+            .persisted_queries(PersistedQueries::Automatic(state.state.cache.clone()))
+            .build();
 
-        let router = router(schema.clone(), Environment::Development);
+        let router = router(schema.clone(), state.clone(), Environment::Development);
 
-        tokio::spawn(api_categories::run(state.clone(), tx));
+        tokio::spawn(api_categories::run(state.clone(), shutdown, tx));
 
         Self {
             router,
@@ -69,7 +90,42 @@ impl TestApp {
         }
     }
 
+    #[allow(dead_code)]
     pub async fn request(&self, req: Request<Body>) -> Response<Body> {
         self.router.clone().oneshot(req).await.unwrap()
     }
 }
+
+/// Connects to the mutations at `address`, signed in as `admin`
+pub async fn mutate_client(address: String) -> MutateClient {
+    let channel = Channel::from_shared(address)
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    MutateCategoriesClient::with_interceptor(channel, sign_in as SignIn)
+}
+
+#[allow(clippy::result_large_err)]
+fn sign_in(mut request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+    let authorization = format!("Bearer {}", admin_token());
+    request
+        .metadata_mut()
+        .insert("authorization", authorization.parse().unwrap());
+    Ok(request)
+}
+
+/// A token for `admin`, signed with the secret the service verifies tokens with
+pub fn admin_token() -> String {
+    let claims = serde_json::json!({
+        "sub": "admin",
+        "roles": [ADMIN_ROLE],
+        "exp": jsonwebtoken::get_current_timestamp() + 3600,
+    });
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(env_var("AUTH_JWT_SECRET").as_bytes()),
+    )
+    .unwrap()
+}
@@ -6,6 +6,7 @@ use axum::{
     routing::get,
     Router,
 };
+use core_services::shutdown::Shutdown;
 use opentelemetry::{global, trace::TraceContextExt};
 use opentelemetry_http::HeaderExtractor;
 use sentry::integrations::tower::{NewSentryLayer, SentryHttpLayer};
@@ -15,7 +16,7 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::state::ApiState;
 
-pub async fn serve(state: Arc<ApiState>) -> anyhow::Result<()> {
+pub async fn serve(state: Arc<ApiState>, shutdown: Shutdown) -> anyhow::Result<()> {
     let addr = state.state.config.listen_address;
 
     let app = Router::new()
@@ -38,7 +39,9 @@ pub async fn serve(state: Arc<ApiState>) -> anyhow::Result<()> {
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     info!(addr = ?addr, "listening");
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.triggered())
+        .await?;
 
     Ok(())
 }
@@ -2,13 +2,13 @@ use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use async_nats::jetstream::{consumer, stream, Context};
-use core_services::state::config::env_var;
+use core_services::{shutdown::Shutdown, state::config::env_var};
 use futures_util::{StreamExt, TryFutureExt};
 use tracing::{debug, error, info};
 
 use crate::state::ApiState;
 
-pub async fn serve(state: Arc<ApiState>) -> Result<()> {
+pub async fn serve(state: Arc<ApiState>, shutdown: Shutdown) -> Result<()> {
     let js = state.state.jetstream_context.clone();
 
     let consumers_iter: Vec<_> = env_var("EVENT_PUBLISHING_SERVICES")
@@ -51,14 +51,17 @@ pub async fn serve(state: Arc<ApiState>) -> Result<()> {
         consumers.push(consumer);
     }
 
-    futures_util::stream::iter(consumers)
-        .for_each_concurrent(None, |consumer| {
-            let state = Arc::clone(&state);
-            async move {
-                tokio::spawn(async move { handle_message(consumer, state).await });
-            }
-        })
-        .await;
+    let handles = consumers.into_iter().map(|consumer| {
+        let state = Arc::clone(&state);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move { handle_message(consumer, state, shutdown).await })
+    });
+
+    for result in futures_util::future::join_all(handles).await {
+        if let Ok(Err(e)) = result {
+            error!("{e}");
+        }
+    }
 
     Ok(())
 }
@@ -66,11 +69,18 @@ pub async fn serve(state: Arc<ApiState>) -> Result<()> {
 async fn handle_message(
     consumer: consumer::Consumer<consumer::pull::Config>,
     state: Arc<ApiState>,
+    shutdown: Shutdown,
 ) -> anyhow::Result<()> {
     // Get messages
     let mut messages = consumer.messages().await?;
-
-    while let Some(Ok(message)) = messages.next().await {
+    let stopped = shutdown.triggered();
+    tokio::pin!(stopped);
+
+    // a message is seen through to its ack, unacked messages are redelivered to the next consumer
+    while let Some(Ok(message)) = tokio::select! {
+        message = messages.next() => message,
+        _ = &mut stopped => None,
+    } {
         info!("Got message {:?}", message);
         if let Err(e) = message.ack().await {
             error!("{e}");
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use api::{http, pub_sub};
+use core_services::shutdown::Shutdown;
 use state::ApiState;
 
 pub mod api;
@@ -9,13 +10,20 @@ pub mod state;
 #[cfg(feature = "nlp")]
 pub mod nlp;
 
-pub async fn serve(config: core_services::state::config::Configuration) -> anyhow::Result<()> {
+/// Serves HTTP and consumes events until `shutdown` starts, then finishes in-flight work within
+/// the shutdown timeout
+pub async fn serve(
+    config: core_services::state::config::Configuration,
+    shutdown: Shutdown,
+) -> anyhow::Result<()> {
     let state = Arc::new(ApiState::initialise(config).await?);
 
-    let pub_sub_task = pub_sub::serve(Arc::clone(&state));
-    let http_task = http::serve(state);
+    let pub_sub_task = pub_sub::serve(Arc::clone(&state), shutdown.clone());
+    let http_task = http::serve(Arc::clone(&state), shutdown.clone());
 
-    let (_, _) = tokio::join!(pub_sub_task, http_task);
+    let (_, _) = tokio::join!(shutdown.drain(pub_sub_task), shutdown.drain(http_task));
+
+    state.state.close().await;
 
     Ok(())
 }
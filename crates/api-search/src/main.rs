@@ -1,5 +1,6 @@
 use anyhow::Result;
 use core_services::{
+    shutdown::Shutdown,
     state::config::Configuration,
     tracing::{
         config::{AppMetadata, LokiConfig},
@@ -28,9 +29,12 @@ async fn main() -> Result<()> {
         .try_with_sentry(&config.sentry_dsn)?
         .build();
 
-    if let Some(task) = std::mem::take(&mut telemetry.loki_task) {
-        tokio::spawn(task);
-    };
+    telemetry.spawn_loki();
+
+    let shutdown = Shutdown::listen(config.shutdown_timeout);
+
+    let result = api_search::serve(config, shutdown.clone()).await;
+    telemetry.shutdown(shutdown.timeout()).await;
 
-    api_search::serve(config).await
+    result
 }
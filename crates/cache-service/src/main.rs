@@ -6,9 +6,10 @@ use anyhow::{anyhow, Result};
 use async_nats::jetstream::{consumer, stream};
 use core_services::{
     cache::{
-        key::{CacheKey, CursorParams, Index},
+        key::{CacheKey, CategoryParams, CountParams, CursorParams, Index, TreeParams},
         PoolLike, PooledConnectionLike,
     },
+    shutdown::Shutdown,
     state::{
         config::{env_var, Configuration},
         events::{Entity, Event},
@@ -27,7 +28,11 @@ use futures_util::{
 use opentelemetry::global;
 use prost::Message;
 use sellershut_core::{
-    categories::{CacheCategoriesConnectionRequest, Category},
+    categories::{
+        cache_categories_request::Payload, CacheCategoriesConnectionRequest,
+        CacheCategoriesRequest, CacheCategoryRequest, Category, CategoryList,
+        UpsertCategoryRequest,
+    },
     common::pagination::{cursor::cursor_value::CursorType, Cursor},
 };
 use state::ApiState;
@@ -55,10 +60,9 @@ async fn main() -> Result<()> {
         .try_with_sentry(&config.sentry_dsn)?
         .build();
 
-    if let Some(task) = std::mem::take(&mut telemetry.loki_task) {
-        tokio::spawn(task);
-    };
+    telemetry.spawn_loki();
 
+    let shutdown = Shutdown::listen(config.shutdown_timeout);
     let state = ApiState::initialise(config).await?;
 
     let js = state.0.jetstream_context.clone();
@@ -73,7 +77,7 @@ async fn main() -> Result<()> {
                 .split(',')
                 .map(String::from)
                 .collect();
-            assert!(subjects.len() > 0);
+            assert!(!subjects.is_empty());
             let stream_max_bytes = env_var(&create_var("STREAM_MAX_BYTES"));
             let consumer = format!("CONSUMER_{}", env!("CARGO_PKG_NAME"));
             debug!(stream = stream, subjects = ?subjects, "configuring subjects");
@@ -102,12 +106,22 @@ async fn main() -> Result<()> {
 
     let consumers = try_join_all(services).await?.into_iter().map(|consumer| {
         let state = state.clone();
-        tokio::spawn(handle_message(consumer, state))
+        tokio::spawn(handle_message(consumer, state, shutdown.clone()))
     });
 
-    if let Err(e) = tokio::spawn(join_all(consumers)).await {
-        error!("{e}");
+    if let Some(results) = shutdown.drain(join_all(consumers)).await {
+        for result in results {
+            match result {
+                Ok(Err(e)) => error!("{e}"),
+                Err(e) => error!("{e}"),
+                Ok(Ok(_)) => {}
+            }
+        }
     }
+    info!("stopped consuming");
+
+    state.0.close().await;
+    telemetry.shutdown(shutdown.timeout()).await;
 
     Ok(())
 }
@@ -115,12 +129,20 @@ async fn main() -> Result<()> {
 async fn handle_message(
     consumer: consumer::Consumer<consumer::pull::Config>,
     state: ApiState,
+    shutdown: Shutdown,
 ) -> anyhow::Result<()> {
     // Get messages
     let mut messages = consumer.messages().await?;
     info!("consumer is ready to receive messages");
 
-    while let Some(Ok(message)) = messages.next().await {
+    let stopped = shutdown.triggered();
+    tokio::pin!(stopped);
+
+    // a message is seen through to its ack, unacked messages are redelivered to the next consumer
+    while let Some(Ok(message)) = tokio::select! {
+        message = messages.next() => message,
+        _ = &mut stopped => None,
+    } {
         debug!("message received");
         let subject = message.subject.to_string();
 
@@ -179,40 +201,52 @@ async fn process_event(
         Event::SetSingle(entity) => match entity {
             Entity::Categories => {
                 trace!(entity = ?entity, "decoding payload");
-                let category = Category::decode(payload)?;
+                match created_category(payload)? {
+                    Some(category) => {
+                        let cache_key = CacheKey::Category(CategoryParams {
+                            id: &category.id,
+                            locale: None,
+                        });
+                        write_to_cache(cache_key, &category.encode_to_vec(), state).await?;
+                    }
+                    None => error!("category is missing from create event"),
+                }
+            }
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
+        },
+        Event::SetBatch(entity) => match entity {
+            Entity::Categories => {
+                trace!(entity = ?entity, "decoding payload");
+                let list = CategoryList::decode(payload)?;
 
-                let cache_key = CacheKey::Category(&category.id);
-                write_to_cache(cache_key, payload, state).await?;
+                write_categories_to_cache(&list.categories, state).await?;
+                drop_localised(&category_ids(&list.categories), state).await?;
             }
-            _ => {}
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
         },
-        Event::SetBatch(_) => {}
         Event::UpdateSingle(entity) => match entity {
             Entity::Categories => {
                 trace!(entity = ?entity, "decoding payload");
                 let category = Category::decode(payload)?;
 
-                let cache_key = CacheKey::Category(&category.id);
+                let cache_key = CacheKey::Category(CategoryParams {
+                    id: &category.id,
+                    locale: None,
+                });
                 write_to_cache(cache_key, payload, state).await?;
+                drop_localised(&[category.id.as_str()], state).await?;
             }
-            _ => todo!(),
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
         },
         Event::UpdateBatch(entity) => match entity {
             Entity::Categories => {
                 trace!(entity = ?entity, "decoding payload");
-                let category = CacheCategoriesConnectionRequest::decode(payload)?;
+                let list = CategoryList::decode(payload)?;
 
-                if let Some((cursor, index)) = get_cursor_params(category.pagination) {
-                    let cache_key = CacheKey::Categories(CursorParams {
-                        cursor: cursor.as_deref(),
-                        index,
-                    });
-                    write_to_cache(cache_key, payload, state).await?;
-                } else {
-                    error!("pagination is missing from payload");
-                }
+                write_categories_to_cache(&list.categories, state).await?;
+                drop_localised(&category_ids(&list.categories), state).await?;
             }
-            _ => todo!(),
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
         },
         Event::DeleteSingle(entity) => {
             let mut cache = state.cache.get().await?;
@@ -221,23 +255,131 @@ async fn process_event(
                     trace!(entity = ?entity, "decoding payload");
                     let category = Category::decode(payload)?;
 
-                    let cache_key = CacheKey::Category(&category.id);
+                    let cache_key = CacheKey::Category(CategoryParams {
+                        id: &category.id,
+                        locale: None,
+                    });
                     cache.del::<_, ()>(cache_key).await?;
+                    drop_localised(&[category.id.as_str()], state).await?;
                 }
-                _ => todo!(),
+                entity => warn!(entity = ?entity, "entity is not cached, acking"),
             }
         }
         Event::DeleteBatch(entity) => {
-            let _cache = state.cache.get().await?;
+            let mut cache = state.cache.get().await?;
             match entity {
                 Entity::Categories => {
-                    unimplemented!()
+                    trace!(entity = ?entity, "decoding payload");
+                    let list = CategoryList::decode(payload)?;
+
+                    // keys can live on different nodes in a cluster, delete them one at a time
+                    for category in list.categories.iter() {
+                        let cache_key = CacheKey::Category(CategoryParams {
+                            id: &category.id,
+                            locale: None,
+                        });
+                        cache.del::<_, ()>(cache_key).await?;
+                    }
+                    drop_localised(&category_ids(&list.categories), state).await?;
                 }
-                _ => todo!(),
+                entity => warn!(entity = ?entity, "entity is not cached, acking"),
             }
         }
-        Event::CacheUpdateSingle(_) => {}
-        Event::CacheUpdateBatch(_) => {}
+        Event::CacheUpdateSingle(entity) => match entity {
+            Entity::Categories => {
+                trace!(entity = ?entity, "decoding payload");
+                let request = CacheCategoryRequest::decode(payload)?;
+
+                match request.category {
+                    Some(category) => {
+                        let cache_key = CacheKey::Category(CategoryParams {
+                            id: &category.id,
+                            locale: request.locale.as_deref(),
+                        });
+                        write_to_cache(cache_key, &category.encode_to_vec(), state).await?;
+                        if request.locale.is_some() {
+                            track_localised(cache_key, &[category.id.as_str()], state).await?;
+                        }
+                    }
+                    None => error!("category is missing from cache request"),
+                }
+            }
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
+        },
+        Event::CacheUpdateBatch(entity) => match entity {
+            Entity::Categories => {
+                trace!(entity = ?entity, "decoding payload");
+                let request = CacheCategoriesRequest::decode(payload)?;
+
+                match request.payload {
+                    Some(Payload::Tree(tree)) => {
+                        let params = tree.request.unwrap_or_default();
+                        let cache_key = CacheKey::CategoryTree(TreeParams {
+                            id: params.id.as_deref(),
+                            max_depth: params.max_depth,
+                        });
+                        let tree = tree.tree.unwrap_or_default().encode_to_vec();
+                        write_to_cache(cache_key, &tree, state).await?;
+                    }
+                    Some(Payload::Ancestors(ancestors)) => {
+                        let cache_key = CacheKey::CategoryAncestors(CategoryParams {
+                            id: &ancestors.id,
+                            locale: ancestors.locale.as_deref(),
+                        });
+                        let list = ancestors.ancestors.unwrap_or_default();
+                        write_to_cache(cache_key, &list.encode_to_vec(), state).await?;
+                        if ancestors.locale.is_some() {
+                            track_localised(cache_key, &category_ids(&list.categories), state)
+                                .await?;
+                        }
+                    }
+                    Some(Payload::Categories(connection)) => {
+                        if let Some((cursor, index)) =
+                            get_cursor_params(connection.pagination.clone())
+                        {
+                            let cache_key = CacheKey::Categories(CursorParams {
+                                parent_id: connection.parent_id.as_deref(),
+                                include_archived: connection.include_archived,
+                                cursor: cursor.as_deref(),
+                                index,
+                                locale: connection.locale.as_deref(),
+                            });
+                            write_to_cache(cache_key, &connection.encode_to_vec(), state).await?;
+                            if connection.locale.is_some() {
+                                track_localised(cache_key, &node_ids(&connection), state).await?;
+                            }
+                        }
+                    }
+                    Some(Payload::SubCategories(connection)) => {
+                        if let Some((cursor, index)) =
+                            get_cursor_params(connection.pagination.clone())
+                        {
+                            let cache_key = CacheKey::CategoriesSubCategory(CursorParams {
+                                parent_id: connection.parent_id.as_deref(),
+                                include_archived: connection.include_archived,
+                                cursor: cursor.as_deref(),
+                                index,
+                                locale: connection.locale.as_deref(),
+                            });
+                            write_to_cache(cache_key, &connection.encode_to_vec(), state).await?;
+                            if connection.locale.is_some() {
+                                track_localised(cache_key, &node_ids(&connection), state).await?;
+                            }
+                        }
+                    }
+                    Some(Payload::Count(count)) => {
+                        let filter = count.filter.clone().unwrap_or_default().encode_to_vec();
+                        let cache_key = CacheKey::CategoriesCount(CountParams {
+                            parent_id: count.parent_id.as_deref(),
+                            filter: &filter,
+                        });
+                        write_to_cache(cache_key, &count.encode_to_vec(), state).await?;
+                    }
+                    None => error!("payload is missing from cache request"),
+                }
+            }
+            entity => warn!(entity = ?entity, "entity is not cached, acking"),
+        },
         _ => {}
     }
 
@@ -282,6 +424,85 @@ fn get_cursor_params(pagination: Option<Cursor>) -> Option<(Option<String>, Inde
     }
 }
 
+/// The category a create event carries, creating a category publishes the request
+fn created_category(payload: &[u8]) -> Result<Option<Category>, prost::DecodeError> {
+    Ok(UpsertCategoryRequest::decode(payload)?.category)
+}
+
+fn category_ids(categories: &[Category]) -> Vec<&str> {
+    categories
+        .iter()
+        .map(|category| category.id.as_str())
+        .collect()
+}
+
+/// The categories on a cached page
+fn node_ids(connection: &CacheCategoriesConnectionRequest) -> Vec<&str> {
+    connection
+        .connection
+        .iter()
+        .flat_map(|connection| connection.edges.iter())
+        .filter_map(|edge| edge.node.as_ref())
+        .map(|category| category.id.as_str())
+        .collect()
+}
+
+/// Remembers that `cache_key` holds a localised copy of each category in `ids`, so it can be
+/// dropped when one of them changes
+#[instrument(err(Debug), skip(ids, state))]
+async fn track_localised(
+    cache_key: CacheKey<'_>,
+    ids: &[&str],
+    state: &ServiceState,
+) -> anyhow::Result<()> {
+    let mut cache = state.cache.get().await?;
+    let entry = cache_key.to_string();
+    for id in ids {
+        let key = CacheKey::LocalisedEntries(id);
+        cache.sadd::<_, _, ()>(key, &entry).await?;
+        // the entries expire on their own, so can the list of them
+        cache.pexpire::<_, ()>(key, 20000).await?;
+    }
+    Ok(())
+}
+
+/// Drops the localised entries the categories in `ids` appear in. Translations are not part of
+/// the events, these entries cannot be rewritten in place
+#[instrument(err(Debug), skip(ids, state))]
+async fn drop_localised(ids: &[&str], state: &ServiceState) -> anyhow::Result<()> {
+    let mut cache = state.cache.get().await?;
+    for id in ids {
+        let key = CacheKey::LocalisedEntries(id);
+        let entries: Vec<String> = cache.smembers(key).await?;
+        // keys can live on different nodes in a cluster, delete them one at a time
+        for entry in entries {
+            trace!(key = entry, "dropping localised entry");
+            cache.del::<_, ()>(entry).await?;
+        }
+        cache.del::<_, ()>(key).await?;
+    }
+    Ok(())
+}
+
+#[instrument(err(Debug), skip(categories, state))]
+async fn write_categories_to_cache(
+    categories: &[Category],
+    state: &ServiceState,
+) -> anyhow::Result<()> {
+    let mut cache = state.cache.get().await?;
+    for category in categories {
+        let cache_key = CacheKey::Category(CategoryParams {
+            id: &category.id,
+            locale: None,
+        });
+        trace!(key = ?cache_key, "writing to cache");
+        cache
+            .pset_ex::<_, _, ()>(cache_key, category.encode_to_vec(), 20000)
+            .await?;
+    }
+    Ok(())
+}
+
 #[instrument(err(Debug), skip(state, payload))]
 async fn write_to_cache(
     cache_key: CacheKey<'_>,
@@ -292,3 +513,43 @@ async fn write_to_cache(
     trace!(key = ?cache_key, "writing to cache");
     Ok(cache.pset_ex::<_, _, ()>(cache_key, payload, 20000).await?)
 }
+
+#[cfg(test)]
+mod tests {
+    use sellershut_core::categories::CategoryEvent;
+
+    use super::*;
+
+    #[test]
+    fn decode_create_events() {
+        let category = Category {
+            id: String::from("V1StGXR8_Z5jdHi6B-myT"),
+            name: String::from("Shoes"),
+            ..Default::default()
+        };
+        let payload = UpsertCategoryRequest {
+            category: Some(category.clone()),
+            event: CategoryEvent::Create.into(),
+        }
+        .encode_to_vec();
+
+        let created = created_category(&payload).unwrap().unwrap();
+        assert_eq!(created, category);
+
+        let cache_key = CacheKey::Category(CategoryParams {
+            id: &created.id,
+            locale: None,
+        });
+        assert_eq!(
+            cache_key.to_string(),
+            "categories:id=V1StGXR8_Z5jdHi6B-myT:locale=[NONE]"
+        );
+
+        let payload = UpsertCategoryRequest {
+            category: None,
+            event: CategoryEvent::Create.into(),
+        }
+        .encode_to_vec();
+        assert_eq!(created_category(&payload).unwrap(), None);
+    }
+}
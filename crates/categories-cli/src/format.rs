@@ -0,0 +1,256 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Separates the names in a path, as in the Google product taxonomy
+pub const SEPARATOR: &str = " > ";
+
+/// A file format taxonomies are read from and written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Comma separated values with a `path` column, and optional `external_id`, `description`
+    /// and `image_url` columns
+    Csv,
+    /// An array of objects with the same fields as the CSV columns
+    Json,
+    /// The Google product taxonomy, one `A > B > C` path per line. Lines may start with a
+    /// numeric ID, as in `123 - A > B > C`
+    Google,
+}
+
+impl Format {
+    /// Guesses the format from the extension of a file
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|value| value.to_str()) {
+            Some("csv") => Ok(Self::Csv),
+            Some("json") => Ok(Self::Json),
+            Some("txt") => Ok(Self::Google),
+            _ => Err(anyhow!(
+                "cannot tell the format of {}, pass --format",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// A category in a taxonomy file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    /// Names from the top-level category down
+    pub path: Vec<String>,
+    /// An identifier in another system, matched instead of the path when importing by ID
+    pub external_id: Option<String>,
+    /// Left as is when unset
+    pub description: Option<String>,
+    /// Left as is when unset
+    pub image_url: Option<String>,
+}
+
+impl Record {
+    /// The path as it is written, `A > B > C`
+    pub fn path(&self) -> String {
+        self.path.join(SEPARATOR)
+    }
+}
+
+/// A record as it appears in CSV and JSON files
+#[derive(Debug, Serialize, Deserialize)]
+struct Row {
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image_url: Option<String>,
+}
+
+impl TryFrom<Row> for Record {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Row) -> Result<Self> {
+        let non_empty = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+
+        Ok(Self {
+            path: parse_path(&value.path)?,
+            external_id: non_empty(value.external_id).map(|value| value.trim().to_string()),
+            description: non_empty(value.description),
+            image_url: non_empty(value.image_url),
+        })
+    }
+}
+
+impl From<&Record> for Row {
+    fn from(value: &Record) -> Self {
+        Self {
+            path: value.path(),
+            external_id: value.external_id.clone(),
+            description: value.description.clone(),
+            image_url: value.image_url.clone(),
+        }
+    }
+}
+
+/// Splits `A > B > C` into its names
+pub fn parse_path(value: &str) -> Result<Vec<String>> {
+    let path: Vec<String> = value
+        .split('>')
+        .map(|name| name.trim().to_string())
+        .collect();
+
+    if path.iter().any(String::is_empty) {
+        bail!("`{value}` is not a valid path, names cannot be empty");
+    }
+
+    Ok(path)
+}
+
+/// Reads the records of a taxonomy file
+pub fn read(format: Format, reader: impl Read) -> Result<Vec<Record>> {
+    match format {
+        Format::Csv => csv::Reader::from_reader(reader)
+            .deserialize::<Row>()
+            .enumerate()
+            // the header is the first line
+            .map(|(index, row)| {
+                row.map_err(anyhow::Error::from)
+                    .and_then(Record::try_from)
+                    .with_context(|| format!("line {}", index + 2))
+            })
+            .collect(),
+        Format::Json => serde_json::from_reader::<_, Vec<Row>>(reader)?
+            .into_iter()
+            .map(Record::try_from)
+            .collect(),
+        Format::Google => {
+            let mut records = vec![];
+            for (index, line) in BufReader::new(reader).lines().enumerate() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let (external_id, path) = match line.split_once(" - ") {
+                    Some((id, path)) if id.chars().all(|value| value.is_ascii_digit()) => {
+                        (Some(id.to_string()), path)
+                    }
+                    _ => (None, line),
+                };
+
+                records.push(Record {
+                    path: parse_path(path).with_context(|| format!("line {}", index + 1))?,
+                    external_id,
+                    ..Default::default()
+                });
+            }
+            Ok(records)
+        }
+    }
+}
+
+/// Writes records to a taxonomy file. The Google format only keeps paths, and numeric IDs
+pub fn write(format: Format, records: &[Record], mut writer: impl Write) -> Result<()> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            // optional columns are always written so every row has the same number of fields
+            writer.write_record(["path", "external_id", "description", "image_url"])?;
+            for record in records {
+                writer.write_record([
+                    record.path(),
+                    record.external_id.clone().unwrap_or_default(),
+                    record.description.clone().unwrap_or_default(),
+                    record.image_url.clone().unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        Format::Json => {
+            let rows: Vec<Row> = records.iter().map(Row::from).collect();
+            serde_json::to_writer_pretty(&mut writer, &rows)?;
+            writeln!(writer)?;
+        }
+        Format::Google => {
+            for record in records {
+                match record.external_id {
+                    Some(ref id) if id.chars().all(|value| value.is_ascii_digit()) => {
+                        writeln!(writer, "{id} - {}", record.path())?
+                    }
+                    _ => writeln!(writer, "{}", record.path())?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, external_id: Option<&str>) -> Record {
+        Record {
+            path: parse_path(path).unwrap(),
+            external_id: external_id.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_google_taxonomy() {
+        let file = "# Google_Product_Taxonomy_Version: 2021-09-21
+1 - Animals & Pet Supplies
+3237 - Animals & Pet Supplies > Live Animals
+
+Apparel & Accessories > T - Shirts
+";
+        let records = read(Format::Google, file.as_bytes()).unwrap();
+
+        assert_eq!(
+            records,
+            vec![
+                record("Animals & Pet Supplies", Some("1")),
+                record("Animals & Pet Supplies > Live Animals", Some("3237")),
+                record("Apparel & Accessories > T - Shirts", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_empty_names() {
+        assert!(read(Format::Google, "Apparel >  > Shoes".as_bytes()).is_err());
+        assert!(read(Format::Csv, "path\nApparel >\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = vec![
+            Record {
+                description: Some(String::from("Things to wear, with \"quotes\", and commas")),
+                image_url: Some(String::from("https://example.com/apparel.png")),
+                ..record("Apparel", Some("166"))
+            },
+            record("Apparel > Shoes", None),
+        ];
+
+        for format in [Format::Csv, Format::Json] {
+            let mut file = vec![];
+            write(format, &records, &mut file).unwrap();
+            assert_eq!(read(format, file.as_slice()).unwrap(), records);
+        }
+
+        let mut file = vec![];
+        write(Format::Google, &records, &mut file).unwrap();
+        assert_eq!(
+            String::from_utf8(file).unwrap(),
+            "166 - Apparel\nApparel > Shoes\n"
+        );
+    }
+}
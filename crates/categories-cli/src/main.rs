@@ -0,0 +1,142 @@
+mod format;
+mod plan;
+mod tree;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use format::Format;
+use plan::{Key, Plan};
+use sellershut_core::categories::{
+    mutate_categories_client::MutateCategoriesClient,
+    query_categories_client::QueryCategoriesClient,
+};
+use tonic::transport::Endpoint;
+
+/// Whole taxonomies are sent in a single message
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Imports and exports category taxonomies
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Address of the categories gRPC API
+    #[arg(
+        long,
+        env = "CATEGORIES_ENDPOINT",
+        default_value = "http://localhost:1304"
+    )]
+    endpoint: String,
+    /// API key of the categories service, imports make changes and need one
+    #[arg(long, env = "CATEGORIES_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Creates, renames and moves categories to match a taxonomy file. Importing the same file
+    /// again changes nothing
+    Import(ImportArgs),
+    /// Writes every live category to a taxonomy file
+    Export(ExportArgs),
+}
+
+#[derive(Debug, Args)]
+struct ImportArgs {
+    /// The taxonomy file
+    file: PathBuf,
+    /// The format of the file, guessed from its extension when unset
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// How records are matched to existing categories
+    #[arg(long, value_enum, default_value_t = Key::Path)]
+    key: Key,
+    /// Archive categories that are not in the file
+    #[arg(long)]
+    prune: bool,
+    /// Print the changes without making them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// The file to write, standard output when unset
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// The format to write, guessed from the extension of the output file when unset
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let channel = Endpoint::from_shared(cli.endpoint.clone())?
+        .connect()
+        .await
+        .with_context(|| format!("cannot connect to {}", cli.endpoint))?;
+
+    let mut query =
+        QueryCategoriesClient::new(channel.clone()).max_decoding_message_size(MAX_MESSAGE_SIZE);
+
+    match cli.command {
+        Command::Import(args) => {
+            let format = match args.format {
+                Some(format) => format,
+                None => Format::from_path(&args.file)?,
+            };
+            let file = File::open(&args.file)
+                .with_context(|| format!("cannot open {}", args.file.display()))?;
+            let records = format::read(format, file)
+                .with_context(|| format!("cannot read {}", args.file.display()))?;
+
+            let existing = tree::fetch(&mut query).await?;
+            let plan = Plan::new(&existing, records, args.key, args.prune)?;
+
+            println!("{plan}");
+            if args.dry_run || plan.is_empty() {
+                return Ok(());
+            }
+
+            let api_key = cli
+                .api_key
+                .map(|key| key.parse())
+                .transpose()
+                .context("API keys are made of visible ASCII characters")?;
+            let mut mutate =
+                MutateCategoriesClient::with_interceptor(channel, tree::ApiKey(api_key))
+                    .max_encoding_message_size(MAX_MESSAGE_SIZE)
+                    .max_decoding_message_size(MAX_MESSAGE_SIZE);
+            tree::apply(&mut mutate, plan).await?;
+        }
+        Command::Export(args) => {
+            let format = match (args.format, args.output.as_deref()) {
+                (Some(format), _) => format,
+                (None, Some(path)) => Format::from_path(path)?,
+                (None, None) => Format::Csv,
+            };
+
+            let records = tree::to_records(tree::fetch(&mut query).await?);
+
+            let writer: Box<dyn Write> = match args.output {
+                Some(ref path) => Box::new(BufWriter::new(
+                    File::create(path)
+                        .with_context(|| format!("cannot create {}", path.display()))?,
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            format::write(format, &records, writer)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,395 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use sellershut_core::categories::Category;
+
+use crate::format::{Record, SEPARATOR};
+
+/// How records are matched to existing categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Key {
+    /// Records are the same category when their paths are the same. A changed path adds a new
+    /// category
+    Path,
+    /// Records are the same category when their external IDs are the same, so categories can be
+    /// renamed and moved. Records without an ID, and categories that have none yet, are matched
+    /// by path
+    ExternalId,
+}
+
+/// An existing category and where it sits in the tree
+#[derive(Debug, Clone)]
+pub struct Existing {
+    pub category: Category,
+    pub path: Vec<String>,
+}
+
+/// An existing category that the import changes
+#[derive(Debug, Clone)]
+pub struct Update {
+    /// The category as it is
+    pub category: Category,
+    /// Where it sits now
+    pub from: Vec<String>,
+    /// What it becomes
+    pub record: Record,
+    pub renamed: bool,
+    pub moved: bool,
+    /// The description, image or external ID changed
+    pub edited: bool,
+}
+
+impl Update {
+    /// The category to send, unset fields in the record are left as they are
+    pub fn category(&self, parent_id: Option<String>) -> Category {
+        let record = self.record.clone();
+        let category = self.category.clone();
+
+        Category {
+            name: record.path.last().cloned().unwrap_or(category.name),
+            parent_id,
+            // an empty slug keeps the current one
+            slug: String::new(),
+            description: record.description.or(category.description),
+            image_url: record.image_url.or(category.image_url),
+            external_id: record.external_id.or(category.external_id),
+            ..category
+        }
+    }
+}
+
+/// The changes needed to bring the existing categories in line with an import
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// New categories, parents before their children
+    pub added: Vec<Record>,
+    /// Changed categories, parents before their children
+    pub updated: Vec<Update>,
+    /// Categories that are not in the import, only filled in when pruning
+    pub removed: Vec<Existing>,
+    /// Where every record ends up, an existing category or [None] when it is added
+    pub matched: HashMap<Vec<String>, Option<String>>,
+}
+
+impl Plan {
+    /// Compares `records` with the `existing` categories. Ancestors that are missing from the
+    /// records are added to them, so `A > B` alone is enough to create `A`
+    pub fn new(existing: &[Existing], records: Vec<Record>, key: Key, prune: bool) -> Result<Self> {
+        let records = complete(records)?;
+
+        let by_path: HashMap<&[String], &Existing> = existing
+            .iter()
+            .map(|value| (value.path.as_slice(), value))
+            .collect();
+        let by_external_id: HashMap<&str, &Existing> = existing
+            .iter()
+            .filter_map(|value| {
+                value
+                    .category
+                    .external_id
+                    .as_deref()
+                    .map(|external_id| (external_id, value))
+            })
+            .collect();
+
+        let mut plan = Self::default();
+        let mut used = HashSet::new();
+
+        for record in records {
+            let found = match (key, record.external_id.as_deref()) {
+                (Key::ExternalId, Some(external_id)) => {
+                    by_external_id.get(external_id).copied().or_else(|| {
+                        // a category that has an ID of its own is a different category
+                        by_path
+                            .get(record.path.as_slice())
+                            .copied()
+                            .filter(|value| value.category.external_id.is_none())
+                    })
+                }
+                _ => by_path.get(record.path.as_slice()).copied(),
+            }
+            .filter(|value| !used.contains(&value.category.id));
+
+            let Some(current) = found else {
+                plan.matched.insert(record.path.clone(), None);
+                plan.added.push(record);
+                continue;
+            };
+            used.insert(current.category.id.clone());
+
+            let parent_id = record
+                .path
+                .split_last()
+                .and_then(|(_, parent)| (!parent.is_empty()).then_some(parent))
+                .map(|parent| plan.matched.get(parent).cloned().flatten());
+
+            let changed = |value: &Option<String>, current: &Option<String>| {
+                value.is_some() && value != current
+            };
+            let update = Update {
+                renamed: record.path.last() != current.path.last(),
+                moved: match parent_id {
+                    // a new parent is a move too
+                    Some(parent_id) => {
+                        parent_id.is_none() || parent_id != current.category.parent_id
+                    }
+                    None => current.category.parent_id.is_some(),
+                },
+                edited: changed(&record.description, &current.category.description)
+                    || changed(&record.image_url, &current.category.image_url)
+                    || changed(&record.external_id, &current.category.external_id),
+                category: current.category.clone(),
+                from: current.path.clone(),
+                record,
+            };
+
+            plan.matched.insert(
+                update.record.path.clone(),
+                Some(current.category.id.clone()),
+            );
+            if update.renamed || update.moved || update.edited {
+                plan.updated.push(update);
+            }
+        }
+
+        if prune {
+            plan.removed = existing
+                .iter()
+                .filter(|value| !used.contains(&value.category.id))
+                .cloned()
+                .collect();
+        }
+
+        Ok(plan)
+    }
+
+    /// Whether the import changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Adds missing ancestors and orders records so parents come before their children. Paths and
+/// external IDs can only be listed once
+fn complete(records: Vec<Record>) -> Result<Vec<Record>> {
+    let mut paths = HashSet::new();
+    let mut external_ids = HashSet::new();
+
+    for record in &records {
+        if !paths.insert(record.path.clone()) {
+            bail!("`{}` is listed more than once", record.path());
+        }
+        if let Some(ref external_id) = record.external_id {
+            if !external_ids.insert(external_id.clone()) {
+                bail!("external ID `{external_id}` is listed more than once");
+            }
+        }
+    }
+
+    let mut completed = Vec::with_capacity(records.len());
+    for record in records {
+        for depth in 1..record.path.len() {
+            let path = &record.path[..depth];
+            if paths.insert(path.to_vec()) {
+                completed.push(Record {
+                    path: path.to_vec(),
+                    ..Default::default()
+                });
+            }
+        }
+        completed.push(record);
+    }
+
+    // stable, records keep the order of the file within a level
+    completed.sort_by_key(|record| record.path.len());
+
+    Ok(completed)
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = |value: &[String]| value.join(SEPARATOR);
+
+        for record in &self.added {
+            writeln!(f, "added    {}", record.path())?;
+        }
+        for update in &self.updated {
+            let label = match (update.renamed, update.moved) {
+                (_, true) => "moved",
+                (true, false) => "renamed",
+                (false, false) => "updated",
+            };
+            if update.from == update.record.path {
+                writeln!(f, "{label:<8} {}", path(&update.from))?;
+            } else {
+                writeln!(
+                    f,
+                    "{label:<8} {} -> {}",
+                    path(&update.from),
+                    update.record.path()
+                )?;
+            }
+        }
+        for existing in &self.removed {
+            writeln!(f, "removed  {}", path(&existing.path))?;
+        }
+
+        write!(
+            f,
+            "{} added, {} renamed, {} moved, {} updated, {} removed",
+            self.added.len(),
+            self.updated
+                .iter()
+                .filter(|value| value.renamed && !value.moved)
+                .count(),
+            self.updated.iter().filter(|value| value.moved).count(),
+            self.updated
+                .iter()
+                .filter(|value| !value.renamed && !value.moved)
+                .count(),
+            self.removed.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::parse_path;
+
+    fn record(path: &str, external_id: Option<&str>) -> Record {
+        Record {
+            path: parse_path(path).unwrap(),
+            external_id: external_id.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    fn existing(
+        id: &str,
+        path: &str,
+        parent_id: Option<&str>,
+        external_id: Option<&str>,
+    ) -> Existing {
+        let path = parse_path(path).unwrap();
+        Existing {
+            category: Category {
+                id: id.to_string(),
+                name: path.last().cloned().unwrap(),
+                parent_id: parent_id.map(String::from),
+                external_id: external_id.map(String::from),
+                ..Default::default()
+            },
+            path,
+        }
+    }
+
+    fn tree() -> Vec<Existing> {
+        vec![
+            existing("apparel", "Apparel", None, Some("1")),
+            existing("shoes", "Apparel > Shoes", Some("apparel"), Some("2")),
+            existing("bags", "Apparel > Bags", Some("apparel"), None),
+        ]
+    }
+
+    #[test]
+    fn reimport_changes_nothing() {
+        let records = vec![
+            record("Apparel", Some("1")),
+            record("Apparel > Shoes", Some("2")),
+            record("Apparel > Bags", None),
+        ];
+
+        for key in [Key::Path, Key::ExternalId] {
+            let plan = Plan::new(&tree(), records.clone(), key, true).unwrap();
+            assert!(plan.is_empty(), "{plan}");
+        }
+    }
+
+    #[test]
+    fn add_missing_ancestors() {
+        let plan = Plan::new(
+            &[],
+            vec![record("Home > Kitchen > Knives", None)],
+            Key::Path,
+            false,
+        )
+        .unwrap();
+
+        let added: Vec<_> = plan.added.iter().map(Record::path).collect();
+        assert_eq!(
+            added,
+            vec!["Home", "Home > Kitchen", "Home > Kitchen > Knives"]
+        );
+    }
+
+    #[test]
+    fn reject_duplicates() {
+        let records = vec![record("Apparel", None), record("Apparel", None)];
+        assert!(Plan::new(&[], records, Key::Path, false).is_err());
+
+        let records = vec![record("Apparel", Some("1")), record("Home", Some("1"))];
+        assert!(Plan::new(&[], records, Key::Path, false).is_err());
+    }
+
+    #[test]
+    fn rename_and_move_by_external_id() {
+        let records = vec![
+            record("Clothing", Some("1")),
+            record("Footwear", None),
+            record("Footwear > Trainers", Some("2")),
+        ];
+
+        let plan = Plan::new(&tree(), records.clone(), Key::ExternalId, true).unwrap();
+
+        let added: Vec<_> = plan.added.iter().map(Record::path).collect();
+        assert_eq!(added, vec!["Footwear"]);
+
+        let apparel = &plan.updated[0];
+        assert_eq!(apparel.category.id, "apparel");
+        assert!(apparel.renamed && !apparel.moved);
+
+        let shoes = &plan.updated[1];
+        assert_eq!(shoes.category.id, "shoes");
+        assert!(shoes.renamed && shoes.moved);
+
+        let removed: Vec<_> = plan
+            .removed
+            .iter()
+            .map(|value| value.path.join(SEPARATOR))
+            .collect();
+        assert_eq!(removed, vec!["Apparel > Bags"]);
+
+        // by path, the renamed categories are new ones
+        let plan = Plan::new(&tree(), records, Key::Path, true).unwrap();
+        assert_eq!(plan.added.len(), 3);
+        assert!(plan.updated.is_empty());
+        assert_eq!(plan.removed.len(), 3);
+    }
+
+    #[test]
+    fn keep_categories_unless_pruning() {
+        let plan = Plan::new(&tree(), vec![record("Apparel", None)], Key::Path, false).unwrap();
+        assert!(plan.is_empty());
+
+        let plan = Plan::new(&tree(), vec![record("Apparel", None)], Key::Path, true).unwrap();
+        assert_eq!(plan.removed.len(), 2);
+    }
+
+    #[test]
+    fn fill_in_external_ids() {
+        let records = vec![record("Apparel > Bags", Some("3"))];
+
+        let plan = Plan::new(&tree(), records, Key::ExternalId, false).unwrap();
+        let bags = &plan.updated[0];
+        assert!(bags.edited && !bags.renamed && !bags.moved);
+        assert_eq!(
+            bags.category(Some(String::from("apparel"))).external_id,
+            Some(String::from("3"))
+        );
+    }
+}
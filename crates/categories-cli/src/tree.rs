@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use sellershut_core::categories::{
+    mutate_categories_client::MutateCategoriesClient,
+    query_categories_client::QueryCategoriesClient, Category, CategoryEvent, CategoryTreeNode,
+    DeleteCategoriesRequest, GetCategoryTreeRequest, UpsertCategoriesRequest,
+};
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::Channel,
+};
+
+use crate::{
+    format::{Record, SEPARATOR},
+    plan::{Existing, Plan},
+};
+
+/// Header the categories service reads API keys from
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Signs requests with the API key the categories service knows this tool by
+#[derive(Debug, Clone)]
+pub struct ApiKey(pub Option<MetadataValue<Ascii>>);
+
+impl Interceptor for ApiKey {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(ref key) = self.0 {
+            request.metadata_mut().insert(API_KEY_HEADER, key.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Reads every live category, parents before their children
+pub async fn fetch(client: &mut QueryCategoriesClient<Channel>) -> Result<Vec<Existing>> {
+    let tree = client
+        .category_tree(GetCategoryTreeRequest {
+            id: None,
+            max_depth: None,
+        })
+        .await?
+        .into_inner();
+
+    let mut existing = vec![];
+    let mut level: Vec<(Vec<String>, CategoryTreeNode)> =
+        tree.roots.into_iter().map(|node| (vec![], node)).collect();
+
+    // level by level, so the order matches the order records are imported in
+    while !level.is_empty() {
+        let mut next = vec![];
+        for (parent, node) in level {
+            let category = node
+                .category
+                .ok_or_else(|| anyhow!("tree node is missing its category"))?;
+
+            let mut path = parent;
+            path.push(category.name.clone());
+
+            next.extend(node.children.into_iter().map(|child| (path.clone(), child)));
+            existing.push(Existing { category, path });
+        }
+        level = next;
+    }
+
+    Ok(existing)
+}
+
+/// The records of a full-tree export
+pub fn to_records(existing: Vec<Existing>) -> Vec<Record> {
+    let mut records: Vec<Record> = existing
+        .into_iter()
+        .map(|value| Record {
+            path: value.path,
+            external_id: value.category.external_id,
+            description: value.category.description,
+            image_url: value.category.image_url,
+        })
+        .collect();
+
+    // every parent sorts before its children
+    records.sort_by(|a, b| a.path.cmp(&b.path));
+    records
+}
+
+/// Carries out a plan. New categories are created a level at a time, then existing ones are
+/// renamed and moved in a single transaction, then removed ones are archived with their
+/// descendants
+pub async fn apply(
+    client: &mut MutateCategoriesClient<InterceptedService<Channel, ApiKey>>,
+    plan: Plan,
+) -> Result<()> {
+    let mut ids: HashMap<Vec<String>, String> = plan
+        .matched
+        .into_iter()
+        .filter_map(|(path, id)| id.map(|id| (path, id)))
+        .collect();
+
+    let parent_id = |ids: &HashMap<Vec<String>, String>, path: &[String]| -> Result<_> {
+        match path.split_last() {
+            Some((_, parent)) if !parent.is_empty() => ids
+                .get(parent)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| anyhow!("no category to add `{}` under", path.join(SEPARATOR))),
+            _ => Ok(None),
+        }
+    };
+
+    let mut added = plan.added.into_iter().peekable();
+    while let Some(first) = added.peek() {
+        let depth = first.path.len();
+        let mut level = vec![];
+        while let Some(record) = added.next_if(|record| record.path.len() == depth) {
+            level.push(record);
+        }
+
+        let categories = level
+            .iter()
+            .map(|record| {
+                Ok(Category {
+                    name: record.path.last().cloned().unwrap_or_default(),
+                    parent_id: parent_id(&ids, &record.path)?,
+                    external_id: record.external_id.clone(),
+                    description: record.description.clone(),
+                    image_url: record.image_url.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let created = client
+            .create_many(UpsertCategoriesRequest {
+                categories,
+                event: CategoryEvent::Create.into(),
+            })
+            .await?
+            .into_inner();
+
+        for (record, category) in level.into_iter().zip(created.categories) {
+            ids.insert(record.path, category.id);
+        }
+    }
+
+    if !plan.updated.is_empty() {
+        let categories = plan
+            .updated
+            .iter()
+            .map(|update| Ok(update.category(parent_id(&ids, &update.record.path)?)))
+            .collect::<Result<_>>()?;
+
+        client
+            .update_many(UpsertCategoriesRequest {
+                categories,
+                event: CategoryEvent::Update.into(),
+            })
+            .await?;
+    }
+
+    // descendants are archived with their ancestors
+    let removed: HashSet<&str> = plan
+        .removed
+        .iter()
+        .map(|value| value.category.id.as_str())
+        .collect();
+    let ids: Vec<String> = plan
+        .removed
+        .iter()
+        .filter(|value| {
+            value
+                .category
+                .parent_id
+                .as_deref()
+                .is_none_or(|parent_id| !removed.contains(parent_id))
+        })
+        .map(|value| value.category.id.clone())
+        .collect();
+
+    if !ids.is_empty() {
+        client
+            .delete_many(DeleteCategoriesRequest {
+                ids,
+                event: CategoryEvent::Delete.into(),
+            })
+            .await?;
+    }
+
+    Ok(())
+}
@@ -7,29 +7,100 @@ use redis::ToRedisArgs;
 pub enum CacheKey<'a> {
     Categories(CursorParams<'a>),
     CategoriesSubCategory(CursorParams<'a>),
-    Category(&'a str),
+    Category(CategoryParams<'a>),
+    CategoryTree(TreeParams<'a>),
+    CategoryAncestors(CategoryParams<'a>),
+    CategoriesCount(CountParams<'a>),
+    /// The localised entries a category appears in, by its id. They are dropped when it changes
+    LocalisedEntries(&'a str),
+    /// A GraphQL operation, by its SHA-256 hash
+    PersistedQuery(&'a str),
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct CategoryParams<'a> {
+    pub id: &'a str,
+    pub locale: Option<&'a str>,
+}
+
+impl Display for CategoryParams<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "id={}:locale={}",
+            self.id,
+            self.locale.unwrap_or("[NONE]")
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TreeParams<'a> {
+    pub id: Option<&'a str>,
+    pub max_depth: Option<i32>,
+}
+
+impl Display for TreeParams<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "id={}:depth={}",
+            self.id.unwrap_or("[NONE]"),
+            match self.max_depth {
+                Some(v) => v.to_string(),
+                None => "[NONE]".to_string(),
+            }
+        )
+    }
+}
+
+/// Identifies a page of a listing. `parent_id` is the category whose children are listed, pages
+/// of different parents are cached apart
 #[derive(Clone, Copy, Debug)]
 pub struct CursorParams<'a> {
+    pub parent_id: Option<&'a str>,
+    pub include_archived: bool,
     pub cursor: Option<&'a str>,
     pub index: Index,
+    pub locale: Option<&'a str>,
 }
 
 impl Display for CursorParams<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "cursor={}:index={}",
+            "parent={}:archived={}:cursor={}:index={}:locale={}",
+            self.parent_id.unwrap_or("[NONE]"),
+            self.include_archived,
             self.cursor.unwrap_or("[NONE]"),
             match self.index {
                 Index::First(v) => format!("first:{v}"),
                 Index::Last(v) => format!("last:{v}"),
-            }
+            },
+            self.locale.unwrap_or("[NONE]")
         )
     }
 }
 
+/// Identifies a counted listing. `filter` is the encoded filter the listing was narrowed down with
+#[derive(Clone, Copy, Debug)]
+pub struct CountParams<'a> {
+    pub parent_id: Option<&'a str>,
+    pub filter: &'a [u8],
+}
+
+impl Display for CountParams<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "parent={}:filter=", self.parent_id.unwrap_or("[NONE]"))?;
+        if self.filter.is_empty() {
+            return write!(f, "[NONE]");
+        }
+        self.filter
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Index {
     First(i32),
@@ -45,7 +116,12 @@ impl Display for CacheKey<'_> {
                 CacheKey::Categories(params) => format!("categories:all:{params}"),
                 CacheKey::CategoriesSubCategory(params) =>
                     format!("categories:subcategories:{params}"),
-                CacheKey::Category(id) => format!("categories:id={id}"),
+                CacheKey::Category(params) => format!("categories:{params}"),
+                CacheKey::CategoryTree(params) => format!("categories:tree:{params}"),
+                CacheKey::CategoryAncestors(params) => format!("categories:ancestors:{params}"),
+                CacheKey::CategoriesCount(params) => format!("categories:count:{params}"),
+                CacheKey::LocalisedEntries(id) => format!("categories:localised:id={id}"),
+                CacheKey::PersistedQuery(hash) => format!("categories:persisted-query:{hash}"),
             }
         )
     }
@@ -4,12 +4,15 @@ mod cluster;
 
 pub mod key;
 
-use bb8::{Pool, RunError};
+use bb8::Pool;
 use bb8_redis::RedisConnectionManager;
 pub use cluster::RedisClusterConnectionManager;
 
+pub use bb8::RunError;
+pub use redis::RedisError;
+
 use async_trait::async_trait;
-use redis::{FromRedisValue, RedisError, RedisResult, ToRedisArgs};
+use redis::{FromRedisValue, RedisResult, ToRedisArgs};
 
 use crate::{state::config::env_var, ServiceError};
 
@@ -85,6 +88,16 @@ pub trait PooledConnectionLike {
         self.query_async(redis::Cmd::lrem(key, count, value)).await
     }
 
+    #[cfg(feature = "cache-write")]
+    async fn pexpire<K: ToRedisArgs + Send, T: FromRedisValue>(
+        &mut self,
+        key: K,
+        milliseconds: i64,
+    ) -> RedisResult<T> {
+        self.query_async(redis::Cmd::pexpire(key, milliseconds))
+            .await
+    }
+
     #[cfg(feature = "cache-write")]
     async fn pset_ex<K: ToRedisArgs + Send, V: ToRedisArgs + Send, T: FromRedisValue>(
         &mut self,
@@ -105,6 +118,15 @@ pub trait PooledConnectionLike {
         self.query_async(redis::Cmd::rpush(key, value)).await
     }
 
+    #[cfg(feature = "cache-write")]
+    async fn sadd<K: ToRedisArgs + Send, M: ToRedisArgs + Send, T: FromRedisValue>(
+        &mut self,
+        key: K,
+        member: M,
+    ) -> RedisResult<T> {
+        self.query_async(redis::Cmd::sadd(key, member)).await
+    }
+
     #[cfg(feature = "cache-write")]
     async fn set<K: ToRedisArgs + Send, V: ToRedisArgs + Send, T: FromRedisValue>(
         &mut self,
@@ -114,6 +136,13 @@ pub trait PooledConnectionLike {
         self.query_async(redis::Cmd::set(key, value)).await
     }
 
+    async fn smembers<K: ToRedisArgs + Send, T: FromRedisValue>(
+        &mut self,
+        key: K,
+    ) -> RedisResult<T> {
+        self.query_async(redis::Cmd::smembers(key)).await
+    }
+
     #[cfg(feature = "cache-write")]
     async fn zadd<
         K: ToRedisArgs + Send,
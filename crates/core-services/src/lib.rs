@@ -16,6 +16,7 @@ pub mod tracing;
     feature = "api",
     feature = "postgres",
     feature = "cache",
+    feature = "health",
     feature = "nats",
     feature = "tracing",
 ))]
@@ -25,6 +26,7 @@ pub mod tracing;
         feature = "api",
         feature = "postgres",
         feature = "cache",
+        feature = "health",
         feature = "nats",
         feature = "tracing",
     )))
@@ -36,6 +38,16 @@ pub mod state;
 #[cfg(feature = "cache")]
 pub mod cache;
 
+/// Graceful shutdown
+#[cfg_attr(docsrs, doc(cfg(feature = "shutdown")))]
+#[cfg(feature = "shutdown")]
+pub mod shutdown;
+
+/// Keyset pagination
+#[cfg_attr(docsrs, doc(cfg(feature = "pagination")))]
+#[cfg(feature = "pagination")]
+pub mod pagination;
+
 use thiserror::Error;
 
 /// Errors returned by services
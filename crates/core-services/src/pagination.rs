@@ -0,0 +1,321 @@
+use sellershut_core::common::pagination::{
+    cursor::{cursor_value::CursorType, Index},
+    query_count, Cursor, CursorBuilder, CursorError, CursorSigner, PageInfo,
+};
+use sqlx::{postgres::PgRow, FromRow, PgPool, Postgres, QueryBuilder};
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use tracing::{debug_span, Instrument};
+
+/// Errors returned while paginating
+#[derive(Error, Debug)]
+pub enum PaginationError {
+    /// Neither `first` nor `last` was given
+    #[error("missing pagination index")]
+    MissingIndex,
+    /// The cursor cannot be read, was tampered with, or was issued for another query
+    #[error(transparent)]
+    InvalidCursor(#[from] CursorError),
+    /// A row has no value for the column it is sorted by
+    #[error("rows cannot be sorted by {0}")]
+    UnknownSortKey(&'static str),
+    /// The sort key of a row cannot be written to a cursor
+    #[error(transparent)]
+    Format(#[from] time::error::Format),
+    /// Postgres error
+    #[error(transparent)]
+    Postgres(#[from] sqlx::Error),
+}
+
+/// The kind of value a sort key holds, used to read it back from a cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    /// A timestamp, written to cursors in RFC 3339
+    Time,
+    /// Text, written to cursors as is
+    Text,
+}
+
+/// The value of a sort key in a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyValue {
+    /// A timestamp
+    Time(OffsetDateTime),
+    /// Text
+    Text(String),
+}
+
+impl KeyValue {
+    fn encode(&self) -> Result<String, PaginationError> {
+        match self {
+            KeyValue::Time(value) => Ok(value.to_offset(UtcOffset::UTC).format(&Rfc3339)?),
+            KeyValue::Text(value) => Ok(value.clone()),
+        }
+    }
+
+    fn decode(kind: KeyKind, value: &str) -> Result<Self, PaginationError> {
+        match kind {
+            KeyKind::Time => OffsetDateTime::parse(value, &Rfc3339)
+                .map(KeyValue::Time)
+                .map_err(|_| PaginationError::InvalidCursor(CursorError::Malformed)),
+            KeyKind::Text => Ok(KeyValue::Text(value.to_string())),
+        }
+    }
+
+    fn push_bind(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            KeyValue::Time(value) => query.push_bind(*value),
+            KeyValue::Text(value) => query.push_bind(value.clone()),
+        };
+    }
+}
+
+/// A column results are ordered by. Ties are broken by the `id` column, in the same direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    /// The column name
+    pub column: &'static str,
+    /// What the column holds
+    pub kind: KeyKind,
+    /// Whether results are ordered from the highest value
+    pub descending: bool,
+}
+
+/// A row that can be paginated over
+pub trait Keyed {
+    /// The unique ID of the row, stored in the `id` column
+    fn id(&self) -> &str;
+
+    /// The value of `column`, [None] if the row cannot be sorted by it
+    fn key(&self, column: &str) -> Option<KeyValue>;
+}
+
+type Filter<'f> = Box<dyn Fn(&mut QueryBuilder<'_, Postgres>) + Send + Sync + 'f>;
+
+/// Keyset pagination over a query. Pages are read by comparing the sort key and ID of the rows
+/// to the ones stored in the cursor, rather than by skipping rows
+pub struct Keyset<'f> {
+    base: String,
+    sort: SortKey,
+    filter: Option<Filter<'f>>,
+    signer: &'f CursorSigner,
+    scope: Vec<u8>,
+}
+
+impl std::fmt::Debug for Keyset<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyset")
+            .field("base", &self.base)
+            .field("sort", &self.sort)
+            .field("filter", &self.filter.is_some())
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A node and its cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge<T> {
+    /// The cursor pointing at the node
+    pub cursor: String,
+    /// The node
+    pub node: T,
+}
+
+/// A page of results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection<T> {
+    /// The nodes on the page, in order
+    pub edges: Vec<Edge<T>>,
+    /// Information about the surrounding pages
+    pub page_info: PageInfo,
+}
+
+impl<'f> Keyset<'f> {
+    /// Paginates over `base`, a query without a where clause such as `select * from category`.
+    /// Cursors are signed by `signer` and only accepted back with the same sort key
+    pub fn new(base: impl Into<String>, sort: SortKey, signer: &'f CursorSigner) -> Self {
+        let scope = format!(
+            "{}:{}",
+            sort.column,
+            if sort.descending { "desc" } else { "asc" }
+        );
+
+        Self {
+            base: base.into(),
+            sort,
+            filter: None,
+            signer,
+            scope: scope.into_bytes(),
+        }
+    }
+
+    /// Narrows down the rows. `filter` pushes conditions, each starting with ` and `. `scope`
+    /// describes the filter, cursors are only accepted back with the same one
+    pub fn filter(
+        mut self,
+        scope: &[u8],
+        filter: impl Fn(&mut QueryBuilder<'_, Postgres>) + Send + Sync + 'f,
+    ) -> Self {
+        self.scope.push(b':');
+        self.scope.extend_from_slice(scope);
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Reads the page described by `pagination`, returning at most `max` rows. A row more than
+    /// asked for is read to know whether another page follows, and when a cursor is given, the
+    /// rows on the other side of it are checked for a page in the opposite direction
+    pub async fn paginate<T>(
+        &self,
+        pool: &PgPool,
+        pagination: &Cursor,
+        max: i32,
+    ) -> Result<Connection<T>, PaginationError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Keyed + Send + Unpin,
+    {
+        let index = pagination.index.ok_or(PaginationError::MissingIndex)?;
+        let count = query_count(max, &index).max(0) as usize;
+        // paging back from the end reads the rows backwards
+        let from_end = matches!(index, Index::Last(_));
+
+        let cursor = match pagination
+            .cursor_value
+            .as_ref()
+            .and_then(|value| value.cursor_type.as_ref())
+        {
+            Some(cursor_type) => {
+                let cursor = CursorBuilder::decode(cursor_type, self.signer, &self.scope)?;
+                let key = KeyValue::decode(self.sort.kind, cursor.key())?;
+                let after = matches!(cursor_type, CursorType::After(_));
+                Some((key, cursor.id().to_string(), after))
+            }
+            None => None,
+        };
+
+        let mut query = self.query(&self.base);
+        if let Some((ref key, ref id, after)) = cursor {
+            self.push_boundary(&mut query, key, id, after, false);
+        }
+        self.push_order(&mut query, from_end);
+        query.push(" limit ");
+        query.push_bind(count as i64 + 1);
+
+        let mut rows: Vec<T> = query
+            .build_query_as()
+            .fetch_all(pool)
+            .instrument(debug_span!("pg.select.*"))
+            .await?;
+
+        let has_more = rows.len() > count;
+        rows.truncate(count);
+        if from_end {
+            rows.reverse();
+        }
+
+        // whether anything sits on the other side of the cursor, the cursor itself included
+        let behind_cursor = match cursor {
+            Some((ref key, ref id, after)) => {
+                let mut query = self.query(&format!("select exists({}", self.base));
+                self.push_boundary(&mut query, key, id, !after, true);
+                query.push(")");
+
+                query
+                    .build_query_scalar::<bool>()
+                    .fetch_one(pool)
+                    .instrument(debug_span!("pg.select.exists"))
+                    .await?
+            }
+            None => false,
+        };
+
+        let after = cursor.as_ref().map(|(_, _, after)| *after);
+        let (has_previous_page, has_next_page) = match (after, from_end) {
+            (None, false) => (false, has_more),
+            (None, true) => (has_more, false),
+            (Some(true), false) => (behind_cursor, has_more),
+            (Some(true), true) => (has_more || behind_cursor, false),
+            (Some(false), false) => (false, has_more || behind_cursor),
+            (Some(false), true) => (has_more, behind_cursor),
+        };
+
+        Ok(Connection {
+            edges: self.edges(rows)?,
+            page_info: PageInfo {
+                has_next_page,
+                has_previous_page,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Gives each row a cursor, so results read in other ways can be paginated from
+    pub fn edges<T: Keyed>(&self, rows: Vec<T>) -> Result<Vec<Edge<T>>, PaginationError> {
+        rows.into_iter()
+            .map(|node| {
+                let key = node
+                    .key(self.sort.column)
+                    .ok_or(PaginationError::UnknownSortKey(self.sort.column))?
+                    .encode()?;
+                Ok(Edge {
+                    cursor: CursorBuilder::new(node.id(), &key).encode(self.signer, &self.scope),
+                    node,
+                })
+            })
+            .collect()
+    }
+
+    /// Starts a query on `base` with the filter applied
+    pub fn query(&self, base: &str) -> QueryBuilder<'static, Postgres> {
+        let mut query = QueryBuilder::new(base);
+        query.push(" where true");
+        if let Some(ref filter) = self.filter {
+            filter(&mut query);
+        }
+        query
+    }
+
+    /// Orders the rows by the sort key, or the other way round when `reverse` is set
+    pub fn push_order(&self, query: &mut QueryBuilder<'_, Postgres>, reverse: bool) {
+        let direction = if self.sort.descending == reverse {
+            " asc"
+        } else {
+            " desc"
+        };
+
+        query.push(" order by ");
+        query.push(self.sort.column);
+        query.push(direction);
+        query.push(", id");
+        query.push(direction);
+    }
+
+    /// Keeps the rows on one side of a cursor. `after` is in the listing order
+    fn push_boundary(
+        &self,
+        query: &mut QueryBuilder<'_, Postgres>,
+        key: &KeyValue,
+        id: &str,
+        after: bool,
+        inclusive: bool,
+    ) {
+        let ascending = self.sort.descending != after;
+        let operator = match (ascending, inclusive) {
+            (true, false) => " > ",
+            (true, true) => " >= ",
+            (false, false) => " < ",
+            (false, true) => " <= ",
+        };
+
+        query.push(" and (");
+        query.push(self.sort.column);
+        query.push(", id)");
+        query.push(operator);
+        query.push("(");
+        key.push_bind(query);
+        query.push(", ");
+        query.push_bind(id.to_string());
+        query.push(")");
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    future::{Future, IntoFuture},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Tells every part of a service to stop, once the process is asked to terminate
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    timeout: Duration,
+}
+
+impl Shutdown {
+    /// A shutdown that only starts when triggered. In-flight work gets `timeout` to finish
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+            timeout,
+        }
+    }
+
+    /// A shutdown that starts on SIGTERM or SIGINT. In-flight work gets `timeout` to finish
+    pub fn listen(timeout: Duration) -> Self {
+        let shutdown = Self::new(timeout);
+
+        let trigger = shutdown.clone();
+        tokio::spawn(async move {
+            signal().await;
+            info!(timeout = ?trigger.timeout, "shutting down");
+            trigger.trigger();
+        });
+
+        shutdown
+    }
+
+    /// Starts the shutdown
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether the shutdown has started
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once the shutdown starts. New work should not be taken on from then on
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.sender.subscribe();
+        async move {
+            // the sender lives as long as the receiver does
+            let _ = receiver.wait_for(|triggered| *triggered).await;
+        }
+    }
+
+    /// How long in-flight work has to finish once the shutdown starts
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Runs `task` to completion, giving up on it when it is still running `timeout` after the
+    /// shutdown started
+    pub async fn drain<F: IntoFuture>(&self, task: F) -> Option<F::Output> {
+        let task = task.into_future();
+        tokio::pin!(task);
+
+        tokio::select! {
+            output = &mut task => return Some(output),
+            _ = self.triggered() => {}
+        }
+
+        match tokio::time::timeout(self.timeout, task).await {
+            Ok(output) => Some(output),
+            Err(_) => {
+                warn!(timeout = ?self.timeout, "in-flight work did not finish in time");
+                None
+            }
+        }
+    }
+}
+
+async fn signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!(error = %e, "cannot listen for SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!(error = %e, "cannot listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn finish_work_in_time() {
+        let shutdown = Shutdown::new(Duration::from_millis(200));
+        shutdown.trigger();
+        assert!(shutdown.is_triggered());
+
+        let output = shutdown
+            .drain(async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                1
+            })
+            .await;
+        assert_eq!(output, Some(1));
+    }
+
+    #[tokio::test]
+    async fn give_up_on_slow_work() {
+        let shutdown = Shutdown::new(Duration::from_millis(10));
+
+        let trigger = shutdown.clone();
+        tokio::spawn(async move { trigger.trigger() });
+
+        let output = shutdown.drain(std::future::pending::<()>()).await;
+        assert_eq!(output, None);
+    }
+}
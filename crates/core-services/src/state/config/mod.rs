@@ -58,6 +58,14 @@ pub struct Configuration {
     #[cfg_attr(docsrs, doc(cfg(feature = "sentry")))]
     /// Sentry dsn
     pub sentry_dsn: String,
+    /// Secret pagination cursors are signed with
+    #[cfg(feature = "pagination")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pagination")))]
+    pub cursor_secret: String,
+    /// How long in-flight work has to finish once the service is asked to stop
+    #[cfg(feature = "shutdown")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "shutdown")))]
+    pub shutdown_timeout: std::time::Duration,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -96,6 +104,15 @@ impl Configuration {
         #[cfg(feature = "sentry")]
         let sentry_dsn = env_var("SENTRY_DSN");
 
+        #[cfg(feature = "pagination")]
+        let cursor_secret = env_var("CURSOR_SECRET");
+
+        #[cfg(feature = "shutdown")]
+        let shutdown_timeout = env_var("SHUTDOWN_TIMEOUT")
+            .parse::<u64>()
+            .map(std::time::Duration::from_secs)
+            .expect("Unable to parse the value of the SHUTDOWN_TIMEOUT environment variable. Please make sure it is a number of seconds.");
+
         #[cfg(feature = "api")]
         let listen_address = SocketAddr::from((Ipv6Addr::UNSPECIFIED, app_port));
 
@@ -128,6 +145,10 @@ impl Configuration {
             otel_collector,
             #[cfg(feature = "sentry")]
             sentry_dsn,
+            #[cfg(feature = "pagination")]
+            cursor_secret,
+            #[cfg(feature = "shutdown")]
+            shutdown_timeout,
         }
     }
 
@@ -81,7 +81,7 @@ impl Display for Event {
                     format!("{entity}.update.index.delete.batch")
                 }
                 Event::CacheUpdateBatch(entity) => {
-                    format!("{entity}.update.set.single")
+                    format!("{entity}.update.set.batch")
                 }
             }
         )
@@ -0,0 +1,79 @@
+use std::{future::Future, time::Duration};
+
+use super::ServiceState;
+
+/// The outcome of probing a dependency
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// The dependency that was probed
+    pub name: &'static str,
+    /// Why the dependency could not be reached
+    pub error: Option<String>,
+}
+
+impl Check {
+    /// Whether the dependency answered
+    pub fn is_up(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl ServiceState {
+    /// Probes every dependency the state holds, giving up on each one after `timeout`
+    pub async fn probe(&self, timeout: Duration) -> Vec<Check> {
+        #[allow(unused_mut)]
+        let mut checks = vec![];
+
+        #[cfg(feature = "postgres")]
+        checks.push(
+            check("postgres", timeout, async {
+                sqlx::query("select 1").execute(&self.db_pool).await?;
+                Ok(())
+            })
+            .await,
+        );
+
+        #[cfg(feature = "cache")]
+        checks.push(
+            check("redis", timeout, async {
+                use crate::cache::{PoolLike, PooledConnectionLike};
+
+                let mut cache = self.cache.get().await?;
+                cache.query_async::<()>(redis::cmd("PING")).await?;
+                Ok(())
+            })
+            .await,
+        );
+
+        #[cfg(feature = "nats")]
+        checks.push(
+            check("jetstream", timeout, async {
+                self.jetstream_context.query_account().await?;
+                Ok(())
+            })
+            .await,
+        );
+
+        checks
+    }
+}
+
+#[allow(dead_code)]
+async fn check(
+    name: &'static str,
+    timeout: Duration,
+    probe: impl Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+) -> Check {
+    let error = match tokio::time::timeout(timeout, probe).await {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("no answer within {timeout:?}")),
+    };
+
+    #[cfg(feature = "tracing")]
+    if let Some(ref error) = error {
+        tracing::warn!(dependency = name, error, "dependency is down");
+    }
+
+    Check { name, error }
+}
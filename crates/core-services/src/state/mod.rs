@@ -7,6 +7,11 @@ pub mod error;
 /// Events
 pub mod events;
 
+/// Dependency probes
+#[cfg(feature = "health")]
+#[cfg_attr(docsrs, doc(cfg(feature = "health")))]
+pub mod health;
+
 /// Utils
 #[cfg(all(feature = "opentelemetry", feature = "nats"))]
 pub mod utils;
@@ -90,4 +95,12 @@ impl ServiceState {
             jetstream_context: jetstream,
         })
     }
+
+    /// Closes the database pool, waiting for connections in use to be returned. Cache connections
+    /// close as the last clone of the state is dropped, and JetStream publishes are acknowledged
+    /// as they are sent, so neither has anything to flush
+    pub async fn close(&self) {
+        #[cfg(feature = "postgres")]
+        self.db_pool.close().await;
+    }
 }
@@ -14,6 +14,12 @@ pub struct Telemetry {
     #[cfg(feature = "tracing-loki")]
     /// Loki [tracing_loki::BackgroundTask]
     pub loki_task: Option<tracing_loki::BackgroundTask>,
+    #[cfg(feature = "tracing-loki")]
+    loki_controller: Option<tracing_loki::BackgroundTaskController>,
+    #[cfg(feature = "tracing-loki")]
+    loki_handle: Option<tokio::task::JoinHandle<()>>,
+    #[cfg(feature = "opentelemetry")]
+    tracer_provider: Option<opentelemetry_sdk::trace::TracerProvider>,
     #[cfg(feature = "sentry")]
     _sentry_guard: Option<sentry::ClientInitGuard>,
 }
@@ -23,6 +29,43 @@ impl Telemetry {
     pub fn builder() -> TelemetryBuilder {
         TelemetryBuilder::default()
     }
+
+    #[cfg(feature = "tracing-loki")]
+    /// Starts shipping logs to Loki, unless [Telemetry::loki_task] was taken to run elsewhere
+    pub fn spawn_loki(&mut self) {
+        if let Some(task) = self.loki_task.take() {
+            self.loki_handle = Some(tokio::spawn(task));
+        }
+    }
+
+    /// Sends the spans, logs and events that are still buffered, giving each pipeline up to
+    /// `timeout`
+    #[allow(unused_variables)]
+    pub async fn shutdown(self, timeout: std::time::Duration) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(provider) = self.tracer_provider {
+            // waits on the batch processor, which runs on the runtime
+            let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+            if let Ok(Err(e)) = result {
+                tracing::warn!(error = %e, "could not flush spans");
+            }
+        }
+
+        #[cfg(feature = "tracing-loki")]
+        if let Some(controller) = self.loki_controller {
+            controller.shutdown().await;
+            if let Some(handle) = self.loki_handle {
+                if tokio::time::timeout(timeout, handle).await.is_err() {
+                    tracing::warn!(?timeout, "could not flush logs to loki in time");
+                }
+            }
+        }
+
+        #[cfg(feature = "sentry")]
+        if let Some(guard) = self._sentry_guard {
+            guard.close(Some(timeout));
+        }
+    }
 }
 
 /// A builder for initialising [tracing] layers
@@ -31,6 +74,10 @@ pub struct TelemetryBuilder {
     layer: Vec<Box<dyn Layer<Registry> + Sync + Send>>,
     #[cfg(feature = "tracing-loki")]
     loki_handle: Option<tracing_loki::BackgroundTask>,
+    #[cfg(feature = "tracing-loki")]
+    loki_controller: Option<tracing_loki::BackgroundTaskController>,
+    #[cfg(feature = "opentelemetry")]
+    tracer_provider: Option<opentelemetry_sdk::trace::TracerProvider>,
     #[cfg(feature = "sentry")]
     sentry_guard: Option<sentry::ClientInitGuard>,
 }
@@ -50,6 +97,10 @@ impl TelemetryBuilder {
             layer: vec![types],
             #[cfg(feature = "tracing-loki")]
             loki_handle: None,
+            #[cfg(feature = "tracing-loki")]
+            loki_controller: None,
+            #[cfg(feature = "opentelemetry")]
+            tracer_provider: None,
             #[cfg(feature = "sentry")]
             sentry_guard: None,
         }
@@ -66,10 +117,11 @@ impl TelemetryBuilder {
         for (key, value) in config.extra_fields.iter() {
             builder = builder.extra_field(key, value)?;
         }
-        let (layer, background_task) =
-            builder.build_url(tracing_loki::url::Url::parse(&config.host)?)?;
+        let (layer, controller, background_task) =
+            builder.build_controller_url(tracing_loki::url::Url::parse(&config.host)?)?;
 
         self.loki_handle = Some(background_task);
+        self.loki_controller = Some(controller);
 
         self.layer.push(layer.boxed());
         Ok(self)
@@ -147,6 +199,7 @@ impl TelemetryBuilder {
 
         global::set_tracer_provider(provider.clone());
         let tracer = provider.tracer(config.name.to_string());
+        self.tracer_provider = Some(provider);
 
         self.layer.push(OpenTelemetryLayer::new(tracer).boxed());
 
@@ -162,6 +215,12 @@ impl TelemetryBuilder {
         Telemetry {
             #[cfg(feature = "tracing-loki")]
             loki_task: self.loki_handle,
+            #[cfg(feature = "tracing-loki")]
+            loki_controller: self.loki_controller,
+            #[cfg(feature = "tracing-loki")]
+            loki_handle: None,
+            #[cfg(feature = "opentelemetry")]
+            tracer_provider: self.tracer_provider,
             #[cfg(feature = "sentry")]
             _sentry_guard: self.sentry_guard,
         }
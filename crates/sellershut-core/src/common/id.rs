@@ -13,11 +13,16 @@ pub fn generate_id() -> String {
     nanoid!(ID_LENGTH, &ID_ALPHABET)
 }
 
+/// Whether `id` could have been made by [generate_id], 21 characters from the ID alphabet
+pub fn is_valid_id(id: &str) -> bool {
+    id.chars().count() == ID_LENGTH && id.chars().all(|c| ID_ALPHABET.contains(&c))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::id::ID_LENGTH;
 
-    use super::generate_id;
+    use super::{generate_id, is_valid_id};
 
     fn check_in_id(character: char, expected_result: bool) -> String {
         let id = generate_id();
@@ -33,4 +38,12 @@ mod tests {
 
         assert_eq!(ID_LENGTH, id.len());
     }
+
+    #[test]
+    fn check_format() {
+        assert!(is_valid_id(&generate_id()));
+        assert!(!is_valid_id("too-short"));
+        assert!(!is_valid_id("does_not_exist_123456"));
+        assert!(!is_valid_id("DOES_NOT_EXIST_ABCDEF"));
+    }
 }
@@ -1,9 +1,9 @@
 /// Pagination
-#[cfg(any(feature = "categories"))]
+#[cfg(feature = "categories")]
 pub mod pagination;
 
 /// Utils
-#[cfg(any(feature = "categories"))]
+#[cfg(feature = "categories")]
 pub mod utils;
 
 #[cfg(feature = "id-gen")]
@@ -3,46 +3,142 @@ tonic::include_proto!("common.pagination");
 #[cfg(feature = "rpc-server-categories")]
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use cursor::Index;
+#[cfg(feature = "rpc-server-categories")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "rpc-server-categories")]
+use sha2::Sha256;
+
+/// The version of the cursor format, the first byte of every cursor
+#[cfg(feature = "rpc-server-categories")]
+pub const CURSOR_VERSION: u8 = 1;
+
+#[cfg(feature = "rpc-server-categories")]
+const SIGNATURE_LENGTH: usize = 32;
+
+/// Why a cursor was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg(feature = "rpc-server-categories")]
+pub enum CursorError {
+    /// The cursor cannot be read
+    Malformed,
+    /// The cursor was made with a format that is no longer read
+    UnsupportedVersion(u8),
+    /// The cursor was changed, or made for a query with different parameters
+    InvalidSignature,
+}
+
+#[cfg(feature = "rpc-server-categories")]
+impl std::fmt::Display for CursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CursorError::Malformed => write!(f, "cursor is malformed"),
+            CursorError::UnsupportedVersion(version) => write!(
+                f,
+                "cursor version {version} is no longer supported, start again from the first page"
+            ),
+            CursorError::InvalidSignature => write!(
+                f,
+                "cursor was not issued for this query, sort and filter must stay the same between pages"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "rpc-server-categories")]
+impl std::error::Error for CursorError {}
+
+/// Signs cursors so they cannot be forged, and checks them when they come back
+#[derive(Clone)]
+#[cfg(feature = "rpc-server-categories")]
+pub struct CursorSigner {
+    mac: Hmac<Sha256>,
+}
+
+#[cfg(feature = "rpc-server-categories")]
+impl std::fmt::Debug for CursorSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CursorSigner").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "rpc-server-categories")]
+impl CursorSigner {
+    /// Create a signer from a secret key
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            mac: Hmac::new_from_slice(secret).expect("hmac to take keys of any length"),
+        }
+    }
+
+    /// The signature covers the version, the scope the cursor was issued for and its payload
+    fn mac(&self, scope: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = self.mac.clone();
+        mac.update(&[CURSOR_VERSION]);
+        mac.update(&(scope.len() as u64).to_be_bytes());
+        mac.update(scope);
+        mac.update(payload);
+        mac
+    }
+}
 
-/// Pagination cursor
+/// Pagination cursor, the value of the active sort key and the id of the last item
 #[derive(Debug, PartialEq, Eq)]
 #[cfg(feature = "rpc-server-categories")]
 pub struct CursorBuilder {
     id: String,
-    dt: String,
+    key: String,
 }
 
 #[cfg(feature = "rpc-server-categories")]
 impl CursorBuilder {
-    /// Create cursor
-    pub fn new(id: &str, dt: &str) -> Self {
+    /// Create cursor, `key` is the value the results are sorted by, such as a date time or a name
+    pub fn new(id: &str, key: &str) -> Self {
         Self {
             id: id.to_string(),
-            dt: dt.to_string(),
+            key: key.to_string(),
         }
     }
-    /// decode a cursor
+
+    /// decode a cursor issued by `signer` for `scope`
     pub fn decode(
         params: &cursor::cursor_value::CursorType,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+        signer: &CursorSigner,
+        scope: &[u8],
+    ) -> Result<Self, CursorError> {
         let cursor = match params {
             cursor::cursor_value::CursorType::After(cursor) => cursor,
             cursor::cursor_value::CursorType::Before(cursor) => cursor,
         };
 
-        let bytes = BASE64_URL_SAFE_NO_PAD.decode(cursor)?;
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| CursorError::Malformed)?;
 
-        let decoded = String::from_utf8(bytes)?;
-
-        let mut tokens = decoded.split('|');
-        if let (Some(dt), Some(id)) = (tokens.next(), tokens.next()) {
-            Ok(Self {
-                id: id.to_string(),
-                dt: dt.parse()?,
-            })
-        } else {
-            Err("missing tokens".into())
+        let (version, rest) = bytes.split_first().ok_or(CursorError::Malformed)?;
+        if *version != CURSOR_VERSION {
+            return Err(CursorError::UnsupportedVersion(*version));
         }
+
+        let payload_length = rest
+            .len()
+            .checked_sub(SIGNATURE_LENGTH)
+            .ok_or(CursorError::Malformed)?;
+        let (payload, signature) = rest.split_at(payload_length);
+
+        signer
+            .mac(scope, payload)
+            .verify_slice(signature)
+            .map_err(|_| CursorError::InvalidSignature)?;
+
+        let decoded = std::str::from_utf8(payload).map_err(|_| CursorError::Malformed)?;
+
+        // ids never contain the separator, sort keys such as names can
+        let (key, id) = decoded.rsplit_once('|').ok_or(CursorError::Malformed)?;
+
+        Ok(Self {
+            id: id.to_string(),
+            key: key.to_string(),
+        })
     }
 
     /// get id
@@ -50,14 +146,26 @@ impl CursorBuilder {
         &self.id
     }
 
-    /// get date time
-    pub fn dt(&self) -> &str {
-        &self.dt
+    /// get the sort key
+    pub fn key(&self) -> &str {
+        &self.key
     }
 
-    /// encode a cursor
-    pub fn encode(&self) -> String {
-        BASE64_URL_SAFE_NO_PAD.encode(format!("{}|{}", self.dt, self.id))
+    /// encode a cursor, signed by `signer` for `scope`. `scope` describes the query the cursor
+    /// belongs to, such as its sort and filter, so it cannot be used with another
+    pub fn encode(&self, signer: &CursorSigner, scope: &[u8]) -> String {
+        let payload = format!("{}|{}", self.key, self.id);
+        let signature = signer
+            .mac(scope, payload.as_bytes())
+            .finalize()
+            .into_bytes();
+
+        let mut bytes = Vec::with_capacity(1 + payload.len() + SIGNATURE_LENGTH);
+        bytes.push(CURSOR_VERSION);
+        bytes.extend_from_slice(payload.as_bytes());
+        bytes.extend_from_slice(&signature);
+
+        BASE64_URL_SAFE_NO_PAD.encode(bytes)
     }
 
     /// Gets pagination direction
@@ -109,18 +217,80 @@ pub fn query_count(max: i32, pagination: &Index) -> i32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{cursor::cursor_value::CursorType, CursorBuilder};
+    use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+
+    use super::{cursor::cursor_value::CursorType, CursorBuilder, CursorError, CursorSigner};
+
+    fn signer() -> CursorSigner {
+        CursorSigner::new(b"secret")
+    }
 
     #[test]
     fn test_cursor() {
         let cursor =
             CursorBuilder::new("9ckyrhcx6jun6n_7a8adq", "2023-01-11T11:32:07.853915+00:00");
 
-        let encode = cursor.encode();
+        let encode = cursor.encode(&signer(), b"created_at");
 
         let cursor_type = CursorType::After(encode);
-        let decode = CursorBuilder::decode(&cursor_type).unwrap();
+        let decode = CursorBuilder::decode(&cursor_type, &signer(), b"created_at").unwrap();
 
         assert_eq!(decode, cursor);
     }
+
+    #[test]
+    fn test_cursor_name_key() {
+        let cursor = CursorBuilder::new("9ckyrhcx6jun6n_7a8adq", "Shoes | Boots");
+
+        let cursor_type = CursorType::Before(cursor.encode(&signer(), b"name"));
+        let decode = CursorBuilder::decode(&cursor_type, &signer(), b"name").unwrap();
+
+        assert_eq!(decode.key(), "Shoes | Boots");
+        assert_eq!(decode.id(), "9ckyrhcx6jun6n_7a8adq");
+    }
+
+    #[test]
+    fn test_cursor_rejected() {
+        let cursor = CursorBuilder::new("9ckyrhcx6jun6n_7a8adq", "Shoes");
+        let encoded = cursor.encode(&signer(), b"name");
+        let decode = |value: String, signer: &CursorSigner, scope: &[u8]| {
+            CursorBuilder::decode(&CursorType::After(value), signer, scope)
+        };
+
+        // another query
+        assert_eq!(
+            decode(encoded.clone(), &signer(), b"name desc"),
+            Err(CursorError::InvalidSignature)
+        );
+
+        // another key
+        assert_eq!(
+            decode(encoded.clone(), &CursorSigner::new(b"other"), b"name"),
+            Err(CursorError::InvalidSignature)
+        );
+
+        // a forged sort key
+        let mut bytes = BASE64_URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+        bytes[1] = b'T';
+        assert_eq!(
+            decode(BASE64_URL_SAFE_NO_PAD.encode(&bytes), &signer(), b"name"),
+            Err(CursorError::InvalidSignature)
+        );
+
+        // an older format
+        bytes[0] = 0;
+        assert_eq!(
+            decode(BASE64_URL_SAFE_NO_PAD.encode(&bytes), &signer(), b"name"),
+            Err(CursorError::UnsupportedVersion(0))
+        );
+
+        // the unsigned format
+        let unsigned = BASE64_URL_SAFE_NO_PAD.encode("Shoes|9ckyrhcx6jun6n_7a8adq");
+        assert!(decode(unsigned, &signer(), b"name").is_err());
+
+        assert_eq!(
+            decode(String::from("not a cursor!"), &signer(), b"name"),
+            Err(CursorError::Malformed)
+        );
+    }
 }
@@ -1,6 +1,6 @@
 // run the server first
-use sellershut_core::{
-    categories::query_categories_client::QueryCategoriesClient, common::pagination::Cursor,
+use sellershut_core::categories::{
+    query_categories_client::QueryCategoriesClient, GetCategoriesRequest,
 };
 use tonic::IntoRequest;
 
@@ -8,9 +8,9 @@ use tonic::IntoRequest;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut client = QueryCategoriesClient::connect("http://[::1]:50051").await?;
 
-    let cursor = Cursor::default();
+    let request = GetCategoriesRequest::default();
 
-    let response = client.categories(cursor.into_request()).await?;
+    let response = client.categories(request.into_request()).await?;
 
     println!("response={response:?}");
 
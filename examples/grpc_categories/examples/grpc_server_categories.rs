@@ -1,9 +1,10 @@
-use sellershut_core::{
-    categories::{
-        query_categories_server::{QueryCategories, QueryCategoriesServer},
-        Category, Connection, GetCategoryRequest, GetSubCategoriesRequest,
-    },
-    common::pagination::Cursor,
+use sellershut_core::categories::{
+    query_categories_server::{QueryCategories, QueryCategoriesServer},
+    Category, CategoryAttributeList, CategoryList, CategoryRevisionConnection,
+    CategoryTranslationList, CategoryTreeResponse, Connection, GetCategoriesRequest,
+    GetCategoryAttributesRequest, GetCategoryByPathRequest, GetCategoryBySlugRequest,
+    GetCategoryRequest, GetCategoryRevisionsRequest, GetCategoryTreeRequest,
+    GetSubCategoriesRequest,
 };
 use tonic::transport::Server;
 
@@ -12,18 +13,16 @@ pub struct CategoryService;
 
 #[tonic::async_trait]
 impl QueryCategories for CategoryService {
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     async fn categories(
         &self,
-        request: tonic::Request<Cursor>,
+        request: tonic::Request<GetCategoriesRequest>,
     ) -> Result<tonic::Response<Connection>, tonic::Status> {
         println!("handling categories request {request:?}");
 
         Ok(tonic::Response::new(Connection::default()))
     }
 
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     async fn category_by_id(
         &self,
@@ -34,7 +33,26 @@ impl QueryCategories for CategoryService {
         Ok(tonic::Response::new(Category::default()))
     }
 
-    #[must_use]
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_by_slug(
+        &self,
+        request: tonic::Request<GetCategoryBySlugRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        println!("handling category_by_slug request {request:?}");
+
+        Ok(tonic::Response::new(Category::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_by_path(
+        &self,
+        request: tonic::Request<GetCategoryByPathRequest>,
+    ) -> Result<tonic::Response<Category>, tonic::Status> {
+        println!("handling category_by_path request {request:?}");
+
+        Ok(tonic::Response::new(Category::default()))
+    }
+
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     async fn sub_categories(
         &self,
@@ -44,6 +62,66 @@ impl QueryCategories for CategoryService {
 
         Ok(tonic::Response::new(Connection::default()))
     }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_tree(
+        &self,
+        request: tonic::Request<GetCategoryTreeRequest>,
+    ) -> Result<tonic::Response<CategoryTreeResponse>, tonic::Status> {
+        println!("handling category_tree request {request:?}");
+
+        Ok(tonic::Response::new(CategoryTreeResponse::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_ancestors(
+        &self,
+        request: tonic::Request<GetCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryList>, tonic::Status> {
+        println!("handling category_ancestors request {request:?}");
+
+        Ok(tonic::Response::new(CategoryList::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_translations(
+        &self,
+        request: tonic::Request<GetCategoryRequest>,
+    ) -> Result<tonic::Response<CategoryTranslationList>, tonic::Status> {
+        println!("handling category_translations request {request:?}");
+
+        Ok(tonic::Response::new(CategoryTranslationList::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_attributes(
+        &self,
+        request: tonic::Request<GetCategoryAttributesRequest>,
+    ) -> Result<tonic::Response<CategoryAttributeList>, tonic::Status> {
+        println!("handling category_attributes request {request:?}");
+
+        Ok(tonic::Response::new(CategoryAttributeList::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn resolved_attributes(
+        &self,
+        request: tonic::Request<GetCategoryAttributesRequest>,
+    ) -> Result<tonic::Response<CategoryAttributeList>, tonic::Status> {
+        println!("handling resolved_attributes request {request:?}");
+
+        Ok(tonic::Response::new(CategoryAttributeList::default()))
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    async fn category_revisions(
+        &self,
+        request: tonic::Request<GetCategoryRevisionsRequest>,
+    ) -> Result<tonic::Response<CategoryRevisionConnection>, tonic::Status> {
+        println!("handling category_revisions request {request:?}");
+
+        Ok(tonic::Response::new(CategoryRevisionConnection::default()))
+    }
 }
 
 #[tokio::main]